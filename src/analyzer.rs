@@ -1,80 +1,410 @@
-use std::collections::HashMap;
-use std::fs::{read_dir, read_to_string};
-use crate::meta_lang::parse_entities;
-
-pub fn analyze_corpus(path: &str) {
-    let mut comp_count: HashMap<String, usize> = HashMap::new();
-    let mut event_count: HashMap<String, usize> = HashMap::new();
-    let mut func_count: HashMap<String, usize> = HashMap::new();
-    let mut total_files = 0usize;
-
-    if let Ok(entries) = read_dir(path) {
-        for ent in entries.flatten() {
-            let p = ent.path();
-            if p.is_file() {
-                if let Some(ext) = p.extension() {
-                    if ext == "meta" {
-                        total_files += 1;
-                        if let Ok(s) = read_to_string(&p) {
-                            let entities = parse_entities(&s);
-                            for e in entities {
-                                for c in e.components {
-                                    *comp_count.entry(c).or_default() += 1;
-                                }
-                                for ev in e.events {
-                                    *event_count.entry(ev.name.clone()).or_default() += 1;
-                                    // scan body for function-like tokens (foo(...))
-                                    let mut i = 0usize;
-                                    let b = ev.body.as_bytes();
-                                    while i + 1 < b.len() {
-                                        // find identifier followed by '('
-                                        if is_ident_start(b[i]) {
-                                            let start = i;
-                                            i += 1;
-                                            while i < b.len() && is_ident_continue(b[i]) { i += 1; }
-                                            // skip spaces
-                                            while i < b.len() && b[i].is_ascii_whitespace() { i += 1; }
-                                            if i < b.len() && b[i] == b'(' {
-                                                let name = String::from_utf8_lossy(&b[start..i]).to_string();
-                                                *func_count.entry(name).or_default() += 1;
-                                            }
-                                        } else {
-                                            i += 1;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use crate::ast::{Expr, Stmt, parse_statements};
+use crate::builtins::{CALL_COST, builtin};
+use crate::corpus::{CorpusFile, CorpusOptions, load_corpus};
+use crate::meta_lang::Function;
+
+/// Everything the analyzer measured over a corpus. Maps are ordered by name so serialized
+/// reports are stable.
+#[derive(Debug, Clone, Default)]
+pub struct CorpusStats {
+    pub files: Vec<FileStats>,
+    pub entities: Vec<EntityStats>,
+    pub events: Vec<EventStats>,
+    /// Entities declaring each component.
+    pub components: BTreeMap<String, usize>,
+    /// Handlers per event name.
+    pub event_names: BTreeMap<String, usize>,
+    /// Call sites per called name, over handlers and `function`s.
+    pub calls: BTreeMap<String, usize>,
+    pub call_graph: Vec<CallEdge>,
+    /// Entities declaring both components, for every pair that occurs (`a < b`).
+    pub co_occurrence: BTreeMap<(String, String), usize>,
+    /// Files that could not be read or parsed.
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileStats {
+    pub path: String,
+    pub entities: usize,
+    pub events: usize,
+    pub functions: usize,
+    pub calls: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct EntityStats {
+    pub file: String,
+    pub name: String,
+    pub components: Vec<String>,
+    pub events: Vec<String>,
+    pub calls: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventStats {
+    pub file: String,
+    pub entity: String,
+    pub event: String,
+    pub params: Option<String>,
+    /// Calls in the body with their counts, in order of first appearance.
+    pub calls: Vec<(String, usize)>,
+    pub metrics: HandlerMetrics,
+}
+
+/// Size and cost of one handler body.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HandlerMetrics {
+    /// Statements including those nested in `if` bodies.
+    pub statements: usize,
+    /// Deepest `if` nesting; a flat body is 0.
+    pub depth: usize,
+    /// 1 + branches (`if`s and `&&`/`||` in their conditions).
+    pub cyclomatic: usize,
+    /// Estimated instructions with every branch taken: builtins by their table cost,
+    /// `function`s by call overhead plus their own body.
+    pub cost: u32,
+}
+
+/// `from` calls `to` `count` times. Handlers are named `Entity.Event`, functions by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallEdge {
+    pub file: String,
+    pub from: String,
+    pub to: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            other => Err(format!("unknown report format `{}` (expected text, json or csv)", other)),
+        }
+    }
+}
+
+pub fn analyze_corpus(path: &str) -> CorpusStats {
+    analyze_corpus_with(path, &CorpusOptions::default())
+}
+
+pub fn analyze_corpus_with(path: &str, opts: &CorpusOptions) -> CorpusStats {
+    let corpus = load_corpus(path, opts);
+    let mut stats = analyze_files(&corpus.files);
+    stats.errors = corpus.errors.iter().map(|e| e.to_string()).collect();
+    stats
+}
+
+/// Statistics of already loaded files, for tools that need the sources as well.
+pub fn analyze_files(files: &[CorpusFile]) -> CorpusStats {
+    let mut stats = CorpusStats::default();
+    for file in files {
+        let mut fs = FileStats { path: file.relative.clone(), entities: file.entities.len(), events: 0, functions: file.functions.len(), calls: 0 };
+        for e in &file.entities {
+            for c in &e.components {
+                *stats.components.entry(c.clone()).or_default() += 1;
+            }
+            let mut comps = e.components.clone();
+            comps.sort();
+            comps.dedup();
+            for (i, a) in comps.iter().enumerate() {
+                for b in &comps[i + 1..] {
+                    *stats.co_occurrence.entry((a.clone(), b.clone())).or_default() += 1;
                 }
             }
+            let mut entity_calls = 0;
+            for ev in &e.events {
+                *stats.event_names.entry(ev.name.clone()).or_default() += 1;
+                let calls = count_calls(&ev.body);
+                let from = format!("{}.{}", e.name, ev.name);
+                entity_calls += record_calls(&mut stats, &file.relative, &from, &calls);
+                stats.events.push(EventStats {
+                    file: file.relative.clone(),
+                    entity: e.name.clone(),
+                    event: ev.name.clone(),
+                    params: ev.params.clone(),
+                    calls,
+                    metrics: handler_metrics(&ev.body, &file.functions),
+                });
+            }
+            fs.events += e.events.len();
+            fs.calls += entity_calls;
+            stats.entities.push(EntityStats {
+                file: file.relative.clone(),
+                name: e.name.clone(),
+                components: e.components.clone(),
+                events: e.events.iter().map(|ev| ev.name.clone()).collect(),
+                calls: entity_calls,
+            });
+        }
+        for f in &file.functions {
+            let calls = count_calls(&f.body);
+            fs.calls += record_calls(&mut stats, &file.relative, &f.name, &calls);
+        }
+        stats.files.push(fs);
+    }
+    stats
+}
+
+fn record_calls(stats: &mut CorpusStats, file: &str, from: &str, calls: &[(String, usize)]) -> usize {
+    let mut total = 0;
+    for (name, n) in calls {
+        *stats.calls.entry(name.clone()).or_default() += n;
+        stats.call_graph.push(CallEdge { file: file.to_string(), from: from.to_string(), to: name.clone(), count: *n });
+        total += n;
+    }
+    total
+}
+
+pub fn handler_metrics(body: &str, functions: &[Function]) -> HandlerMetrics {
+    let stmts = parse_statements(body);
+    let mut m = HandlerMetrics { cyclomatic: 1, ..HandlerMetrics::default() };
+    measure(&stmts, 0, &mut m);
+    m.cost = block_cost(&stmts, functions, &mut Vec::new());
+    m
+}
+
+fn measure(stmts: &[Stmt], depth: usize, m: &mut HandlerMetrics) {
+    m.depth = m.depth.max(depth);
+    for st in stmts {
+        match st {
+            Stmt::Empty => {}
+            Stmt::Expr(_) => m.statements += 1,
+            Stmt::If { cond, body } => {
+                m.statements += 1;
+                let cond = crate::ast::expr_to_string(cond);
+                m.cyclomatic += 1 + cond.matches("&&").count() + cond.matches("||").count();
+                measure(body, depth + 1, m);
+            }
         }
     }
+}
 
-    println!("Analyzed {} files", total_files);
-    println!("Top components:");
-    for (k,v) in top_n(&comp_count, 10) { println!("  {} => {}", k, v); }
-    println!("Top events:");
-    for (k,v) in top_n(&event_count, 10) { println!("  {} => {}", k, v); }
-    println!("Top function calls in bodies:");
-    for (k,v) in top_n(&func_count, 20) { println!("  {} => {}", k, v); }
+/// `stack` holds the functions being costed, so recursion is charged only the call overhead.
+fn block_cost(stmts: &[Stmt], functions: &[Function], stack: &mut Vec<String>) -> u32 {
+    stmts.iter().map(|st| match st {
+        Stmt::Empty => 0,
+        Stmt::Expr(Expr::Call { name, .. }) => call_cost(name, functions, stack),
+        Stmt::Expr(_) => 1,
+        Stmt::If { body, .. } => 1 + block_cost(body, functions, stack),
+    }).sum()
 }
 
-fn is_ident_start(b: u8) -> bool { (b'A' <= b && b <= b'Z') || (b'a' <= b && b <= b'z') || b == b'_' }
-fn is_ident_continue(b: u8) -> bool { is_ident_start(b) || (b'0' <= b && b <= b'9') }
+fn call_cost(name: &str, functions: &[Function], stack: &mut Vec<String>) -> u32 {
+    if let Some(b) = builtin(name) { return b.cost; }
+    let Some(f) = functions.iter().find(|f| f.name == name) else { return CALL_COST };
+    if stack.iter().any(|s| s == name) { return CALL_COST; }
+    stack.push(name.to_string());
+    let cost = CALL_COST + block_cost(&parse_statements(&f.body), functions, stack);
+    stack.pop();
+    cost
+}
 
-fn top_n(map: &HashMap<String, usize>, n: usize) -> Vec<(String, usize)> {
+/// Function-like tokens (`foo(...)`) in a body, with counts, in order of first appearance.
+fn count_calls(body: &str) -> Vec<(String, usize)> {
+    let mut calls: Vec<(String, usize)> = Vec::new();
+    let mut i = 0usize;
+    let b = body.as_bytes();
+    while i + 1 < b.len() {
+        // find identifier followed by '('
+        if is_ident_start(b[i]) {
+            let start = i;
+            i += 1;
+            while i < b.len() && is_ident_continue(b[i]) { i += 1; }
+            // skip spaces
+            while i < b.len() && b[i].is_ascii_whitespace() { i += 1; }
+            if i < b.len() && b[i] == b'(' {
+                let name = String::from_utf8_lossy(&b[start..i]).trim().to_string();
+                // `if (...)` is not a call
+                if name == "if" { continue; }
+                match calls.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, c)) => *c += 1,
+                    None => calls.push((name, 1)),
+                }
+            }
+        } else {
+            i += 1;
+        }
+    }
+    calls
+}
+
+fn is_ident_start(b: u8) -> bool { b.is_ascii_alphabetic() || b == b'_' }
+fn is_ident_continue(b: u8) -> bool { is_ident_start(b) || b.is_ascii_digit() }
+
+fn top_n(map: &BTreeMap<String, usize>, n: usize) -> Vec<(String, usize)> {
     let mut v: Vec<_> = map.iter().map(|(k,&c)| (k.clone(), c)).collect();
-    v.sort_by(|a,b| b.1.cmp(&a.1));
+    v.sort_by_key(|b| std::cmp::Reverse(b.1));
     v.into_iter().take(n).collect()
 }
 
+impl HandlerMetrics {
+    /// Named values, in the order reports list them.
+    pub fn fields(&self) -> [(&'static str, usize); 4] {
+        [("statements", self.statements), ("depth", self.depth), ("cyclomatic", self.cyclomatic), ("cost", self.cost as usize)]
+    }
+}
+
+impl CorpusStats {
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_string(),
+            ReportFormat::Json => self.to_json(),
+            ReportFormat::Csv => self.to_csv(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut o = String::from("{\n");
+        let files: Vec<String> = self.files.iter().map(|f| format!(
+            "{{\"path\":{},\"entities\":{},\"events\":{},\"functions\":{},\"calls\":{}}}",
+            json_str(&f.path), f.entities, f.events, f.functions, f.calls)).collect();
+        let entities: Vec<String> = self.entities.iter().map(|e| format!(
+            "{{\"file\":{},\"name\":{},\"components\":{},\"events\":{},\"calls\":{}}}",
+            json_str(&e.file), json_str(&e.name), json_list(&e.components), json_list(&e.events), e.calls)).collect();
+        let events: Vec<String> = self.events.iter().map(|e| format!(
+            "{{\"file\":{},\"entity\":{},\"event\":{},\"params\":{},\"calls\":{},\"metrics\":{}}}",
+            json_str(&e.file), json_str(&e.entity), json_str(&e.event),
+            e.params.as_deref().map(json_str).unwrap_or_else(|| "null".to_string()),
+            json_counts(e.calls.iter().map(|(k, v)| (k.as_str(), *v))),
+            json_counts(e.metrics.fields().into_iter()))).collect();
+        let graph: Vec<String> = self.call_graph.iter().map(|c| format!(
+            "{{\"file\":{},\"from\":{},\"to\":{},\"count\":{}}}",
+            json_str(&c.file), json_str(&c.from), json_str(&c.to), c.count)).collect();
+        let co: Vec<String> = self.co_occurrence.iter().map(|((a, b), n)| format!(
+            "{{\"a\":{},\"b\":{},\"count\":{}}}", json_str(a), json_str(b), n)).collect();
+        let _ = writeln!(o, "  \"files\": [{}],", files.join(","));
+        let _ = writeln!(o, "  \"entities\": [{}],", entities.join(","));
+        let _ = writeln!(o, "  \"events\": [{}],", events.join(","));
+        let _ = writeln!(o, "  \"components\": {},", json_counts(self.components.iter().map(|(k, v)| (k.as_str(), *v))));
+        let _ = writeln!(o, "  \"event_names\": {},", json_counts(self.event_names.iter().map(|(k, v)| (k.as_str(), *v))));
+        let _ = writeln!(o, "  \"calls\": {},", json_counts(self.calls.iter().map(|(k, v)| (k.as_str(), *v))));
+        let _ = writeln!(o, "  \"call_graph\": [{}],", graph.join(","));
+        let _ = writeln!(o, "  \"co_occurrence\": [{}],", co.join(","));
+        let _ = writeln!(o, "  \"errors\": {}", json_list(&self.errors));
+        o.push_str("}\n");
+        o
+    }
+
+    /// One long table (`kind,file,entity,event,name,count`) so every breakdown fits one CSV.
+    pub fn to_csv(&self) -> String {
+        let mut o = String::from("kind,file,entity,event,name,count\n");
+        let mut row = |kind: &str, file: &str, entity: &str, event: &str, name: &str, count: usize| {
+            let cells: Vec<String> = [kind, file, entity, event, name].iter().map(|c| csv_cell(c)).collect();
+            let _ = writeln!(o, "{},{}", cells.join(","), count);
+        };
+        for f in &self.files {
+            row("file_entities", &f.path, "", "", "", f.entities);
+            row("file_events", &f.path, "", "", "", f.events);
+            row("file_calls", &f.path, "", "", "", f.calls);
+        }
+        for e in &self.entities {
+            for c in &e.components { row("entity_component", &e.file, &e.name, "", c, 1); }
+            row("entity_calls", &e.file, &e.name, "", "", e.calls);
+        }
+        for e in &self.events {
+            for (name, n) in &e.calls { row("event_call", &e.file, &e.entity, &e.event, name, *n); }
+            for (name, n) in e.metrics.fields() { row("event_metric", &e.file, &e.entity, &e.event, name, n); }
+        }
+        for (k, v) in &self.components { row("component", "", "", "", k, *v); }
+        for (k, v) in &self.event_names { row("event", "", "", "", k, *v); }
+        for (k, v) in &self.calls { row("call", "", "", "", k, *v); }
+        for c in &self.call_graph { row("call_edge", &c.file, &c.from, "", &c.to, c.count); }
+        for ((a, b), n) in &self.co_occurrence { row("co_occurrence", "", a, "", b, *n); }
+        for e in &self.errors { row("error", "", "", "", e, 1); }
+        o
+    }
+}
+
+/// Human-readable top lists, as the analyzer always printed.
+impl std::fmt::Display for CorpusStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Analyzed {} files", self.files.len())?;
+        for e in &self.errors { writeln!(f, "  skipped {}", e)?; }
+        writeln!(f, "Top components:")?;
+        for (k,v) in top_n(&self.components, 10) { writeln!(f, "  {} => {}", k, v)?; }
+        writeln!(f, "Top events:")?;
+        for (k,v) in top_n(&self.event_names, 10) { writeln!(f, "  {} => {}", k, v)?; }
+        writeln!(f, "Top function calls in bodies:")?;
+        for (k,v) in top_n(&self.calls, 20) { writeln!(f, "  {} => {}", k, v)?; }
+        Ok(())
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut o = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => o.push_str("\\\""),
+            '\\' => o.push_str("\\\\"),
+            '\n' => o.push_str("\\n"),
+            '\t' => o.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(o, "\\u{:04x}", c as u32); }
+            c => o.push(c),
+        }
+    }
+    o.push('"');
+    o
+}
+
+fn json_list(items: &[String]) -> String {
+    format!("[{}]", items.iter().map(|s| json_str(s)).collect::<Vec<_>>().join(","))
+}
+
+fn json_counts<'a>(items: impl Iterator<Item = (&'a str, usize)>) -> String {
+    format!("{{{}}}", items.map(|(k, v)| format!("{}:{}", json_str(k), v)).collect::<Vec<_>>().join(","))
+}
+
+fn csv_cell(s: &str) -> String {
+    if s.contains([',', '"', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn smoke() {
-        analyze_corpus("corpus");
+        let stats = analyze_corpus("corpus");
+        assert_eq!(stats.files.len(), 3);
+        assert!(stats.components["Transform"] > 0);
+        assert!(stats.call_graph.iter().any(|c| c.from == "Player.Update" && c.to == "move"));
+        assert!(!stats.calls.contains_key("if"));
+    }
+
+    #[test]
+    fn handler_metrics_count_branches_and_inline_function_costs() {
+        let functions = crate::meta_lang::parse_functions("function hit(n) { takeDamage(n); collide(); }");
+        let m = handler_metrics("move(1); if (a && b) { hit(2); if (c) { move(3); } }", &functions);
+        assert_eq!((m.statements, m.depth, m.cyclomatic), (5, 2, 4));
+        // move 4 + if 1 + (call 2 + takeDamage 2 + collide 8) + if 1 + move 4
+        assert_eq!(m.cost, 22);
+    }
+
+    #[test]
+    fn serializers_escape_and_cover_every_breakdown() {
+        let mut stats = CorpusStats::default();
+        stats.components.insert("Trans\"form".to_string(), 2);
+        stats.co_occurrence.insert(("Physics".to_string(), "Transform".to_string()), 1);
+        stats.errors.push("a,b.meta: parse error".to_string());
+        let json = stats.to_json();
+        assert!(json.contains("\"components\": {\"Trans\\\"form\":2}"));
+        assert!(json.contains("{\"a\":\"Physics\",\"b\":\"Transform\",\"count\":1}"));
+        let csv = stats.to_csv();
+        assert!(csv.contains("component,,,,\"Trans\"\"form\",2\n"));
+        assert!(csv.contains("error,,,,\"a,b.meta: parse error\",1\n"));
     }
 }
@@ -1,5 +1,6 @@
 use std::fs::{create_dir_all, write};
 use std::process::Command;
+use crate::runtime::{split_guard, tag_guard};
 
 /// Generate a small Rust binary crate under out_dir 'aot_bin' that implements AOT functions
 /// for given hot sequences (seq, helper_name) and runs simple micro-benchmarks.
@@ -67,3 +68,133 @@ opt-level = 3
         println!("AOT binary not found at {}", exe);
     }
 }
+
+/// Input for one AOT-compiled handler run: initial entity state and the event parameters
+/// the executor can observe (`dt` as a float, entity snapshots by tag).
+#[derive(Debug, Clone)]
+pub struct AotCase {
+    pub handler: usize,
+    pub health: i32,
+    pub velocity: f64,
+    pub position: f64,
+    pub dt: Option<f64>,
+    pub tags: Vec<(String, String)>,
+}
+
+/// Result of one AOT run: final state and the effects, encoded as `M <dist>`, `C`, `D <amount>`
+/// or `U <stmt>` (statement text with whitespace removed).
+#[derive(Debug, Clone)]
+pub struct AotOutcome {
+    pub health: i32,
+    pub velocity: f64,
+    pub position: f64,
+    pub effects: Vec<String>,
+}
+
+/// Compile a handler body into a Rust function `fn <fn_name>(s: &mut State, p: &Params)`.
+/// The body structure is resolved at generation time, mirroring `runtime::execute_event`.
+pub fn compile_handler(fn_name: &str, body: &str) -> String {
+    let mut code = format!("fn {}(s: &mut State, p: &Params) {{\n", fn_name);
+    let (guard, stmts) = split_guard(body);
+    let indent = match guard {
+        Some(cond) => {
+            match tag_guard(cond) {
+                Some((var, tag)) => code.push_str(&format!("    if p.tag({:?}) == Some({:?}) {{\n", var, tag)),
+                None => code.push_str("    if false {\n"),
+            }
+            "        "
+        }
+        None => "    ",
+    };
+    for stmt in stmts.split(';') {
+        let s = stmt.trim();
+        if s.is_empty() { continue; }
+        if s.starts_with("move(") {
+            if s.contains("velocity * dt") {
+                code.push_str(&format!("{}if let Some(dt) = p.dt {{ let dist = s.velocity * dt; s.position += dist; s.effects.push(format!(\"M {{:?}}\", dist)); }}\n", indent));
+            }
+        } else if s.starts_with("collide(") || s == "collide()" {
+            code.push_str(&format!("{}s.effects.push(\"C\".to_string());\n", indent));
+        } else if s == "queueFree()" {
+            code.push_str(&format!("{}s.effects.push(\"F\".to_string());\n", indent));
+        } else if s.starts_with("takeDamage(") {
+            if let (Some(open), Some(close)) = (s.find('('), s.find(')')) {
+                if let Ok(v) = s[open+1..close].trim().trim_matches('"').parse::<i32>() {
+                    code.push_str(&format!("{}s.health -= {}; s.effects.push(\"D {}\".to_string());\n", indent, v, v));
+                }
+            }
+        } else {
+            let text: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+            code.push_str(&format!("{}s.effects.push({:?}.to_string());\n", indent, format!("U {}", text)));
+        }
+    }
+    if guard.is_some() { code.push_str("    }\n"); }
+    code.push_str("}\n\n");
+    code
+}
+
+/// Generate a crate under `out_dir` with every handler AOT-compiled, build it in release mode,
+/// run all `cases` and collect their outcomes in order.
+pub fn run_aot_cases(out_dir: &str, handlers: &[String], cases: &[AotCase]) -> Result<Vec<AotOutcome>, String> {
+    create_dir_all(format!("{}/src", out_dir)).map_err(|e| format!("cannot create {}: {}", out_dir, e))?;
+    let cargo = "[package]\nname = \"aot_cases\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n";
+    write(format!("{}/Cargo.toml", out_dir), cargo).map_err(|e| e.to_string())?;
+
+    let mut code = String::from("#![allow(unused)]\n\n");
+    code.push_str("struct State { health: i32, velocity: f64, position: f64, effects: Vec<String> }\n");
+    code.push_str("struct Params { dt: Option<f64>, tags: Vec<(&'static str, &'static str)> }\n\n");
+    code.push_str("impl Params {\n    fn tag(&self, var: &str) -> Option<&'static str> { self.tags.iter().find(|(v, _)| *v == var).map(|(_, t)| *t) }\n}\n\n");
+    for (idx, body) in handlers.iter().enumerate() {
+        code.push_str(&compile_handler(&format!("handler_{}", idx), body));
+    }
+    code.push_str("fn report(s: &State) {\n    println!(\"state {} {:?} {:?}\", s.health, s.velocity, s.position);\n    for e in &s.effects { println!(\"effect {}\", e); }\n}\n\n");
+    code.push_str("fn main() {\n");
+    for (idx, case) in cases.iter().enumerate() {
+        let dt = match case.dt { Some(dt) => format!("Some({:?}f64)", dt), None => "None".to_string() };
+        let tags: Vec<String> = case.tags.iter().map(|(v, t)| format!("({:?}, {:?})", v, t)).collect();
+        code.push_str(&format!("    println!(\"case {}\");\n", idx));
+        code.push_str(&format!(
+            "    {{ let mut s = State {{ health: {}, velocity: {:?}f64, position: {:?}f64, effects: Vec::new() }}; let p = Params {{ dt: {}, tags: vec![{}] }}; handler_{}(&mut s, &p); report(&s); }}\n",
+            case.health, case.velocity, case.position, dt, tags.join(", "), case.handler
+        ));
+    }
+    code.push_str("}\n");
+    write(format!("{}/src/main.rs", out_dir), code).map_err(|e| e.to_string())?;
+
+    let cargo_bin = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo_bin)
+        .args(["build", "--release", "--quiet", "--manifest-path", &format!("{}/Cargo.toml", out_dir)])
+        .status()
+        .map_err(|e| format!("failed to run cargo: {}", e))?;
+    if !status.success() {
+        return Err(format!("AOT build failed: {}", status));
+    }
+
+    let exe = format!("{}/target/release/aot_cases{}", out_dir, std::env::consts::EXE_SUFFIX);
+    let output = Command::new(&exe).output().map_err(|e| format!("failed to run {}: {}", exe, e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let mut outcomes: Vec<AotOutcome> = Vec::new();
+    for line in stdout.lines() {
+        if let Some(rest) = line.strip_prefix("state ") {
+            let parts: Vec<&str> = rest.split(' ').collect();
+            if parts.len() != 3 { return Err(format!("malformed AOT output: {}", line)); }
+            let malformed = || format!("malformed AOT output: {}", line);
+            outcomes.push(AotOutcome {
+                health: parts[0].parse().map_err(|_| malformed())?,
+                velocity: parts[1].parse().map_err(|_| malformed())?,
+                position: parts[2].parse().map_err(|_| malformed())?,
+                effects: Vec::new(),
+            });
+        } else if let Some(effect) = line.strip_prefix("effect ") {
+            match outcomes.last_mut() {
+                Some(o) => o.effects.push(effect.to_string()),
+                None => return Err(format!("effect before state in AOT output: {}", line)),
+            }
+        }
+    }
+    if outcomes.len() != cases.len() {
+        return Err(format!("AOT binary reported {} outcomes for {} cases", outcomes.len(), cases.len()));
+    }
+    Ok(outcomes)
+}
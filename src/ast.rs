@@ -144,7 +144,7 @@ fn expr_stmt_block_to_string(stmt: &Stmt) -> String {
 pub fn expr_to_string(e: &Expr) -> String {
     match e {
         Expr::Call { name, args } => {
-            let a: Vec<String> = args.iter().map(|x| expr_to_string(x)).collect();
+            let a: Vec<String> = args.iter().map(expr_to_string).collect();
             format!("{}({})", name, a.join(", "))
         }
         Expr::Ident(s) => s.clone(),
@@ -155,18 +155,45 @@ pub fn expr_to_string(e: &Expr) -> String {
     }
 }
 
+/// One rewrite performed by a transformation pass, kept so the simplified corpus can be audited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rewrite {
+    pub pass: &'static str,
+    pub before: String,
+    /// Empty when the statement was removed.
+    pub after: String,
+}
+
+impl std::fmt::Display for Rewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let after = if self.after.is_empty() { "<removed>" } else { &self.after };
+        write!(f, "[{}] {} => {}", self.pass, self.before.trim(), after.trim())
+    }
+}
+
 // simple constant folding: replace binary ops with literals when both sides are literals
-pub fn fold_constants(stmts: &mut Vec<Stmt>) {
+pub fn fold_constants(stmts: &mut [Stmt], log: &mut Vec<Rewrite>) {
     for st in stmts.iter_mut() {
         match st {
-            Stmt::Expr(e) => { fold_expr(e); }
-            Stmt::If { cond, body } => { fold_expr(cond); fold_constants(body); }
+            Stmt::Expr(e) => {
+                let before = expr_to_string(e);
+                fold_expr(e);
+                let after = expr_to_string(e);
+                if before != after { log.push(Rewrite { pass: "fold_constants", before, after }); }
+            }
+            Stmt::If { cond, body } => {
+                let before = expr_to_string(cond);
+                fold_expr(cond);
+                let after = expr_to_string(cond);
+                if before != after { log.push(Rewrite { pass: "fold_constants", before, after }); }
+                fold_constants(body, log);
+            }
             Stmt::Empty => {}
         }
     }
 }
 
-fn fold_expr(e: &mut Expr) {
+pub(crate) fn fold_expr(e: &mut Expr) {
     match e {
         Expr::BinaryOp { op, lhs, rhs } => {
             fold_expr(lhs); fold_expr(rhs);
@@ -188,20 +215,22 @@ fn fold_expr(e: &mut Expr) {
 }
 
 // inline helpers: replace Call nodes whose name matches helper name with sequence of helper statements
-pub fn inline_helpers(stmts: &mut Vec<Stmt>, helper_bodies: &std::collections::HashMap<String, Vec<Stmt>>) {
+pub fn inline_helpers(stmts: &mut Vec<Stmt>, helper_bodies: &std::collections::HashMap<String, Vec<Stmt>>, log: &mut Vec<Rewrite>) {
     let mut out = Vec::new();
     for st in stmts.iter() {
         match st {
             Stmt::Expr(Expr::Call { name, .. }) => {
                 if let Some(body) = helper_bodies.get(name) {
                     // inline body (clone)
+                    let after: Vec<String> = body.iter().map(stmt_to_string).collect();
+                    log.push(Rewrite { pass: "inline_helpers", before: stmt_to_string(st), after: after.join(" ") });
                     for b in body.iter() { out.push(b.clone()); }
                     continue;
                 } else { out.push(st.clone()); }
             }
             Stmt::If { cond, body } => {
                 let mut new_body = body.clone();
-                inline_helpers(&mut new_body, helper_bodies);
+                inline_helpers(&mut new_body, helper_bodies, log);
                 out.push(Stmt::If { cond: cond.clone(), body: new_body });
             }
             _ => out.push(st.clone()),
@@ -210,30 +239,44 @@ pub fn inline_helpers(stmts: &mut Vec<Stmt>, helper_bodies: &std::collections::H
     *stmts = out;
 }
 
-// dead code elimination: remove Empty and trivial literal-only expr statements
-pub fn dce(stmts: &mut Vec<Stmt>) {
-    stmts.retain(|st| match st {
-        Stmt::Empty => false,
-        Stmt::Expr(e) => match e {
-            Expr::Int(_) | Expr::Float(_) | Expr::Str(_) => false,
-            _ => true,
-        },
-        Stmt::If { cond: _, body } => { let mut nb = body.clone(); dce(&mut nb); !nb.is_empty() }
-    });
+// dead code elimination: remove Empty statements and `if`s whose body ends up empty.
+// Bare literals (`5;`) are kept: the interpreter reports them, so dropping them is observable.
+pub fn dce(stmts: &mut Vec<Stmt>, log: &mut Vec<Rewrite>) {
+    let mut out = Vec::new();
+    for st in stmts.drain(..) {
+        match st {
+            Stmt::Empty => {}
+            Stmt::If { cond, mut body } => {
+                dce(&mut body, log);
+                if body.is_empty() {
+                    log.push(Rewrite { pass: "dce", before: stmt_to_string(&Stmt::If { cond, body }), after: String::new() });
+                } else {
+                    out.push(Stmt::If { cond, body });
+                }
+            }
+            st => out.push(st),
+        }
+    }
+    *stmts = out;
 }
 
 // resolve wrapper helpers: if a helper body is a single call to another helper, replace it with the target body
-pub fn resolve_wrappers(helper_bodies: &mut std::collections::HashMap<String, Vec<Stmt>>) {
+pub fn resolve_wrappers(helper_bodies: &mut std::collections::HashMap<String, Vec<Stmt>>, log: &mut Vec<Rewrite>) {
     // iterate until no change
     loop {
         let mut changed = false;
-        let keys: Vec<String> = helper_bodies.keys().cloned().collect();
+        let mut keys: Vec<String> = helper_bodies.keys().cloned().collect();
+        keys.sort();
         for k in keys {
             if let Some(body) = helper_bodies.get(&k).cloned() {
                 if body.len() == 1 {
                     if let Stmt::Expr(Expr::Call { name, .. }) = &body[0] {
-                        if helper_bodies.contains_key(name) {
+                        // wrappers of themselves (directly or through a cycle) never settle
+                        if name != &k && helper_bodies.contains_key(name) {
                             let target = helper_bodies.get(name).cloned().unwrap_or_default();
+                            let after: Vec<String> = target.iter().map(stmt_to_string).collect();
+                            if after == [stmt_to_string(&body[0])] { continue; }
+                            log.push(Rewrite { pass: "resolve_wrappers", before: format!("{}: {}", k, stmt_to_string(&body[0])), after: format!("{}: {}", k, after.join(" ")) });
                             helper_bodies.insert(k.clone(), target);
                             changed = true;
                         }
@@ -0,0 +1,122 @@
+//! Per-frame cost budgets for handlers that `GameEngine::update` runs every frame.
+//!
+//! A budget file has one `<key> <cost>` per line, `#` starts a comment. The key is
+//! `default`, an event name (`Tick`) or a handler (`Player.Update`); the most specific wins.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::analyzer::CorpusStats;
+
+/// Events dispatched on every frame, the only ones a budget applies to.
+pub const PER_FRAME_EVENTS: &[&str] = crate::game_engine::FRAME_EVENTS;
+
+pub const DEFAULT_FRAME_BUDGET: u32 = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Budget {
+    pub default: u32,
+    pub events: BTreeMap<String, u32>,
+    pub handlers: BTreeMap<String, u32>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget { default: DEFAULT_FRAME_BUDGET, events: BTreeMap::new(), handlers: BTreeMap::new() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetViolation {
+    pub file: String,
+    /// `Entity.Event`.
+    pub handler: String,
+    pub cost: u32,
+    pub limit: u32,
+}
+
+impl fmt::Display for BudgetViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} costs {} per frame, budget is {}", self.file, self.handler, self.cost, self.limit)
+    }
+}
+
+impl Budget {
+    pub fn parse(text: &str) -> Result<Budget, String> {
+        let mut budget = Budget::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() { continue; }
+            let mut parts = line.split_whitespace();
+            let (Some(key), Some(cost), None) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(format!("line {}: expected `<key> <cost>`", n + 1));
+            };
+            let cost: u32 = cost.parse().map_err(|_| format!("line {}: `{}` is not a cost", n + 1, cost))?;
+            if key == "default" {
+                budget.default = cost;
+            } else if key.contains('.') {
+                budget.handlers.insert(key.to_string(), cost);
+            } else {
+                budget.events.insert(key.to_string(), cost);
+            }
+        }
+        Ok(budget)
+    }
+
+    pub fn load(path: &str) -> Result<Budget, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Budget::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn limit(&self, entity: &str, event: &str) -> u32 {
+        self.handlers.get(&format!("{}.{}", entity, event))
+            .or_else(|| self.events.get(event))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Per-frame handlers whose estimated cost exceeds their limit.
+pub fn check_budget(stats: &CorpusStats, budget: &Budget) -> Vec<BudgetViolation> {
+    stats.events.iter()
+        .filter(|e| PER_FRAME_EVENTS.contains(&e.event.as_str()))
+        .filter_map(|e| {
+            let limit = budget.limit(&e.entity, &e.event);
+            (e.metrics.cost > limit).then(|| BudgetViolation {
+                file: e.file.clone(),
+                handler: format!("{}.{}", e.entity, e.event),
+                cost: e.metrics.cost,
+                limit,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_specific_limit_wins() {
+        let b = Budget::parse("# frame budget\ndefault 10\nTick 20 # AI\nBoss.Tick 40\n").unwrap();
+        assert_eq!(b.limit("Player", "Update"), 10);
+        assert_eq!(b.limit("Guard", "Tick"), 20);
+        assert_eq!(b.limit("Boss", "Tick"), 40);
+        assert!(Budget::parse("Tick lots").is_err());
+    }
+
+    #[test]
+    fn only_per_frame_handlers_are_checked() {
+        let src = "entity A { on Update() { collide(); collide(); } on Hit() { collide(); collide(); } }";
+        let file = crate::corpus::CorpusFile {
+            path: "a.meta".into(),
+            relative: "a.meta".to_string(),
+            source: src.to_string(),
+            entities: crate::meta_lang::parse_entities(src),
+            functions: Vec::new(),
+        };
+        let stats = crate::analyzer::analyze_files(&[file]);
+        let violations = check_budget(&stats, &Budget::parse("default 10").unwrap());
+        assert_eq!(violations, vec![BudgetViolation { file: "a.meta".to_string(), handler: "A.Update".to_string(), cost: 16, limit: 10 }]);
+    }
+}
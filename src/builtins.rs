@@ -0,0 +1,32 @@
+//! Builtins handlers may call, and the components an entity needs for each of them.
+
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    /// Components the builtin reads or writes on the calling entity.
+    pub requires: &'static [&'static str],
+    /// Estimated instructions per call, for per-frame budgets.
+    pub cost: u32,
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "move", requires: &["Physics"], cost: 4 },
+    Builtin { name: "collide", requires: &["Physics"], cost: 8 },
+    Builtin { name: "takeDamage", requires: &[], cost: 2 },
+    Builtin { name: "dealDamage", requires: &[], cost: 2 },
+    Builtin { name: "patrol", requires: &["AI"], cost: 6 },
+    Builtin { name: "chase", requires: &["AI"], cost: 6 },
+    Builtin { name: "follow", requires: &["Follow"], cost: 5 },
+    Builtin { name: "emit", requires: &[], cost: 3 },
+    Builtin { name: "queueFree", requires: &[], cost: 1 },
+];
+
+/// Cost of calling a `function` or an unknown name, on top of the callee's body.
+pub const CALL_COST: u32 = 2;
+
+/// Components the engine itself consumes (rendering, input), so declaring them is never unused.
+pub const ENGINE_COMPONENTS: &[&str] = &["Transform", "Sprite", "Input"];
+
+pub fn builtin(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
@@ -0,0 +1,319 @@
+//! Semantic clone detection: handlers with the same structure modulo renamed identifiers and
+//! literals (e.g. `Bullet.Update` vs `Projectile.Update`).
+//!
+//! Each handler is normalized (identifiers renamed in order of appearance, literals replaced by
+//! their kind, call names kept since they carry the semantics) and hashed; equal hashes are
+//! structural clones. Near misses are found by the similarity of the normalized token streams.
+
+use std::collections::HashMap;
+
+use crate::ast::{Expr, Stmt, expr_to_string, parse_statements, stmt_to_string};
+use crate::corpus::{CorpusFile, CorpusOptions, load_corpus};
+use crate::incremental::content_hash;
+use crate::runtime::tag_guard;
+use crate::supercompiler::{anti_unify, function_to_string};
+
+#[derive(Debug, Clone)]
+pub struct CloneOptions {
+    /// Handlers at least this similar (0..=1, over normalized tokens) end up in one group.
+    pub min_similarity: f64,
+    /// Smaller handlers are too trivial to report.
+    pub min_tokens: usize,
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self { min_similarity: 0.8, min_tokens: 4 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handler {
+    pub file: String,
+    pub entity: String,
+    pub event: String,
+}
+
+impl std::fmt::Display for Handler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{} ({})", self.entity, self.event, self.file)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CloneGroup {
+    pub members: Vec<Handler>,
+    /// Lowest pairwise similarity of the normalized structure; 1.0 for renamings.
+    pub structural: f64,
+    /// Lowest pairwise similarity of the raw tokens; 1.0 for verbatim copies.
+    pub textual: f64,
+    /// Shared structure hash, if all members have the same normalized structure.
+    pub hash: Option<u64>,
+    pub suggestion: Suggestion,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Suggestion {
+    /// Verbatim copies of one handler: a shared mixin would do.
+    Mixin { event: String, body: String },
+    /// Same structure: extract a function, the listed members' bodies become one call each.
+    /// In a mixed group this covers the largest subset with one structure.
+    Function { definition: String, calls: Vec<(Handler, String)> },
+    /// Similar but no two members share a structure; worth a look.
+    Review,
+}
+
+pub fn find_clones(path: &str, opts: &CloneOptions) -> Vec<CloneGroup> {
+    detect_clones(&load_corpus(path, &CorpusOptions::default()).files, opts)
+}
+
+pub fn detect_clones(files: &[CorpusFile], opts: &CloneOptions) -> Vec<CloneGroup> {
+    struct Item {
+        handler: Handler,
+        params: Option<String>,
+        body: Vec<Stmt>,
+        raw: Vec<String>,
+        normalized: Vec<String>,
+        hash: u64,
+    }
+    let mut items = Vec::new();
+    for file in files {
+        for e in &file.entities {
+            for ev in &e.events {
+                let body = parse_statements(&ev.body);
+                let raw = tokens(&body, &mut |t| t.to_string());
+                let mut renaming = Renaming::default();
+                let normalized = tokens(&body, &mut |t| renaming.normalize(t));
+                if normalized.len() < opts.min_tokens { continue; }
+                let hash = content_hash(normalized.join(" ").as_bytes());
+                items.push(Item {
+                    handler: Handler { file: file.relative.clone(), entity: e.name.clone(), event: ev.name.clone() },
+                    params: ev.params.clone(),
+                    body,
+                    raw,
+                    normalized,
+                    hash,
+                });
+            }
+        }
+    }
+
+    // union-find over similar pairs
+    let mut parent: Vec<usize> = (0..items.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i { parent[i] = parent[parent[i]]; i = parent[i]; }
+        i
+    }
+    for i in 0..items.len() {
+        for j in i + 1..items.len() {
+            let similar = items[i].hash == items[j].hash
+                || similarity(&items[i].normalized, &items[j].normalized) >= opts.min_similarity;
+            if similar {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[b] = a;
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..items.len() {
+        let r = root(&mut parent, i);
+        groups.entry(r).or_default().push(i);
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    groups.sort();
+
+    groups.into_iter().map(|g| {
+        let (mut structural, mut textual) = (1.0f64, 1.0f64);
+        for (k, &i) in g.iter().enumerate() {
+            for &j in &g[k + 1..] {
+                structural = structural.min(similarity(&items[i].normalized, &items[j].normalized));
+                textual = textual.min(similarity(&items[i].raw, &items[j].raw));
+            }
+        }
+        let first = &items[g[0]];
+        let same_structure = g.iter().all(|&i| items[i].hash == first.hash);
+        let same_signature = g.iter().all(|&i| items[i].handler.event == first.handler.event && items[i].params == first.params);
+        // largest subset sharing one structure (first one wins ties)
+        let mut shared: Vec<usize> = Vec::new();
+        for &i in &g {
+            let subset: Vec<usize> = g.iter().copied().filter(|&j| items[j].hash == items[i].hash).collect();
+            if subset.len() > shared.len() { shared = subset; }
+        }
+        let suggestion = if textual == 1.0 && same_signature {
+            let body: Vec<String> = first.body.iter().map(stmt_to_string).collect();
+            Suggestion::Mixin { event: first.handler.event.clone(), body: body.join(" ") }
+        } else if shared.len() > 1 {
+            let bodies: Vec<Vec<Stmt>> = shared.iter().map(|&i| items[i].body.clone()).collect();
+            let name = format!("shared_{}", items[shared[0]].handler.event.to_lowercase());
+            let f = anti_unify(&name, &bodies);
+            let calls = shared.iter().map(|&i| {
+                let args: Vec<Expr> = f.params.iter().map(|p| leaf_for(&f.body, &items[i].body, p).unwrap_or_else(|| Expr::Ident(p.clone()))).collect();
+                (items[i].handler.clone(), format!("{};", expr_to_string(&Expr::Call { name: name.clone(), args })))
+            }).collect();
+            Suggestion::Function { definition: function_to_string(&f.name, &f.params, &f.body).trim_end().to_string(), calls }
+        } else {
+            Suggestion::Review
+        };
+        CloneGroup {
+            members: g.iter().map(|&i| items[i].handler.clone()).collect(),
+            structural,
+            textual,
+            hash: same_structure.then_some(first.hash),
+            suggestion,
+        }
+    }).collect()
+}
+
+/// Dice coefficient of the longest common subsequence: `2 * lcs / (|a| + |b|)`.
+pub fn similarity(a: &[String], b: &[String]) -> f64 {
+    if a.is_empty() && b.is_empty() { return 1.0; }
+    let mut prev = vec![0usize; b.len() + 1];
+    for x in a {
+        let mut cur = vec![0usize; b.len() + 1];
+        for (j, y) in b.iter().enumerate() {
+            cur[j + 1] = if x == y { prev[j] + 1 } else { prev[j + 1].max(cur[j]) };
+        }
+        prev = cur;
+    }
+    2.0 * prev[b.len()] as f64 / (a.len() + b.len()) as f64
+}
+
+/// Identifiers become `$0`, `$1`, ... in order of first appearance, literals their kind.
+#[derive(Default)]
+struct Renaming {
+    names: Vec<String>,
+}
+
+impl Renaming {
+    fn normalize(&mut self, token: &str) -> String {
+        if let Some(kind) = token.strip_prefix("lit:") {
+            return format!("#{}", kind.split(':').next().unwrap_or(""));
+        }
+        if let Some(name) = token.strip_prefix("id:") {
+            let k = match self.names.iter().position(|n| n == name) {
+                Some(k) => k,
+                None => { self.names.push(name.to_string()); self.names.len() - 1 }
+            };
+            return format!("${}", k);
+        }
+        token.to_string()
+    }
+}
+
+/// Token stream of a body; `leaf` maps each raw token (`id:x`, `lit:int:5`, `call:move`, ...).
+fn tokens(stmts: &[Stmt], leaf: &mut dyn FnMut(&str) -> String) -> Vec<String> {
+    let mut out = Vec::new();
+    for st in stmts {
+        match st {
+            Stmt::Expr(e) => { expr_tokens(e, leaf, &mut out); out.push(";".into()); }
+            Stmt::If { cond, body } => {
+                out.push("if".into());
+                expr_tokens(cond, leaf, &mut out);
+                out.push("{".into());
+                out.extend(tokens(body, leaf));
+                out.push("}".into());
+            }
+            Stmt::Empty => {}
+        }
+    }
+    out
+}
+
+fn expr_tokens(e: &Expr, leaf: &mut dyn FnMut(&str) -> String, out: &mut Vec<String>) {
+    match e {
+        Expr::Call { name, args } => {
+            out.push(leaf(&format!("call:{}", name)));
+            for a in args { expr_tokens(a, leaf, out); out.push(",".into()); }
+            out.push(")".into());
+        }
+        Expr::Ident(text) => match tag_guard(text) {
+            // `other.tag == "Enemy"` is parsed as one identifier
+            Some((var, tag)) => {
+                out.push(leaf(&format!("id:{}", var)));
+                out.push(".tag==".into());
+                out.push(leaf(&format!("lit:str:{}", tag)));
+            }
+            None => out.push(leaf(&format!("id:{}", text))),
+        },
+        Expr::Int(i) => out.push(leaf(&format!("lit:int:{}", i))),
+        Expr::Float(f) => out.push(leaf(&format!("lit:float:{}", f))),
+        Expr::Str(s) => out.push(leaf(&format!("lit:str:{}", s))),
+        Expr::BinaryOp { op, lhs, rhs } => {
+            expr_tokens(lhs, leaf, out);
+            out.push(op.to_string());
+            expr_tokens(rhs, leaf, out);
+        }
+    }
+}
+
+/// The expression of `body` sitting where `pattern` has the parameter `param`.
+fn leaf_for(pattern: &[Stmt], body: &[Stmt], param: &str) -> Option<Expr> {
+    pattern.iter().zip(body).find_map(|(p, b)| match (p, b) {
+        (Stmt::Expr(pe), Stmt::Expr(be)) => expr_leaf_for(pe, be, param),
+        (Stmt::If { cond: pc, body: pb }, Stmt::If { cond: bc, body: bb }) => {
+            expr_leaf_for(pc, bc, param).or_else(|| leaf_for(pb, bb, param))
+        }
+        _ => None,
+    })
+}
+
+fn expr_leaf_for(p: &Expr, e: &Expr, param: &str) -> Option<Expr> {
+    match (p, e) {
+        (Expr::Ident(v), _) if v == param => Some(e.clone()),
+        (Expr::Call { args: pa, .. }, Expr::Call { args: ea, .. }) => pa.iter().zip(ea).find_map(|(p, e)| expr_leaf_for(p, e, param)),
+        (Expr::BinaryOp { lhs: pl, rhs: pr, .. }, Expr::BinaryOp { lhs: el, rhs: er, .. }) => {
+            expr_leaf_for(pl, el, param).or_else(|| expr_leaf_for(pr, er, param))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(src: &str) -> CorpusFile {
+        CorpusFile {
+            path: "a.meta".into(),
+            relative: "a.meta".to_string(),
+            source: src.to_string(),
+            entities: crate::meta_lang::parse_entities(src),
+            functions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn renamed_handlers_share_a_function() {
+        let src = r#"
+entity Bullet { on Update(dt) { move(speed * dt); collide(); } }
+entity Projectile { on Update(dt) { move(velocity * dt); collide(); } }
+entity Rock { on Update(dt) { takeDamage(1); } }
+"#;
+        let groups = detect_clones(&[file(src)], &CloneOptions::default());
+        assert_eq!(groups.len(), 1);
+        let g = &groups[0];
+        let names: Vec<&str> = g.members.iter().map(|m| m.entity.as_str()).collect();
+        assert_eq!(names, vec!["Bullet", "Projectile"]);
+        assert_eq!(g.structural, 1.0);
+        assert!(g.textual < 1.0 && g.hash.is_some());
+        assert_eq!(g.suggestion, Suggestion::Function {
+            definition: "function shared_update(a) { move(a * dt); collide();  }".to_string(),
+            calls: vec![(g.members[0].clone(), "shared_update(speed);".to_string()), (g.members[1].clone(), "shared_update(velocity);".to_string())],
+        });
+    }
+
+    #[test]
+    fn near_misses_and_copies() {
+        let src = r#"
+entity A { on Hit(other) { if (other.tag == "Enemy") { takeDamage(10); collide(); } } }
+entity B { on Hit(o) { if (o.tag == "Wall") { takeDamage(3); collide(); move(1); } } }
+entity C { on Tick() { patrol(); chase(player); } }
+entity D { on Tick() { patrol(); chase(player); } }
+"#;
+        let groups = detect_clones(&[file(src)], &CloneOptions::default());
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].suggestion, Suggestion::Review);
+        assert!(groups[0].structural >= 0.8 && groups[0].structural < 1.0);
+        assert_eq!(groups[1].suggestion, Suggestion::Mixin { event: "Tick".to_string(), body: "patrol(); chase(player);".to_string() });
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
 /// Встроенные компоненты движка
 pub trait IComponent: Send + Sync {
     fn name(&self) -> &str;
@@ -5,10 +7,10 @@ pub trait IComponent: Send + Sync {
 }
 
 /// Transform компонент - определяет позицию, ротацию, масштаб
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Transform {
     pub position: Vector3,
-    pub rotation: Vector3,
+    pub rotation: Quaternion,
     pub scale: Vector3,
 }
 
@@ -41,13 +43,13 @@ pub struct Physics {
 }
 
 /// Input компонент - для обработки ввода
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Input {
     pub keys_pressed: Vec<String>,
 }
 
 /// Вектор 3D для позиции и направления
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Vector3 {
     pub x: f32,
     pub y: f32,
@@ -76,11 +78,165 @@ impl Vector3 {
     }
 }
 
+impl Vector3 {
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
+    }
+
+    pub fn distance(self, other: Self) -> f32 {
+        (self - other).length()
+    }
+
+    /// Единичный вектор того же направления; нулевой вектор остаётся нулевым
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        if len > f32::EPSILON { self / len } else { Self::zero() }
+    }
+
+    /// Линейная интерполяция: `t = 0` — `self`, `t = 1` — `other`
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Add for Vector3 {
+    type Output = Self;
+    fn add(self, o: Self) -> Self { Self::new(self.x + o.x, self.y + o.y, self.z + o.z) }
+}
+
+impl Sub for Vector3 {
+    type Output = Self;
+    fn sub(self, o: Self) -> Self { Self::new(self.x - o.x, self.y - o.y, self.z - o.z) }
+}
+
+/// Покомпонентное произведение
+impl Mul for Vector3 {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self { Self::new(self.x * o.x, self.y * o.y, self.z * o.z) }
+}
+
+impl Mul<f32> for Vector3 {
+    type Output = Self;
+    fn mul(self, k: f32) -> Self { Self::new(self.x * k, self.y * k, self.z * k) }
+}
+
+impl Div<f32> for Vector3 {
+    type Output = Self;
+    fn div(self, k: f32) -> Self { Self::new(self.x / k, self.y / k, self.z / k) }
+}
+
+impl Neg for Vector3 {
+    type Output = Self;
+    fn neg(self) -> Self { Self::new(-self.x, -self.y, -self.z) }
+}
+
+impl AddAssign for Vector3 {
+    fn add_assign(&mut self, o: Self) { *self = *self + o; }
+}
+
+impl SubAssign for Vector3 {
+    fn sub_assign(&mut self, o: Self) { *self = *self - o; }
+}
+
+impl MulAssign<f32> for Vector3 {
+    fn mul_assign(&mut self, k: f32) { *self = *self * k; }
+}
+
+impl From<glam::Vec3> for Vector3 {
+    fn from(v: glam::Vec3) -> Self { Self::new(v.x, v.y, v.z) }
+}
+
+impl From<Vector3> for glam::Vec3 {
+    fn from(v: Vector3) -> Self { glam::Vec3::new(v.x, v.y, v.z) }
+}
+
+/// Кватернион поворота (единичный)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Self = Self { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+
+    /// Поворот на `angle` радиан вокруг оси `axis`
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        glam::Quat::from_axis_angle(axis.normalize().into(), angle).into()
+    }
+
+    /// Из углов Эйлера (радианы): сначала X, затем Y, затем Z
+    pub fn from_euler(euler: Vector3) -> Self {
+        glam::Quat::from_euler(glam::EulerRot::ZYX, euler.z, euler.y, euler.x).into()
+    }
+
+    pub fn to_euler(self) -> Vector3 {
+        let (z, y, x) = glam::Quat::from(self).to_euler(glam::EulerRot::ZYX);
+        Vector3::new(x, y, z)
+    }
+
+    pub fn normalize(self) -> Self {
+        glam::Quat::from(self).normalize().into()
+    }
+
+    pub fn inverse(self) -> Self {
+        glam::Quat::from(self).inverse().into()
+    }
+
+    /// Поворачивает вектор
+    pub fn rotate(self, v: Vector3) -> Vector3 {
+        (glam::Quat::from(self) * glam::Vec3::from(v)).into()
+    }
+
+    /// Сферическая интерполяция
+    pub fn slerp(self, other: Self, t: f32) -> Self {
+        glam::Quat::from(self).slerp(other.into(), t).into()
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Композиция поворотов: сначала `o`, затем `self`
+impl Mul for Quaternion {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self { (glam::Quat::from(self) * glam::Quat::from(o)).into() }
+}
+
+impl From<glam::Quat> for Quaternion {
+    fn from(q: glam::Quat) -> Self { Self { x: q.x, y: q.y, z: q.z, w: q.w } }
+}
+
+impl From<Quaternion> for glam::Quat {
+    fn from(q: Quaternion) -> Self { glam::Quat::from_xyzw(q.x, q.y, q.z, q.w) }
+}
+
 impl Default for Transform {
     fn default() -> Self {
         Self {
             position: Vector3::zero(),
-            rotation: Vector3::zero(),
+            rotation: Quaternion::IDENTITY,
             scale: Vector3::one(),
         }
     }
@@ -122,18 +278,40 @@ impl Default for Physics {
     }
 }
 
-impl Default for Input {
-    fn default() -> Self {
-        Self {
-            keys_pressed: Vec::new(),
-        }
-    }
-}
-
 impl Transform {
     pub fn new() -> Self {
         Self::default()
     }
+
+    pub fn from_position(position: Vector3) -> Self {
+        Self { position, ..Self::default() }
+    }
+
+    /// Матрица scale → rotation → translation
+    pub fn to_mat4(&self) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(self.scale.into(), self.rotation.into(), self.position.into())
+    }
+
+    /// Разложение аффинной матрицы без сдвига (shear) на масштаб, поворот и позицию
+    pub fn from_mat4(m: glam::Mat4) -> Self {
+        let (scale, rotation, position) = m.to_scale_rotation_translation();
+        Self { position: position.into(), rotation: rotation.into(), scale: scale.into() }
+    }
+
+    /// Переводит точку из локального пространства в пространство родителя
+    pub fn transform_point(&self, p: Vector3) -> Vector3 {
+        self.position + self.rotation.rotate(p * self.scale)
+    }
+
+    /// Композиция: `self` — родитель, `child` — локальный трансформ внутри него
+    pub fn mul_transform(&self, child: &Transform) -> Transform {
+        Transform::from_mat4(self.to_mat4() * child.to_mat4())
+    }
+
+    /// Локальный трансформ, который внутри `parent` даёт мировой `self`
+    pub fn relative_to(&self, parent: &Transform) -> Transform {
+        Transform::from_mat4(parent.to_mat4().inverse() * self.to_mat4())
+    }
 }
 
 impl Health {
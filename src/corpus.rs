@@ -0,0 +1,214 @@
+//! Corpus discovery shared by the analyzer, the supercompiler and the checkers.
+//!
+//! Walks the corpus directory recursively, keeps files matching the include globs and none of
+//! the exclude globs (both relative to the root, `/`-separated), and reads and parses them,
+//! optionally on several threads. Unreadable or malformed files are reported, not skipped
+//! silently.
+
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+use crate::meta_lang::{Entity, Function, parse_entities, parse_functions};
+
+#[derive(Debug, Clone)]
+pub struct CorpusOptions {
+    /// `*` and `?` match within a path segment, `**` matches any number of segments.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Files read and parsed in parallel; 1 keeps everything on the calling thread.
+    pub jobs: usize,
+}
+
+impl Default for CorpusOptions {
+    fn default() -> Self {
+        Self {
+            include: vec!["**/*.meta".to_string()],
+            // hidden directories, e.g. the supercompiler cache
+            exclude: vec!["**/.*/**".to_string()],
+            jobs: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CorpusFile {
+    pub path: PathBuf,
+    /// Path below the corpus root with `/` separators, e.g. `levels/boss.meta`.
+    pub relative: String,
+    pub source: String,
+    pub entities: Vec<Entity>,
+    pub functions: Vec<Function>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorpusError {
+    Io { path: String, message: String },
+    Parse { path: String, message: String },
+}
+
+impl std::fmt::Display for CorpusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CorpusError::Io { path, message } => write!(f, "{}: {}", path, message),
+            CorpusError::Parse { path, message } => write!(f, "{}: parse error: {}", path, message),
+        }
+    }
+}
+
+/// Loaded files sorted by relative path, and every file that could not be loaded.
+#[derive(Debug, Default)]
+pub struct Corpus {
+    pub files: Vec<CorpusFile>,
+    pub errors: Vec<CorpusError>,
+}
+
+pub fn load_corpus(root: &str, opts: &CorpusOptions) -> Corpus {
+    let mut corpus = Corpus::default();
+    let mut found = Vec::new();
+    walk(Path::new(root), "", opts, &mut found, &mut corpus.errors);
+    found.sort();
+
+    let jobs = opts.jobs.clamp(1, found.len().max(1));
+    let chunk = found.len().div_ceil(jobs).max(1);
+    let results: Vec<Result<CorpusFile, CorpusError>> = if jobs == 1 {
+        found.iter().map(|rel| load_file(root, rel)).collect()
+    } else {
+        std::thread::scope(|s| {
+            let handles: Vec<_> = found.chunks(chunk)
+                .map(|part| s.spawn(move || part.iter().map(|rel| load_file(root, rel)).collect::<Vec<_>>()))
+                .collect();
+            handles.into_iter().flat_map(|h| h.join().expect("corpus loader thread panicked")).collect()
+        })
+    };
+    for r in results {
+        match r {
+            Ok(file) => corpus.files.push(file),
+            Err(e) => corpus.errors.push(e),
+        }
+    }
+    corpus
+}
+
+fn walk(dir: &Path, prefix: &str, opts: &CorpusOptions, found: &mut Vec<String>, errors: &mut Vec<CorpusError>) {
+    let entries = match read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            errors.push(CorpusError::Io { path: dir.to_string_lossy().to_string(), message: e.to_string() });
+            return;
+        }
+    };
+    for ent in entries.flatten() {
+        let name = ent.file_name().to_string_lossy().to_string();
+        let relative = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+        // symlinked directories are not followed, so cycles cannot occur
+        if ent.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            walk(&ent.path(), &relative, opts, found, errors);
+        } else if ent.path().is_file()
+            && opts.include.iter().any(|g| glob_match(g, &relative))
+            && !opts.exclude.iter().any(|g| glob_match(g, &relative))
+        {
+            found.push(relative);
+        }
+    }
+}
+
+fn load_file(root: &str, relative: &str) -> Result<CorpusFile, CorpusError> {
+    let path = Path::new(root).join(relative);
+    let source = read_to_string(&path)
+        .map_err(|e| CorpusError::Io { path: relative.to_string(), message: e.to_string() })?;
+    check_syntax(&source).map_err(|message| CorpusError::Parse { path: relative.to_string(), message })?;
+    Ok(CorpusFile {
+        entities: parse_entities(&source),
+        functions: parse_functions(&source),
+        path,
+        relative: relative.to_string(),
+        source,
+    })
+}
+
+/// The parsers recover from anything, so catch what they would silently drop: unbalanced
+/// brackets and `entity` blocks they could not make sense of.
+fn check_syntax(src: &str) -> Result<(), String> {
+    let mut open: Vec<(char, usize)> = Vec::new();
+    let mut in_string = false;
+    for (n, line) in src.lines().enumerate() {
+        let code = line.split("//").next().unwrap_or("");
+        for c in code.chars() {
+            match c {
+                '"' => in_string = !in_string,
+                _ if in_string => {}
+                '{' | '(' | '[' => open.push((c, n + 1)),
+                '}' | ')' | ']' => {
+                    let expected = match c { '}' => '{', ')' => '(', _ => '[' };
+                    match open.pop() {
+                        Some((o, _)) if o == expected => {}
+                        Some((o, at)) => return Err(format!("line {}: `{}` closes `{}` opened on line {}", n + 1, c, o, at)),
+                        None => return Err(format!("line {}: unmatched `{}`", n + 1, c)),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    if let Some((o, at)) = open.pop() {
+        return Err(format!("line {}: `{}` is never closed", at, o));
+    }
+    let declared = src.lines()
+        .filter(|l| l.split("//").next().unwrap_or("").trim_start().starts_with("entity "))
+        .count();
+    let parsed = parse_entities(src).len();
+    if parsed < declared {
+        return Err(format!("{} entity declarations, only {} parsed", declared, parsed));
+    }
+    Ok(())
+}
+
+/// Match a `/`-separated relative path against a glob.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pat: Vec<&str> = pattern.split('/').collect();
+    let segs: Vec<&str> = path.split('/').collect();
+    match_segments(&pat, &segs)
+}
+
+fn match_segments(pat: &[&str], segs: &[&str]) -> bool {
+    match pat.split_first() {
+        None => segs.is_empty(),
+        Some((&"**", rest)) => (0..=segs.len()).any(|k| match_segments(rest, &segs[k..])),
+        Some((p, rest)) => match segs.split_first() {
+            Some((s, more)) => match_segment(p.as_bytes(), s.as_bytes()) && match_segments(rest, more),
+            None => false,
+        },
+    }
+}
+
+fn match_segment(p: &[u8], s: &[u8]) -> bool {
+    match (p.first(), s.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => match_segment(&p[1..], s) || (!s.is_empty() && match_segment(p, &s[1..])),
+        (Some(b'?'), Some(_)) => match_segment(&p[1..], &s[1..]),
+        (Some(a), Some(b)) if a == b => match_segment(&p[1..], &s[1..]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("**/*.meta", "player.meta"));
+        assert!(glob_match("**/*.meta", "levels/boss/guard.meta"));
+        assert!(!glob_match("*.meta", "levels/guard.meta"));
+        assert!(glob_match("levels/**", "levels/a/b.meta"));
+        assert!(glob_match("**/.*/**", ".cache/0123.driven"));
+        assert!(glob_match("enemy_?.meta", "enemy_1.meta"));
+        assert!(!glob_match("enemy_?.meta", "enemy_10.meta"));
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_line() {
+        assert_eq!(check_syntax("entity A {\n  on Hit() { collide(); \n}\n"), Err("line 1: `{` is never closed".to_string()));
+        assert!(check_syntax("entity A { on Hit() { collide(); } }").is_ok());
+    }
+}
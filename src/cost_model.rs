@@ -0,0 +1,185 @@
+//! Cost model and solver deciding which repeated statement sequences the supercompiler
+//! extracts into helpers and which stay inlined at their call sites.
+
+/// What the cost model knows about one repeated sequence.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// Call names and arities, e.g. `takeDamage/1;collide/0`.
+    pub shape: String,
+    /// Statements in one occurrence.
+    pub statements: usize,
+    /// AST nodes in one occurrence (code size).
+    pub size: usize,
+    /// Parameters the helper needs after anti-unification.
+    pub params: usize,
+    /// Non-overlapping occurrences across the corpus.
+    pub occurrences: usize,
+    /// Sum of the profile weights of the handlers containing the occurrences.
+    pub hotness: f64,
+}
+
+pub trait CostModel {
+    /// Net benefit of extracting the candidate into a helper; `<= 0` keeps it inlined.
+    fn benefit(&self, c: &Candidate) -> f64;
+
+    /// Budget units the helper consumes.
+    fn cost(&self, c: &Candidate) -> usize {
+        c.statements
+    }
+
+    /// Identifies the model and its parameters in cache keys; models with tunable
+    /// parameters should include them.
+    fn fingerprint(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+}
+
+/// Code size saved by sharing the sequence, minus call overhead weighted by how often
+/// the occurrences run.
+#[derive(Debug, Clone)]
+pub struct DefaultCostModel {
+    pub size_weight: f64,
+    /// Cost of one helper call per unit of hotness.
+    pub call_overhead: f64,
+}
+
+impl Default for DefaultCostModel {
+    fn default() -> Self {
+        Self { size_weight: 1.0, call_overhead: 0.1 }
+    }
+}
+
+impl DefaultCostModel {
+    /// Nodes saved: every occurrence shrinks to a call (name + arguments), the body is kept once
+    /// plus one node for the definition.
+    pub fn size_saving(c: &Candidate) -> f64 {
+        let inline = c.occurrences * c.size;
+        let extracted = c.size + 1 + c.occurrences * (1 + c.params);
+        inline as f64 - extracted as f64
+    }
+}
+
+impl CostModel for DefaultCostModel {
+    fn benefit(&self, c: &Candidate) -> f64 {
+        self.size_weight * Self::size_saving(c) - self.call_overhead * c.hotness
+    }
+
+    fn fingerprint(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Extract,
+    /// Not worth a helper on its own.
+    Unprofitable,
+    /// Would exceed the helper budget given the better choices.
+    OverBudget,
+    /// Shares occurrences with the chosen candidate at this index.
+    Conflicts(usize),
+}
+
+/// Pick the subset of candidates with maximal total benefit such that the summed cost fits
+/// `budget` and no two chosen candidates overlap (`conflicts(i, j)`). Profitable candidates
+/// are searched exactly (branch and bound) up to `EXACT_LIMIT`, the rest greedily.
+pub fn select(
+    candidates: &[Candidate],
+    model: &dyn CostModel,
+    budget: usize,
+    conflicts: &dyn Fn(usize, usize) -> bool,
+) -> Vec<Verdict> {
+    let benefits: Vec<f64> = candidates.iter().map(|c| model.benefit(c)).collect();
+    let costs: Vec<usize> = candidates.iter().map(|c| model.cost(c)).collect();
+
+    let mut order: Vec<usize> = (0..candidates.len()).filter(|&i| benefits[i] > 0.0).collect();
+    order.sort_by(|&a, &b| benefits[b].total_cmp(&benefits[a]).then(a.cmp(&b)));
+    let (exact, greedy) = order.split_at(order.len().min(EXACT_LIMIT));
+
+    let mut search = Search { items: exact, benefits: &benefits, costs: &costs, conflicts, best: Vec::new(), best_value: 0.0 };
+    search.run(0, &mut Vec::new(), 0.0, budget);
+    let mut chosen = search.best;
+    let mut used: usize = chosen.iter().map(|&i| costs[i]).sum();
+    for &i in greedy {
+        if used + costs[i] <= budget && !chosen.iter().any(|&j| conflicts(i, j)) {
+            chosen.push(i);
+            used += costs[i];
+        }
+    }
+
+    (0..candidates.len()).map(|i| {
+        if chosen.contains(&i) {
+            Verdict::Extract
+        } else if benefits[i] <= 0.0 {
+            Verdict::Unprofitable
+        } else if let Some(&j) = chosen.iter().find(|&&j| conflicts(i, j)) {
+            Verdict::Conflicts(j)
+        } else {
+            Verdict::OverBudget
+        }
+    }).collect()
+}
+
+const EXACT_LIMIT: usize = 16;
+
+struct Search<'a> {
+    items: &'a [usize],
+    benefits: &'a [f64],
+    costs: &'a [usize],
+    conflicts: &'a dyn Fn(usize, usize) -> bool,
+    best: Vec<usize>,
+    best_value: f64,
+}
+
+impl Search<'_> {
+    fn run(&mut self, k: usize, picked: &mut Vec<usize>, value: f64, left: usize) {
+        if value > self.best_value {
+            self.best_value = value;
+            self.best = picked.clone();
+        }
+        if k == self.items.len() { return; }
+        // bound: even taking every remaining item cannot beat the best
+        let optimistic: f64 = self.items[k..].iter().map(|&i| self.benefits[i]).sum();
+        if value + optimistic <= self.best_value { return; }
+
+        let i = self.items[k];
+        if self.costs[i] <= left && !picked.iter().any(|&j| (self.conflicts)(i, j)) {
+            picked.push(i);
+            self.run(k + 1, picked, value + self.benefits[i], left - self.costs[i]);
+            picked.pop();
+        }
+        self.run(k + 1, picked, value, left);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cand(shape: &str, statements: usize, occurrences: usize) -> Candidate {
+        Candidate { shape: shape.to_string(), statements, size: statements * 2, params: 0, occurrences, hotness: 0.0 }
+    }
+
+    #[test]
+    fn solver_prefers_best_combination_over_greedy() {
+        // greedy would take the single most beneficial candidate and run out of budget
+        let cs = vec![cand("a;b;c;d;e;f", 6, 3), cand("x;y;z", 3, 4), cand("p;q;r", 3, 4)];
+        let verdicts = select(&cs, &DefaultCostModel::default(), 6, &|_, _| false);
+        assert_eq!(verdicts, vec![Verdict::OverBudget, Verdict::Extract, Verdict::Extract]);
+    }
+
+    #[test]
+    fn conflicting_candidates_are_not_both_chosen() {
+        let cs = vec![cand("a;b;c", 3, 3), cand("a;b", 2, 3)];
+        let verdicts = select(&cs, &DefaultCostModel::default(), 10, &|_, _| true);
+        assert_eq!(verdicts, vec![Verdict::Extract, Verdict::Conflicts(0)]);
+    }
+
+    #[test]
+    fn hot_code_stays_inlined() {
+        let mut c = cand("a;b;c", 3, 3);
+        assert!(DefaultCostModel::default().benefit(&c) > 0.0);
+        c.hotness = 1000.0;
+        assert_eq!(select(&[c], &DefaultCostModel::default(), 10, &|_, _| false), vec![Verdict::Unprofitable]);
+    }
+}
@@ -0,0 +1,426 @@
+//! Differential testing harness: runs every corpus handler with generated inputs in the
+//! interpreter, then re-runs the supercompiled corpus (and optionally its AOT build) on the
+//! same inputs and compares final entity state and emitted effects.
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::meta_lang::Event;
+use crate::corpus::{CorpusOptions, load_corpus};
+use crate::runtime::{Effect, EntityInstance, SimpleEntity, Value, execute_event_traced};
+use crate::ast::{parse_statements, stmt_to_string};
+use crate::driving::{FunctionDef, inline_functions};
+use crate::aot_generator::{AotCase, run_aot_cases};
+use crate::supercompiler::simplify_corpus;
+
+pub struct DiffOptions {
+    pub cases_per_handler: usize,
+    pub seed: u64,
+    /// Where `simplify_corpus` writes the corpus under test. Defaults to a per-process
+    /// directory under the system temp dir, so the checked-in `corpus_simplified/` is untouched.
+    pub simplified_dir: String,
+    /// Scratch crate directory for the AOT backend; `None` skips the AOT comparison.
+    pub aot_dir: Option<String>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            cases_per_handler: 32,
+            seed: 0x5eed,
+            simplified_dir: std::env::temp_dir()
+                .join(format!("experiment_difftest_{}", std::process::id()))
+                .to_string_lossy()
+                .to_string(),
+            aot_dir: None,
+        }
+    }
+}
+
+/// One generated input for a handler: initial entity state plus event parameters.
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub file: String,
+    pub entity: String,
+    pub event: String,
+    pub health: i32,
+    pub velocity: f64,
+    pub position: f64,
+    pub params: Vec<(String, Value)>,
+}
+
+/// What a backend observed after running a case. Unrecognized statements are compared
+/// with whitespace removed, since the supercompiler re-prints bodies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub health: i32,
+    pub velocity: f64,
+    pub position: f64,
+    pub effects: Vec<Effect>,
+}
+
+/// A case on which a backend disagrees with the reference interpreter, already minimised.
+/// `actual` is `None` when the backend has no handler for the case.
+#[derive(Debug)]
+pub struct Divergence {
+    pub backend: String,
+    pub case: Case,
+    pub expected: Option<Observation>,
+    pub actual: Option<Observation>,
+}
+
+#[derive(Debug)]
+pub struct DiffReport {
+    pub handlers: usize,
+    pub cases: usize,
+    pub backends: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum DiffError {
+    Io(String),
+    Backend(String),
+    Diverged(Box<Divergence>),
+}
+
+/// An implementation of the meta language that can execute handlers in batches.
+pub trait Backend {
+    fn name(&self) -> &str;
+    fn run(&mut self, cases: &[Case]) -> Result<Vec<Option<Observation>>, String>;
+}
+
+type HandlerKey = (String, String, String);
+
+/// Runs handlers through `runtime::execute_event_traced`.
+pub struct InterpreterBackend {
+    name: String,
+    handlers: HashMap<HandlerKey, Event>,
+}
+
+/// Runs handlers AOT-compiled by `aot_generator::run_aot_cases`.
+pub struct AotBackend {
+    dir: String,
+    handlers: HashMap<HandlerKey, Event>,
+}
+
+pub fn run_differential(corpus_dir: &str, opts: &DiffOptions) -> Result<DiffReport, DiffError> {
+    let original = load_handlers(corpus_dir)?;
+    simplify_corpus(corpus_dir, &opts.simplified_dir);
+    let simplified = load_handlers(&opts.simplified_dir)?;
+
+    let mut keys: Vec<&HandlerKey> = original.keys().collect();
+    keys.sort();
+    let mut rng = Rng(opts.seed.max(1));
+    let mut cases = Vec::new();
+    for key in &keys {
+        for _ in 0..opts.cases_per_handler {
+            cases.push(generate_case(&mut rng, key, &original[*key]));
+        }
+    }
+
+    let mut reference = InterpreterBackend { name: "interpreter".to_string(), handlers: original.clone() };
+    let mut backends: Vec<Box<dyn Backend>> = vec![Box::new(InterpreterBackend { name: "supercompiled".to_string(), handlers: simplified.clone() })];
+    if let Some(dir) = &opts.aot_dir {
+        backends.push(Box::new(AotBackend { dir: dir.clone(), handlers: simplified }));
+    }
+
+    let expected = reference.run(&cases).map_err(DiffError::Backend)?;
+    for backend in backends.iter_mut() {
+        let actual = backend.run(&cases).map_err(DiffError::Backend)?;
+        if let Some(idx) = (0..cases.len()).find(|&i| expected[i] != actual[i]) {
+            let case = minimise(cases[idx].clone(), &mut reference, backend.as_mut()).map_err(DiffError::Backend)?;
+            let expected = reference.run(std::slice::from_ref(&case)).map_err(DiffError::Backend)?.remove(0);
+            let actual = backend.run(std::slice::from_ref(&case)).map_err(DiffError::Backend)?.remove(0);
+            return Err(DiffError::Diverged(Box::new(Divergence { backend: backend.name().to_string(), case, expected, actual })));
+        }
+    }
+
+    let mut names = vec![reference.name.clone()];
+    names.extend(backends.iter().map(|b| b.name().to_string()));
+    Ok(DiffReport { handlers: keys.len(), cases: cases.len(), backends: names })
+}
+
+/// Load every handler of the corpus in `dir`, keyed by (relative path, entity, event).
+/// Calls to `function`s defined in the same file are inlined, since the interpreter has none.
+fn load_handlers(dir: &str) -> Result<HashMap<HandlerKey, Event>, DiffError> {
+    let mut handlers = HashMap::new();
+    let corpus = load_corpus(dir, &CorpusOptions::default());
+    if let Some(e) = corpus.errors.first() {
+        return Err(DiffError::Io(e.to_string()));
+    }
+    for file in corpus.files {
+        let fname = file.relative;
+        let helpers: Vec<FunctionDef> = file.functions.iter().map(FunctionDef::from).collect();
+        for e in file.entities {
+            for mut ev in e.events {
+                if helpers.iter().any(|h| ev.body.contains(h.name.as_str())) {
+                    let stmts = inline_functions(&parse_statements(&ev.body), &helpers);
+                    ev.body = stmts.iter().map(stmt_to_string).collect::<Vec<_>>().join(" ");
+                }
+                handlers.entry((fname.clone(), e.name.clone(), ev.name.clone())).or_insert(ev);
+            }
+        }
+    }
+    Ok(handlers)
+}
+
+impl Backend for InterpreterBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&mut self, cases: &[Case]) -> Result<Vec<Option<Observation>>, String> {
+        Ok(cases.iter().map(|case| {
+            let ev = self.handlers.get(&case.key())?;
+            let mut entity = EntityInstance::new(&case.entity, &case.entity);
+            entity.health = case.health;
+            entity.velocity = case.velocity;
+            entity.position = case.position;
+            let params: HashMap<String, Value> = case.params.iter().cloned().collect();
+            let effects = execute_event_traced(&mut entity, ev, &params);
+            Some(Observation {
+                health: entity.health,
+                velocity: entity.velocity,
+                position: entity.position,
+                effects: effects.into_iter().map(normalize).collect(),
+            })
+        }).collect())
+    }
+}
+
+impl Backend for AotBackend {
+    fn name(&self) -> &str {
+        "aot"
+    }
+
+    fn run(&mut self, cases: &[Case]) -> Result<Vec<Option<Observation>>, String> {
+        let mut keys: Vec<&HandlerKey> = self.handlers.keys().collect();
+        keys.sort();
+        let bodies: Vec<String> = keys.iter().map(|k| self.handlers[*k].body.clone()).collect();
+        let index: HashMap<&HandlerKey, usize> = keys.iter().enumerate().map(|(i, k)| (*k, i)).collect();
+
+        let mut slots = Vec::new();
+        let mut aot_cases = Vec::new();
+        for case in cases {
+            match index.get(&case.key()) {
+                Some(&handler) => {
+                    slots.push(Some(aot_cases.len()));
+                    aot_cases.push(case.to_aot(handler));
+                }
+                None => slots.push(None),
+            }
+        }
+        let outcomes = run_aot_cases(&self.dir, &bodies, &aot_cases)?;
+        slots.into_iter().map(|slot| match slot {
+            Some(i) => {
+                let o = &outcomes[i];
+                let effects = o.effects.iter().map(|e| decode_effect(e)).collect::<Result<Vec<_>, _>>()?;
+                Ok(Some(Observation { health: o.health, velocity: o.velocity, position: o.position, effects }))
+            }
+            None => Ok(None),
+        }).collect()
+    }
+}
+
+impl Case {
+    fn key(&self) -> HandlerKey {
+        (self.file.clone(), self.entity.clone(), self.event.clone())
+    }
+
+    /// Project the case onto what the executor can observe: `dt` only as a float,
+    /// other parameters only through `.tag` of entity snapshots.
+    fn to_aot(&self, handler: usize) -> AotCase {
+        let mut dt = None;
+        let mut tags = Vec::new();
+        for (name, v) in &self.params {
+            match v {
+                Value::Float(f) if name == "dt" => dt = Some(*f),
+                Value::EntitySnapshot(se) => tags.push((name.clone(), se.tag.clone())),
+                _ => {}
+            }
+        }
+        AotCase { handler, health: self.health, velocity: self.velocity, position: self.position, dt, tags }
+    }
+
+    /// Strictly simpler variants of this case, used to shrink a counterexample.
+    fn shrink_candidates(&self) -> Vec<Case> {
+        let mut out = Vec::new();
+        for i in 0..self.params.len() {
+            let mut c = self.clone();
+            c.params.remove(i);
+            out.push(c);
+        }
+        for i in 0..self.params.len() {
+            let simpler = match &self.params[i].1 {
+                Value::Float(f) if *f != 0.0 => Some(Value::Float(0.0)),
+                Value::Int(n) if *n != 0 => Some(Value::Int(0)),
+                Value::Str(s) if !s.is_empty() => Some(Value::Str(String::new())),
+                Value::EntitySnapshot(se) if !se.tag.is_empty() || se.health != 100 => {
+                    Some(Value::EntitySnapshot(SimpleEntity { name: se.name.clone(), tag: String::new(), health: 100 }))
+                }
+                _ => None,
+            };
+            if let Some(v) = simpler {
+                let mut c = self.clone();
+                c.params[i].1 = v;
+                out.push(c);
+            }
+        }
+        if self.health != 100 { out.push(Case { health: 100, ..self.clone() }); }
+        if self.velocity != 0.0 { out.push(Case { velocity: 0.0, ..self.clone() }); }
+        if self.position != 0.0 { out.push(Case { position: 0.0, ..self.clone() }); }
+        out
+    }
+}
+
+/// Greedily shrink a diverging case: evaluate all one-step simplifications in a single batch
+/// per backend and keep the first that still diverges, until none does.
+fn minimise(mut case: Case, reference: &mut dyn Backend, backend: &mut dyn Backend) -> Result<Case, String> {
+    loop {
+        let candidates = case.shrink_candidates();
+        if candidates.is_empty() { return Ok(case); }
+        let expected = reference.run(&candidates)?;
+        let actual = backend.run(&candidates)?;
+        match (0..candidates.len()).find(|&i| expected[i] != actual[i]) {
+            Some(i) => case = candidates[i].clone(),
+            None => return Ok(case),
+        }
+    }
+}
+
+fn normalize(effect: Effect) -> Effect {
+    match effect {
+        Effect::Unrecognized(s) => Effect::Unrecognized(s.chars().filter(|c| !c.is_whitespace()).collect()),
+        other => other,
+    }
+}
+
+fn decode_effect(encoded: &str) -> Result<Effect, String> {
+    let bad = || format!("unknown AOT effect: {}", encoded);
+    match encoded.split_once(' ') {
+        Some(("M", d)) => d.parse().map(Effect::Moved).map_err(|_| bad()),
+        Some(("D", v)) => v.parse().map(Effect::Damaged).map_err(|_| bad()),
+        Some(("U", s)) => Ok(Effect::Unrecognized(s.to_string())),
+        None if encoded == "C" => Ok(Effect::Collided),
+        None if encoded == "F" => Ok(Effect::Freed),
+        _ => Err(bad()),
+    }
+}
+
+// deterministic xorshift generator so failures are reproducible from the seed
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[(self.next() % items.len() as u64) as usize]
+    }
+}
+
+fn generate_case(rng: &mut Rng, key: &HandlerKey, ev: &Event) -> Case {
+    const TAGS: [&str; 4] = ["Enemy", "Player", "Ally", ""];
+    let mut params = Vec::new();
+    for name in ev.params.as_deref().unwrap_or("").split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let v = match rng.next() % 4 {
+            0 => Value::Float(*rng.pick(&[0.0, 0.016, 0.5, 1.0, 2.0])),
+            1 => Value::Int(*rng.pick(&[0, 1, 10, -3])),
+            2 => Value::Str(rng.pick(&TAGS).to_string()),
+            _ => Value::EntitySnapshot(SimpleEntity {
+                name: "Other".to_string(),
+                tag: rng.pick(&TAGS).to_string(),
+                health: *rng.pick(&[100, 50, 0]),
+            }),
+        };
+        params.push((name.to_string(), v));
+    }
+    Case {
+        file: key.0.clone(),
+        entity: key.1.clone(),
+        event: key.2.clone(),
+        health: *rng.pick(&[100, 50, 1, 0]),
+        velocity: *rng.pick(&[0.0, 5.0, -2.5, 1.5]),
+        position: *rng.pick(&[0.0, 10.0, -3.0]),
+        params,
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} diverges from the interpreter on {}:{}.{}", self.backend, self.case.file, self.case.entity, self.case.event)?;
+        writeln!(f, "  initial: health={} velocity={} position={}", self.case.health, self.case.velocity, self.case.position)?;
+        writeln!(f, "  params:  {:?}", self.case.params)?;
+        writeln!(f, "  expected: {:?}", self.expected)?;
+        write!(f, "  actual:   {:?}", self.actual)
+    }
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiffError::Io(e) => write!(f, "I/O error: {}", e),
+            DiffError::Backend(e) => write!(f, "backend error: {}", e),
+            DiffError::Diverged(d) => write!(f, "{}", d),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler(body: &str) -> HashMap<HandlerKey, Event> {
+        let ev = Event { name: "Hit".to_string(), params: Some("target, dt".to_string()), body: body.to_string() };
+        let mut m = HashMap::new();
+        m.insert(("a.meta".to_string(), "A".to_string(), "Hit".to_string()), ev);
+        m
+    }
+
+    #[test]
+    fn minimises_counterexample() {
+        let mut reference = InterpreterBackend { name: "interpreter".to_string(), handlers: handler("if (target.tag == \"Enemy\") { takeDamage(10); }") };
+        let mut broken = InterpreterBackend { name: "broken".to_string(), handlers: handler("if (target.tag == \"Enemy\") { takeDamage(11); }") };
+        let case = Case {
+            file: "a.meta".to_string(),
+            entity: "A".to_string(),
+            event: "Hit".to_string(),
+            health: 50,
+            velocity: 5.0,
+            position: 10.0,
+            params: vec![
+                ("target".to_string(), Value::EntitySnapshot(SimpleEntity { name: "E".to_string(), tag: "Enemy".to_string(), health: 100 })),
+                ("dt".to_string(), Value::Float(0.5)),
+            ],
+        };
+        let min = minimise(case, &mut reference, &mut broken).unwrap();
+        assert_eq!(min.health, 100);
+        assert_eq!(min.velocity, 0.0);
+        assert_eq!(min.position, 0.0);
+        // the Enemy tag is what triggers the divergence, so it must survive shrinking
+        assert_eq!(min.params.len(), 1);
+        assert!(matches!(&min.params[0].1, Value::EntitySnapshot(se) if se.tag == "Enemy"));
+    }
+
+    #[test]
+    fn compiled_handler_matches_interpreter() {
+        let dir = std::env::temp_dir().join(format!("experiment_difftest_unit_{}", std::process::id()));
+        let body = "move(velocity * dt); collide(); takeDamage(3)";
+        let mut interp = InterpreterBackend { name: "interpreter".to_string(), handlers: handler(body) };
+        let mut aot = AotBackend { dir: dir.to_string_lossy().to_string(), handlers: handler(body) };
+        let case = Case {
+            file: "a.meta".to_string(),
+            entity: "A".to_string(),
+            event: "Hit".to_string(),
+            health: 100,
+            velocity: 1.5,
+            position: -3.0,
+            params: vec![("dt".to_string(), Value::Float(0.016))],
+        };
+        let cases = vec![case];
+        assert_eq!(interp.run(&cases).unwrap(), aot.run(&cases).unwrap());
+    }
+}
@@ -0,0 +1,513 @@
+//! Positive supercompilation of handler bodies.
+//!
+//! Handler code is driven symbolically against partially known inputs: known identifiers
+//! are substituted, statically decided `if`s are pruned, and calls to `function`s are
+//! unfolded. Every unfolding is a node of the process tree; before unfolding, the call is
+//! compared with the calls on the path from the root. If an ancestor is a renaming of it we
+//! fold, if an ancestor is homeomorphically embedded in it we generalize, and the folded or
+//! generalized nodes are residualized as specialized functions.
+
+use std::collections::{HashMap, HashSet};
+use crate::ast::{Expr, Stmt, expr_to_string, fold_expr, parse_statements};
+use crate::meta_lang::Function;
+use crate::runtime::tag_guard;
+
+/// Identifiers (and `var.tag` selectors) whose value is known at specialization time.
+pub type KnownEnv = HashMap<String, Expr>;
+
+/// A `function` definition with its body parsed.
+#[derive(Debug, Clone)]
+pub struct FunctionDef {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Stmt>,
+}
+
+impl From<&Function> for FunctionDef {
+    fn from(f: &Function) -> Self {
+        let params = f.params.as_deref().unwrap_or("")
+            .split(',')
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+        Self { name: f.name.clone(), params, body: parse_statements(&f.body) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// Root of the tree: the handler being driven.
+    Start,
+    /// `if` with an undecided condition; both outcomes stay in the residual code.
+    Branch(String),
+    /// `if` decided at drive time; the condition and the dead outcome disappear.
+    Prune(String, bool),
+    /// Call of a `function`, replaced by its driven body.
+    Unfold(String),
+    /// Call that is a renaming of the ancestor node; becomes a call of its residual function.
+    Fold(usize),
+    /// Call in which the ancestor node is embedded; driven again in generalized form.
+    Generalize(usize),
+    /// Unfolding stopped by the depth limit; the call is kept as is.
+    Stop(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessNode {
+    pub parent: Option<usize>,
+    pub step: Step,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProcessTree {
+    pub nodes: Vec<ProcessNode>,
+}
+
+impl ProcessTree {
+    pub fn count(&self, pred: impl Fn(&Step) -> bool) -> usize {
+        self.nodes.iter().filter(|n| pred(&n.step)).count()
+    }
+}
+
+/// Driven handler body together with the specialized functions it calls.
+#[derive(Debug, Clone)]
+pub struct Residual {
+    pub body: Vec<Stmt>,
+    pub functions: Vec<FunctionDef>,
+    pub tree: ProcessTree,
+}
+
+/// Unfoldings along one path before the driver gives up and keeps the call.
+const MAX_DEPTH: usize = 64;
+
+/// Drives handlers of one compilation unit; residual functions accumulate across handlers
+/// so their names stay unique within the unit.
+pub struct Supercompiler {
+    functions: HashMap<String, FunctionDef>,
+    residuals: Vec<FunctionDef>,
+    counter: usize,
+}
+
+impl Supercompiler {
+    pub fn new(functions: &[FunctionDef]) -> Self {
+        Self {
+            functions: functions.iter().map(|f| (f.name.clone(), f.clone())).collect(),
+            residuals: Vec::new(),
+            counter: 0,
+        }
+    }
+
+    pub fn drive(&mut self, body: &[Stmt], env: &KnownEnv) -> (Vec<Stmt>, ProcessTree) {
+        let mut driver = Driver { sc: self, tree: ProcessTree::default(), path: Vec::new(), pending: HashMap::new() };
+        let root = driver.node(None, Step::Start);
+        let body = driver.drive_block(body, env, root);
+        (body, driver.tree)
+    }
+
+    pub fn residual_functions(&self) -> &[FunctionDef] {
+        &self.residuals
+    }
+}
+
+pub fn supercompile(body: &[Stmt], env: &KnownEnv, functions: &[FunctionDef]) -> Residual {
+    let mut sc = Supercompiler::new(functions);
+    let (body, tree) = sc.drive(body, env);
+    Residual { body, functions: sc.residuals, tree }
+}
+
+/// Replace calls of `functions` by their bodies with parameters substituted (one level deep,
+/// also inside `if` bodies).
+pub fn inline_functions(stmts: &[Stmt], functions: &[FunctionDef]) -> Vec<Stmt> {
+    let mut out = Vec::new();
+    for st in stmts {
+        match st {
+            Stmt::Expr(Expr::Call { name, args }) => match functions.iter().find(|f| &f.name == name) {
+                Some(f) => {
+                    let bindings: HashMap<String, Expr> = f.params.iter().cloned().zip(args.iter().cloned()).collect();
+                    out.extend(f.body.iter().map(|b| subst_stmt(b, &bindings)));
+                }
+                None => out.push(st.clone()),
+            },
+            Stmt::If { cond, body } => out.push(Stmt::If { cond: cond.clone(), body: inline_functions(body, functions) }),
+            _ => out.push(st.clone()),
+        }
+    }
+    out
+}
+
+/// Worst-case number of interpreted statements, counting a `function` call as one step
+/// plus its body.
+pub fn static_cost(stmts: &[Stmt], functions: &[FunctionDef]) -> usize {
+    let table: HashMap<&str, &FunctionDef> = functions.iter().map(|f| (f.name.as_str(), f)).collect();
+    cost_with(stmts, &table, 0)
+}
+
+fn cost_with(stmts: &[Stmt], table: &HashMap<&str, &FunctionDef>, depth: usize) -> usize {
+    stmts.iter().map(|st| match st {
+        Stmt::Empty => 0,
+        Stmt::If { body, .. } => 1 + cost_with(body, table, depth),
+        Stmt::Expr(Expr::Call { name, .. }) => match table.get(name.as_str()) {
+            Some(f) if depth < MAX_DEPTH => 1 + cost_with(&f.body, table, depth + 1),
+            _ => 1,
+        },
+        Stmt::Expr(_) => 1,
+    }).sum()
+}
+
+struct PathEntry {
+    node: usize,
+    config: Expr,
+}
+
+struct Driver<'a> {
+    sc: &'a mut Supercompiler,
+    tree: ProcessTree,
+    /// Unfolded calls from the root to the node being driven.
+    path: Vec<PathEntry>,
+    /// Residual function names for nodes that something folded into.
+    pending: HashMap<usize, String>,
+}
+
+impl Driver<'_> {
+    fn node(&mut self, parent: Option<usize>, step: Step) -> usize {
+        self.tree.nodes.push(ProcessNode { parent, step });
+        self.tree.nodes.len() - 1
+    }
+
+    fn drive_block(&mut self, stmts: &[Stmt], env: &KnownEnv, parent: usize) -> Vec<Stmt> {
+        let mut out = Vec::new();
+        for st in stmts {
+            match st {
+                Stmt::Empty => {}
+                Stmt::If { cond, body } => {
+                    let cond = reduce(cond, env);
+                    let text = expr_to_string(&cond);
+                    match decide(&cond, env) {
+                        Some(taken) => {
+                            let id = self.node(Some(parent), Step::Prune(text, taken));
+                            if taken { out.extend(self.drive_block(body, env, id)); }
+                        }
+                        None => {
+                            let id = self.node(Some(parent), Step::Branch(text));
+                            let inner_env = assume(&cond, env);
+                            let body = self.drive_block(body, &inner_env, id);
+                            out.push(Stmt::If { cond, body });
+                        }
+                    }
+                }
+                Stmt::Expr(Expr::Call { name, args }) if self.sc.functions.contains_key(name) => {
+                    let args = args.iter().map(|a| reduce(a, env)).collect();
+                    out.extend(self.drive_call(name, args, env, parent));
+                }
+                Stmt::Expr(e) => out.push(Stmt::Expr(reduce(e, env))),
+            }
+        }
+        out
+    }
+
+    fn drive_call(&mut self, name: &str, args: Vec<Expr>, env: &KnownEnv, parent: usize) -> Vec<Stmt> {
+        let config = Expr::Call { name: name.to_string(), args };
+
+        // fold into an ancestor this call is a renaming (instance) of
+        for i in (0..self.path.len()).rev() {
+            if let Some(subst) = instance_of(&self.path[i].config, &config) {
+                let anc = self.path[i].node;
+                let vars = free_vars(&self.path[i].config);
+                self.node(Some(parent), Step::Fold(anc));
+                let fname = self.residual_name(anc, name);
+                let call_args = vars.iter().map(|v| subst.get(v).cloned().unwrap_or_else(|| Expr::Ident(v.clone()))).collect();
+                return vec![Stmt::Expr(Expr::Call { name: fname, args: call_args })];
+            }
+        }
+
+        // whistle: an ancestor is embedded in this call, so drive the generalization instead
+        let whistle = self.path.iter().rev()
+            .find(|p| matches!(&p.config, Expr::Call { name: n, .. } if n == name) && embeds(&p.config, &config))
+            .map(|p| (p.node, p.config.clone()));
+        if let Some((anc, anc_config)) = whistle {
+            let (general, subst) = generalize(&anc_config, &config, &self.sc.functions[name].params);
+            let id = self.node(Some(parent), Step::Generalize(anc));
+            let vars = free_vars(&general);
+            let fname = self.residual_name(id, name);
+            self.unfold(id, general, env);
+            let call_args = vars.iter().map(|v| subst.get(v).cloned().unwrap_or_else(|| Expr::Ident(v.clone()))).collect();
+            return vec![Stmt::Expr(Expr::Call { name: fname, args: call_args })];
+        }
+
+        if self.path.len() >= MAX_DEPTH {
+            self.node(Some(parent), Step::Stop(expr_to_string(&config)));
+            return vec![Stmt::Expr(config)];
+        }
+
+        let id = self.node(Some(parent), Step::Unfold(expr_to_string(&config)));
+        let body = self.unfold(id, config.clone(), env);
+        match self.pending.get(&id) {
+            // something folded back into this node: it becomes a residual function
+            Some(fname) => {
+                let args = free_vars(&config).into_iter().map(Expr::Ident).collect();
+                vec![Stmt::Expr(Expr::Call { name: fname.clone(), args })]
+            }
+            None => body,
+        }
+    }
+
+    /// Drive the body of the called function with parameters bound to the call arguments.
+    /// If the node ends up with a residual function, its definition is recorded.
+    fn unfold(&mut self, id: usize, config: Expr, env: &KnownEnv) -> Vec<Stmt> {
+        let Expr::Call { name, args } = &config else { return Vec::new() };
+        let f = self.sc.functions[name].clone();
+        let bindings: HashMap<String, Expr> = f.params.iter().cloned().zip(args.iter().cloned()).collect();
+        let body: Vec<Stmt> = f.body.iter().map(|st| subst_stmt(st, &bindings)).collect();
+
+        self.path.push(PathEntry { node: id, config: config.clone() });
+        let driven = self.drive_block(&body, env, id);
+        self.path.pop();
+
+        if let Some(fname) = self.pending.get(&id) {
+            self.sc.residuals.push(FunctionDef { name: fname.clone(), params: free_vars(&config), body: driven.clone() });
+        }
+        driven
+    }
+
+    fn residual_name(&mut self, node: usize, function: &str) -> String {
+        if let Some(name) = self.pending.get(&node) { return name.clone(); }
+        self.sc.counter += 1;
+        let name = format!("{}__s{}", function, self.sc.counter);
+        self.pending.insert(node, name.clone());
+        name
+    }
+}
+
+/// Substitute known identifiers and fold constants.
+fn reduce(e: &Expr, env: &KnownEnv) -> Expr {
+    let mut out = match e {
+        Expr::Ident(name) => env.get(name).cloned().unwrap_or_else(|| e.clone()),
+        Expr::Call { name, args } => Expr::Call { name: name.clone(), args: args.iter().map(|a| reduce(a, env)).collect() },
+        Expr::BinaryOp { op, lhs, rhs } => Expr::BinaryOp { op: *op, lhs: Box::new(reduce(lhs, env)), rhs: Box::new(reduce(rhs, env)) },
+        other => other.clone(),
+    };
+    fold_expr(&mut out);
+    out
+}
+
+/// Decide a condition at drive time if the environment allows it.
+fn decide(cond: &Expr, env: &KnownEnv) -> Option<bool> {
+    match cond {
+        Expr::Int(i) => Some(*i != 0),
+        Expr::Float(f) => Some(*f != 0.0),
+        Expr::Str(s) => Some(!s.is_empty()),
+        Expr::Ident(text) => {
+            let (var, tag) = tag_guard(text)?;
+            match env.get(&format!("{}.tag", var)) {
+                Some(Expr::Str(known)) => Some(known == tag),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Positive information propagation: inside `if (other.tag == "Enemy")` the tag is known.
+fn assume(cond: &Expr, env: &KnownEnv) -> KnownEnv {
+    let mut env = env.clone();
+    if let Expr::Ident(text) = cond {
+        if let Some((var, tag)) = tag_guard(text) {
+            env.insert(format!("{}.tag", var), Expr::Str(tag.to_string()));
+        }
+    }
+    env
+}
+
+pub(crate) fn subst_stmt(st: &Stmt, b: &HashMap<String, Expr>) -> Stmt {
+    match st {
+        Stmt::Expr(e) => Stmt::Expr(subst_expr(e, b)),
+        Stmt::If { cond, body } => Stmt::If { cond: subst_expr(cond, b), body: body.iter().map(|s| subst_stmt(s, b)).collect() },
+        Stmt::Empty => Stmt::Empty,
+    }
+}
+
+fn subst_expr(e: &Expr, b: &HashMap<String, Expr>) -> Expr {
+    match e {
+        Expr::Ident(name) => {
+            if let Some(v) = b.get(name) { return v.clone(); }
+            // rename the variable of a tag guard when the parameter is bound to another variable
+            if let Some((var, tag)) = tag_guard(name) {
+                if let Some(Expr::Ident(actual)) = b.get(var) {
+                    return Expr::Ident(format!("{}.tag == \"{}\"", actual, tag));
+                }
+            }
+            e.clone()
+        }
+        Expr::Call { name, args } => Expr::Call { name: name.clone(), args: args.iter().map(|a| subst_expr(a, b)).collect() },
+        Expr::BinaryOp { op, lhs, rhs } => Expr::BinaryOp { op: *op, lhs: Box::new(subst_expr(lhs, b)), rhs: Box::new(subst_expr(rhs, b)) },
+        other => other.clone(),
+    }
+}
+
+/// Homeomorphic embedding `a ⊴ b` over expressions. Identifiers embed each other and so do
+/// literals of the same kind, which keeps the alphabet finite and the whistle sound.
+pub fn embeds(a: &Expr, b: &Expr) -> bool {
+    couples(a, b) || dives(a, b)
+}
+
+fn dives(a: &Expr, b: &Expr) -> bool {
+    match b {
+        Expr::Call { args, .. } => args.iter().any(|x| embeds(a, x)),
+        Expr::BinaryOp { lhs, rhs, .. } => embeds(a, lhs) || embeds(a, rhs),
+        _ => false,
+    }
+}
+
+fn couples(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::Ident(_), Expr::Ident(_)) => true,
+        (Expr::Int(_), Expr::Int(_)) | (Expr::Float(_), Expr::Float(_)) | (Expr::Str(_), Expr::Str(_)) => true,
+        (Expr::Call { name: n1, args: a1 }, Expr::Call { name: n2, args: a2 }) => {
+            n1 == n2 && a1.len() == a2.len() && a1.iter().zip(a2).all(|(x, y)| embeds(x, y))
+        }
+        (Expr::BinaryOp { op: o1, lhs: l1, rhs: r1 }, Expr::BinaryOp { op: o2, lhs: l2, rhs: r2 }) => {
+            o1 == o2 && embeds(l1, l2) && embeds(r1, r2)
+        }
+        _ => false,
+    }
+}
+
+/// If `term` is an instance of `pattern` (identifiers in `pattern` are variables),
+/// return the substitution.
+fn instance_of(pattern: &Expr, term: &Expr) -> Option<HashMap<String, Expr>> {
+    let mut subst = HashMap::new();
+    if match_expr(pattern, term, &mut subst) { Some(subst) } else { None }
+}
+
+fn match_expr(p: &Expr, t: &Expr, subst: &mut HashMap<String, Expr>) -> bool {
+    match (p, t) {
+        (Expr::Ident(v), _) => match subst.get(v) {
+            Some(bound) => same(bound, t),
+            None => { subst.insert(v.clone(), t.clone()); true }
+        },
+        (Expr::Int(a), Expr::Int(b)) => a == b,
+        (Expr::Float(a), Expr::Float(b)) => a == b,
+        (Expr::Str(a), Expr::Str(b)) => a == b,
+        (Expr::Call { name: n1, args: a1 }, Expr::Call { name: n2, args: a2 }) => {
+            n1 == n2 && a1.len() == a2.len() && a1.iter().zip(a2).all(|(x, y)| match_expr(x, y, subst))
+        }
+        (Expr::BinaryOp { op: o1, lhs: l1, rhs: r1 }, Expr::BinaryOp { op: o2, lhs: l2, rhs: r2 }) => {
+            o1 == o2 && match_expr(l1, l2, subst) && match_expr(r1, r2, subst)
+        }
+        _ => false,
+    }
+}
+
+fn same(a: &Expr, b: &Expr) -> bool {
+    expr_to_string(a) == expr_to_string(b)
+}
+
+/// Most specific generalization of two calls of the same function. Differing top-level
+/// arguments are named after the function parameters; deeper ones get fresh names.
+/// Returns the generalized call and the substitution that turns it back into `b`.
+fn generalize(a: &Expr, b: &Expr, params: &[String]) -> (Expr, HashMap<String, Expr>) {
+    let mut subst = HashMap::new();
+    let mut taken: HashSet<String> = free_vars(a).into_iter().chain(free_vars(b)).collect();
+    let general = match (a, b) {
+        (Expr::Call { name, args: a1 }, Expr::Call { args: a2, .. }) => {
+            let args = a1.iter().zip(a2).enumerate().map(|(i, (x, y))| {
+                let hint = params.get(i).map(|p| p.as_str()).unwrap_or("v");
+                msg(x, y, hint, &mut subst, &mut taken)
+            }).collect();
+            Expr::Call { name: name.clone(), args }
+        }
+        _ => a.clone(),
+    };
+    (general, subst)
+}
+
+fn msg(a: &Expr, b: &Expr, hint: &str, subst: &mut HashMap<String, Expr>, taken: &mut HashSet<String>) -> Expr {
+    if same(a, b) && free_vars(a).is_empty() { return a.clone(); }
+    match (a, b) {
+        (Expr::Ident(x), Expr::Ident(y)) if x == y => a.clone(),
+        (Expr::BinaryOp { op: o1, lhs: l1, rhs: r1 }, Expr::BinaryOp { op: o2, lhs: l2, rhs: r2 }) if o1 == o2 => {
+            Expr::BinaryOp { op: *o1, lhs: Box::new(msg(l1, l2, "v", subst, taken)), rhs: Box::new(msg(r1, r2, "v", subst, taken)) }
+        }
+        (Expr::Call { name: n1, args: a1 }, Expr::Call { name: n2, args: a2 }) if n1 == n2 && a1.len() == a2.len() => {
+            Expr::Call { name: n1.clone(), args: a1.iter().zip(a2).map(|(x, y)| msg(x, y, "v", subst, taken)).collect() }
+        }
+        _ => {
+            let mut name = hint.to_string();
+            let mut k = 1;
+            while taken.contains(&name) { name = format!("{}{}", hint, k); k += 1; }
+            taken.insert(name.clone());
+            subst.insert(name.clone(), b.clone());
+            Expr::Ident(name)
+        }
+    }
+}
+
+/// Identifiers occurring in an expression, in order of first occurrence.
+fn free_vars(e: &Expr) -> Vec<String> {
+    fn walk(e: &Expr, out: &mut Vec<String>) {
+        match e {
+            Expr::Ident(v) if !out.contains(v) => out.push(v.clone()),
+            Expr::Call { args, .. } => for a in args { walk(a, out) },
+            Expr::BinaryOp { lhs, rhs, .. } => { walk(lhs, out); walk(rhs, out) }
+            _ => {}
+        }
+    }
+    let mut out = Vec::new();
+    walk(e, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::stmt_to_string;
+    use crate::meta_lang::parse_functions;
+
+    fn functions(src: &str) -> Vec<FunctionDef> {
+        parse_functions(src).iter().map(FunctionDef::from).collect()
+    }
+
+    fn render(stmts: &[Stmt]) -> String {
+        stmts.iter().map(stmt_to_string).collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn known_tag_prunes_guard() {
+        let body = parse_statements(r#"if (other.tag == "Enemy") { takeDamage(10); }"#);
+        let mut env = KnownEnv::new();
+        env.insert("other.tag".to_string(), Expr::Str("Enemy".to_string()));
+        let r = supercompile(&body, &env, &[]);
+        assert_eq!(render(&r.body), "takeDamage(10);");
+        assert!(static_cost(&r.body, &[]) < static_cost(&body, &[]));
+    }
+
+    #[test]
+    fn unknown_recursion_folds_into_residual_function() {
+        let fs = functions("function countdown(n) { tick(); if (n) { countdown(n - 1); } }");
+        let body = parse_statements("countdown(lives);");
+        let r = supercompile(&body, &KnownEnv::new(), &fs);
+        assert_eq!(r.functions.len(), 1);
+        let f = &r.functions[0];
+        assert_eq!(render(&r.body), format!("{}(lives);", f.name));
+        assert_eq!(render(&f.body), format!("tick(); if (lives) {{ {}(lives - 1);  }}", f.name));
+        assert_eq!(r.tree.count(|s| matches!(s, Step::Fold(_))), 1);
+    }
+
+    #[test]
+    fn growing_constants_trigger_generalization() {
+        let fs = functions("function climb(n) { step(n); if (n) { climb(n + 1); } }");
+        let body = parse_statements("climb(1);");
+        let r = supercompile(&body, &KnownEnv::new(), &fs);
+        assert_eq!(r.tree.count(|s| matches!(s, Step::Generalize(_))), 1);
+        assert_eq!(r.functions.len(), 1);
+        assert_eq!(render(&r.body), format!("step(1); {}(2);", r.functions[0].name));
+    }
+
+    #[test]
+    fn embedding() {
+        let a = Expr::Call { name: "f".to_string(), args: vec![Expr::Ident("x".to_string())] };
+        let b = Expr::Call { name: "f".to_string(), args: vec![Expr::BinaryOp { op: '-', lhs: Box::new(Expr::Ident("x".to_string())), rhs: Box::new(Expr::Int(1)) }] };
+        assert!(embeds(&a, &b));
+        assert!(!embeds(&b, &a));
+    }
+}
@@ -0,0 +1,631 @@
+use std::any::{Any, TypeId};
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use crate::components::{Health, Input, Physics, Sprite, Transform, Velocity};
+use crate::game_engine::Node;
+use crate::spawner::TypedComponent;
+
+/// Сущность ECS: индекс слота и поколение, чтобы ID удалённой сущности не совпал с новой
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entity {
+    pub index: u32,
+    pub generation: u32,
+}
+
+/// Любой тип, который можно хранить в колонке архетипа
+pub trait Component: Any + Send + Sync {}
+impl<T: Any + Send + Sync> Component for T {}
+
+/// Нода, из которой создана сущность (мост между деревом нод и ECS)
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeRef {
+    pub id: String,
+    pub name: String,
+}
+
+/// Родитель сущности в дереве нод
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parent(pub Entity);
+
+/// Колонка однотипных компонентов архетипа
+trait Column: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn empty(&self) -> Box<dyn Column>;
+    fn swap_remove(&mut self, row: usize);
+    /// Переносит строку в колонку того же типа другого архетипа
+    fn move_row(&mut self, row: usize, to: &mut dyn Column);
+}
+
+impl<T: Component> Column for Vec<T> {
+    fn as_any(&self) -> &dyn Any { self }
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+    fn empty(&self) -> Box<dyn Column> { Box::new(Vec::<T>::new()) }
+    fn swap_remove(&mut self, row: usize) { Vec::swap_remove(self, row); }
+    fn move_row(&mut self, row: usize, to: &mut dyn Column) {
+        let value = Vec::swap_remove(self, row);
+        to.as_any_mut().downcast_mut::<Vec<T>>().expect("column type mismatch").push(value);
+    }
+}
+
+/// Колонка со счётчиком заимствований: запросы из разных потоков (`World::query_shared`)
+/// берут её на чтение (> 0) или запись (-1), пересечение — паника, а не гонка данных
+struct ColumnCell {
+    data: UnsafeCell<Box<dyn Column>>,
+    borrow: AtomicIsize,
+}
+
+// SAFETY: изменяемый доступ через `&ColumnCell` возможен только после захвата `borrow` на запись
+unsafe impl Sync for ColumnCell {}
+
+impl ColumnCell {
+    fn new(column: Box<dyn Column>) -> Self {
+        Self { data: UnsafeCell::new(column), borrow: AtomicIsize::new(0) }
+    }
+
+    fn get(&self) -> &dyn Column {
+        // SAFETY: `&World` не отдаётся системам во время параллельного выполнения,
+        // а запись по `&World` идёт только через захваченный `borrow`
+        unsafe { &**self.data.get() }
+    }
+
+    fn get_mut(&mut self) -> &mut dyn Column {
+        self.data.get_mut().as_mut()
+    }
+
+    fn acquire(&self, write: bool) -> bool {
+        if write {
+            return self.borrow.compare_exchange(0, -1, Ordering::Acquire, Ordering::Relaxed).is_ok();
+        }
+        let mut current = self.borrow.load(Ordering::Relaxed);
+        while current >= 0 {
+            match self.borrow.compare_exchange_weak(current, current + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(now) => current = now,
+            }
+        }
+        false
+    }
+
+    fn release(&self, write: bool) {
+        if write {
+            self.borrow.store(0, Ordering::Release);
+        } else {
+            self.borrow.fetch_sub(1, Ordering::Release);
+        }
+    }
+}
+
+/// Все сущности с одинаковым набором компонентов; строка `i` каждой колонки
+/// принадлежит `entities[i]`
+pub struct Archetype {
+    types: Vec<TypeId>,
+    columns: HashMap<TypeId, ColumnCell>,
+    entities: Vec<Entity>,
+}
+
+impl Archetype {
+    pub fn has(&self, t: TypeId) -> bool {
+        self.columns.contains_key(&t)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    fn column<T: Component>(&self) -> Option<&Vec<T>> {
+        self.columns.get(&TypeId::of::<T>())?.get().as_any().downcast_ref()
+    }
+
+    fn column_mut<T: Component>(&mut self) -> Option<&mut Vec<T>> {
+        self.columns.get_mut(&TypeId::of::<T>())?.get_mut().as_any_mut().downcast_mut()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    generation: u32,
+    /// Архетип и строка живой сущности
+    location: Option<(usize, usize)>,
+}
+
+/// Мир ECS: сущности, архетипы и их колонки
+#[derive(Default)]
+pub struct World {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    archetypes: Vec<Archetype>,
+    by_types: HashMap<Vec<TypeId>, usize>,
+}
+
+/// Пустые колонки нового архетипа
+pub struct Columns(HashMap<TypeId, Box<dyn Column>>);
+
+impl Columns {
+    fn with<T: Component>(mut self) -> Self {
+        self.0.insert(TypeId::of::<T>(), Box::new(Vec::<T>::new()));
+        self
+    }
+}
+
+/// Набор компонентов, создаваемых вместе: `world.spawn((Transform::new(), Velocity::default()))`
+pub trait Bundle: Send + Sync + 'static {
+    fn type_ids() -> Vec<TypeId>;
+    fn columns() -> Columns;
+    fn push(self, archetype: &mut Archetype);
+}
+
+macro_rules! impl_bundle {
+    ($($t:ident),*) => {
+        impl<$($t: Component),*> Bundle for ($($t,)*) {
+            fn type_ids() -> Vec<TypeId> {
+                vec![$(TypeId::of::<$t>()),*]
+            }
+
+            fn columns() -> Columns {
+                Columns(HashMap::new())$(.with::<$t>())*
+            }
+
+            #[allow(non_snake_case)]
+            fn push(self, archetype: &mut Archetype) {
+                let ($($t,)*) = self;
+                $(archetype.column_mut::<$t>().expect("bundle column").push($t);)*
+            }
+        }
+    };
+}
+
+impl_bundle!(A);
+impl_bundle!(A, B);
+impl_bundle!(A, B, C);
+impl_bundle!(A, B, C, D);
+impl_bundle!(A, B, C, D, E);
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let mut types = B::type_ids();
+        let unique = types.len();
+        types.sort();
+        types.dedup();
+        assert_eq!(unique, types.len(), "bundle contains the same component twice");
+        let arch = match self.by_types.get(&types) {
+            Some(&a) => a,
+            None => self.add_archetype(types, B::columns().0),
+        };
+        let entity = self.alloc();
+        let archetype = &mut self.archetypes[arch];
+        bundle.push(archetype);
+        archetype.entities.push(entity);
+        self.slots[entity.index as usize].location = Some((arch, archetype.entities.len() - 1));
+        entity
+    }
+
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        let Some((arch, row)) = self.location(entity) else { return false };
+        let archetype = &mut self.archetypes[arch];
+        for column in archetype.columns.values_mut() {
+            column.get_mut().swap_remove(row);
+        }
+        self.remove_row(arch, row);
+        let slot = &mut self.slots[entity.index as usize];
+        slot.location = None;
+        slot.generation += 1;
+        self.free.push(entity.index);
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.location(entity).is_some()
+    }
+
+    /// Число живых сущностей
+    pub fn len(&self) -> usize {
+        self.archetypes.iter().map(Archetype::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        let (arch, row) = self.location(entity)?;
+        self.archetypes[arch].column::<T>()?.get(row)
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        let (arch, row) = self.location(entity)?;
+        self.archetypes[arch].column_mut::<T>()?.get_mut(row)
+    }
+
+    /// Добавляет или заменяет компонент; сущность переезжает в архетип с новым набором типов
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> bool {
+        let Some((arch, row)) = self.location(entity) else { return false };
+        if let Some(slot) = self.archetypes[arch].column_mut::<T>() {
+            slot[row] = component;
+            return true;
+        }
+        let mut types = self.archetypes[arch].types.clone();
+        types.push(TypeId::of::<T>());
+        types.sort();
+        let target = match self.by_types.get(&types) {
+            Some(&a) => a,
+            None => {
+                let mut columns: HashMap<TypeId, Box<dyn Column>> = self.archetypes[arch].columns.iter()
+                    .map(|(t, c)| (*t, c.get().empty()))
+                    .collect();
+                columns.insert(TypeId::of::<T>(), Box::new(Vec::<T>::new()));
+                self.add_archetype(types, columns)
+            }
+        };
+        self.move_entity(entity, arch, row, target);
+        self.archetypes[target].column_mut::<T>().expect("new column").push(component);
+        true
+    }
+
+    /// Удаляет компонент и возвращает его
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        let (arch, row) = self.location(entity)?;
+        self.archetypes[arch].column::<T>()?;
+        let types: Vec<TypeId> = self.archetypes[arch].types.iter().copied().filter(|t| *t != TypeId::of::<T>()).collect();
+        let target = match self.by_types.get(&types) {
+            Some(&a) => a,
+            None => {
+                let columns = self.archetypes[arch].columns.iter()
+                    .filter(|(t, _)| **t != TypeId::of::<T>())
+                    .map(|(t, c)| (*t, c.get().empty()))
+                    .collect();
+                self.add_archetype(types, columns)
+            }
+        };
+        let value = self.archetypes[arch].column_mut::<T>().expect("column").swap_remove(row);
+        self.move_entity(entity, arch, row, target);
+        Some(value)
+    }
+
+    /// Итератор по всем сущностям, у которых есть компоненты запроса:
+    /// `world.query::<(&mut Transform, &Velocity)>()`
+    pub fn query<Q: Query>(&mut self) -> QueryIter<'_, Q> {
+        query_access::<Q>();
+        let batches = self.archetypes.iter_mut()
+            .filter(|a| !a.is_empty() && Q::matches(a))
+            .map(|a| (Q::prepare(a), a.len()))
+            .collect();
+        QueryIter { batches, batch: 0, row: 0, _world: PhantomData }
+    }
+
+    /// Запрос по разделяемой ссылке для систем, работающих параллельно. Колонки захвачены,
+    /// пока жив результат; пересечение с ещё живым запросом (своим или другого потока) — паника
+    pub(crate) fn query_shared<Q: Query>(&self) -> SharedQuery<'_, Q> {
+        let access = query_access::<Q>();
+        let mut borrows = Borrows::default();
+        let mut batches = Vec::new();
+        for archetype in self.archetypes.iter().filter(|a| !a.is_empty() && Q::matches(a)) {
+            for &(t, write) in &access {
+                let cell = &archetype.columns[&t];
+                if !cell.acquire(write) {
+                    panic!("component {:?} is already borrowed by another query", t);
+                }
+                borrows.0.push((cell, write));
+            }
+            // SAFETY: все колонки запроса в этом архетипе захвачены
+            batches.push((unsafe { Q::prepare_shared(archetype) }, archetype.len()));
+        }
+        SharedQuery { batches, _borrows: borrows }
+    }
+
+    fn alloc(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(index) => Entity { index, generation: self.slots[index as usize].generation },
+            None => {
+                self.slots.push(Slot { generation: 0, location: None });
+                Entity { index: self.slots.len() as u32 - 1, generation: 0 }
+            }
+        }
+    }
+
+    fn location(&self, entity: Entity) -> Option<(usize, usize)> {
+        let slot = self.slots.get(entity.index as usize)?;
+        if slot.generation != entity.generation { return None; }
+        slot.location
+    }
+
+    fn add_archetype(&mut self, types: Vec<TypeId>, columns: HashMap<TypeId, Box<dyn Column>>) -> usize {
+        let columns = columns.into_iter().map(|(t, c)| (t, ColumnCell::new(c))).collect();
+        self.archetypes.push(Archetype { types: types.clone(), columns, entities: Vec::new() });
+        self.by_types.insert(types, self.archetypes.len() - 1);
+        self.archetypes.len() - 1
+    }
+
+    /// Переносит общие колонки строки `row` из `from` в `to`; колонки, которых нет
+    /// в `to`, уже должны быть удалены вызывающим
+    fn move_entity(&mut self, entity: Entity, from: usize, row: usize, to: usize) {
+        let (src, dst) = if from < to {
+            let (a, b) = self.archetypes.split_at_mut(to);
+            (&mut a[from], &mut b[0])
+        } else {
+            let (a, b) = self.archetypes.split_at_mut(from);
+            (&mut b[0], &mut a[to])
+        };
+        for (t, column) in src.columns.iter_mut() {
+            // колонки удаляемого компонента нет в `to`, её строка уже удалена
+            if let Some(target) = dst.columns.get_mut(t) {
+                column.get_mut().move_row(row, target.get_mut());
+            }
+        }
+        dst.entities.push(entity);
+        self.slots[entity.index as usize].location = Some((to, self.archetypes[to].entities.len() - 1));
+        self.remove_row(from, row);
+    }
+
+    /// Убирает сущность из списка архетипа (swap-remove) и чинит положение переставленной
+    fn remove_row(&mut self, arch: usize, row: usize) {
+        let archetype = &mut self.archetypes[arch];
+        archetype.entities.swap_remove(row);
+        if let Some(&moved) = archetype.entities.get(row) {
+            self.slots[moved.index as usize].location = Some((arch, row));
+        }
+    }
+}
+
+/// Доступы запроса без повторов; паника, если один тип запрошен изменяемо дважды
+/// или одновременно на чтение и запись
+fn query_access<Q: Query>() -> Vec<(TypeId, bool)> {
+    let mut access = Vec::new();
+    Q::access(&mut access);
+    for (i, (t, write)) in access.iter().enumerate() {
+        assert!(
+            !access[i + 1..].iter().any(|(u, w)| u == t && (*write || *w)),
+            "query borrows the same component mutably more than once"
+        );
+    }
+    access.dedup();
+    access
+}
+
+/// Элемент запроса: `&T`, `&mut T`, `Entity` или кортеж из них.
+///
+/// # Safety
+/// `access` должен перечислять все типы, к которым обращается `get`, чтобы `World::query`
+/// мог запретить два изменяемых доступа к одной колонке.
+pub unsafe trait Query {
+    type Item<'w>;
+    type State: Copy;
+    fn access(out: &mut Vec<(TypeId, bool)>);
+    fn matches(archetype: &Archetype) -> bool;
+    fn prepare(archetype: &mut Archetype) -> Self::State;
+    /// # Safety
+    /// Колонки из `access` захвачены вызывающим в этом архетипе.
+    unsafe fn prepare_shared(archetype: &Archetype) -> Self::State;
+    /// # Safety
+    /// `row` меньше длины архетипа, из которого получен `state`.
+    unsafe fn get<'w>(state: Self::State, row: usize) -> Self::Item<'w>;
+}
+
+unsafe impl<T: Component> Query for &T {
+    type Item<'w> = &'w T;
+    type State = *const T;
+    fn access(out: &mut Vec<(TypeId, bool)>) { out.push((TypeId::of::<T>(), false)); }
+    fn matches(archetype: &Archetype) -> bool { archetype.has(TypeId::of::<T>()) }
+    fn prepare(archetype: &mut Archetype) -> *const T { archetype.column_mut::<T>().expect("column").as_ptr() }
+    unsafe fn prepare_shared(archetype: &Archetype) -> *const T { archetype.column::<T>().expect("column").as_ptr() }
+    unsafe fn get<'w>(state: *const T, row: usize) -> &'w T { &*state.add(row) }
+}
+
+unsafe impl<T: Component> Query for &mut T {
+    type Item<'w> = &'w mut T;
+    type State = *mut T;
+    fn access(out: &mut Vec<(TypeId, bool)>) { out.push((TypeId::of::<T>(), true)); }
+    fn matches(archetype: &Archetype) -> bool { archetype.has(TypeId::of::<T>()) }
+    fn prepare(archetype: &mut Archetype) -> *mut T { archetype.column_mut::<T>().expect("column").as_mut_ptr() }
+    unsafe fn prepare_shared(archetype: &Archetype) -> *mut T {
+        let cell = &archetype.columns[&TypeId::of::<T>()];
+        (*cell.data.get()).as_any_mut().downcast_mut::<Vec<T>>().expect("column").as_mut_ptr()
+    }
+    unsafe fn get<'w>(state: *mut T, row: usize) -> &'w mut T { &mut *state.add(row) }
+}
+
+unsafe impl Query for Entity {
+    type Item<'w> = Entity;
+    type State = *const Entity;
+    fn access(_: &mut Vec<(TypeId, bool)>) {}
+    fn matches(_: &Archetype) -> bool { true }
+    fn prepare(archetype: &mut Archetype) -> *const Entity { archetype.entities.as_ptr() }
+    unsafe fn prepare_shared(archetype: &Archetype) -> *const Entity { archetype.entities.as_ptr() }
+    unsafe fn get<'w>(state: *const Entity, row: usize) -> Self::Item<'w> { *state.add(row) }
+}
+
+macro_rules! impl_query {
+    ($($t:ident),*) => {
+        unsafe impl<$($t: Query),*> Query for ($($t,)*) {
+            type Item<'w> = ($($t::Item<'w>,)*);
+            type State = ($($t::State,)*);
+            fn access(out: &mut Vec<(TypeId, bool)>) { $($t::access(out);)* }
+            fn matches(archetype: &Archetype) -> bool { $($t::matches(archetype))&&* }
+            fn prepare(archetype: &mut Archetype) -> Self::State { ($($t::prepare(archetype),)*) }
+            unsafe fn prepare_shared(archetype: &Archetype) -> Self::State { ($($t::prepare_shared(archetype),)*) }
+            #[allow(non_snake_case)]
+            unsafe fn get<'w>(state: Self::State, row: usize) -> Self::Item<'w> {
+                let ($($t,)*) = state;
+                ($($t::get($t, row),)*)
+            }
+        }
+    };
+}
+
+impl_query!(A);
+impl_query!(A, B);
+impl_query!(A, B, C);
+impl_query!(A, B, C, D);
+
+/// Итератор запроса: по архетипам, подходящим под запрос, и их строкам
+pub struct QueryIter<'w, Q: Query> {
+    batches: Vec<(Q::State, usize)>,
+    batch: usize,
+    row: usize,
+    _world: PhantomData<&'w mut World>,
+}
+
+impl<'w, Q: Query> Iterator for QueryIter<'w, Q> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&(state, len)) = self.batches.get(self.batch) {
+            if self.row < len {
+                self.row += 1;
+                // SAFETY: строка в пределах архетипа; `query` держит `&mut World`, а `SharedQuery` —
+                // захваченные колонки всё время жизни итератора, пересекающиеся доступы запрещены
+                return Some(unsafe { Q::get(state, self.row - 1) });
+            }
+            self.batch += 1;
+            self.row = 0;
+        }
+        None
+    }
+}
+
+/// Запрос системы (`SystemContext::query`): держит колонки захваченными, пока жив.
+/// Элементы `iter` не переживают сам запрос, поэтому после
+/// `ctx.query::<&mut Health>().iter()...` следующий запрос к `Health` в той же системе возможен
+pub struct SharedQuery<'w, Q: Query> {
+    batches: Vec<(Q::State, usize)>,
+    _borrows: Borrows<'w>,
+}
+
+impl<Q: Query> SharedQuery<'_, Q> {
+    pub fn iter(&mut self) -> QueryIter<'_, Q> {
+        QueryIter { batches: self.batches.clone(), batch: 0, row: 0, _world: PhantomData }
+    }
+}
+
+/// Колонки, захваченные `World::query_shared`; отпускаются при удалении
+#[derive(Default)]
+struct Borrows<'w>(Vec<(&'w ColumnCell, bool)>);
+
+impl Drop for Borrows<'_> {
+    fn drop(&mut self) {
+        for (cell, write) in self.0.drain(..) {
+            cell.release(write);
+        }
+    }
+}
+
+impl World {
+    /// Создаёт сущности для ноды и всех её потомков: `NodeRef`, `Parent` и типизированные
+    /// компоненты из свойств нод. Записывает сущность в `Node::ecs`
+    pub fn attach_tree(&mut self, node: &mut Node) -> Entity {
+        self.attach_node(node, None)
+    }
+
+    fn attach_node(&mut self, node: &mut Node, parent: Option<Entity>) -> Entity {
+        let entity = self.spawn((NodeRef { id: node.id.clone(), name: node.name.clone() },));
+        if let Some(parent) = parent {
+            self.insert(entity, Parent(parent));
+        }
+        for component in node.components.values() {
+            match TypedComponent::from_component(component) {
+                // поворот и масштаб есть только у типизированного трансформа ноды
+                Some(TypedComponent::Transform(_)) => { self.insert(entity, *node.transform()); }
+                Some(TypedComponent::Velocity(c)) => { self.insert(entity, c); }
+                Some(TypedComponent::Health(c)) => { self.insert(entity, c); }
+                Some(TypedComponent::Sprite(c)) => { self.insert(entity, c); }
+                Some(TypedComponent::Physics(c)) => { self.insert(entity, c); }
+                Some(TypedComponent::Input(c)) => { self.insert(entity, c); }
+                None => {}
+            }
+        }
+        node.ecs = Some(entity);
+        for child in &mut node.children {
+            self.attach_node(child, Some(entity));
+        }
+        entity
+    }
+
+    /// Записывает типизированные компоненты сущностей обратно в свойства их нод
+    pub fn sync_tree(&self, node: &mut Node) {
+        if let Some(entity) = node.ecs {
+            let typed = [
+                self.get::<Transform>(entity).cloned().map(TypedComponent::Transform),
+                self.get::<Velocity>(entity).cloned().map(TypedComponent::Velocity),
+                self.get::<Health>(entity).cloned().map(TypedComponent::Health),
+                self.get::<Sprite>(entity).cloned().map(TypedComponent::Sprite),
+                self.get::<Physics>(entity).cloned().map(TypedComponent::Physics),
+                self.get::<Input>(entity).cloned().map(TypedComponent::Input),
+            ];
+            if let Some(t) = self.get::<Transform>(entity) {
+                if t != node.transform() { node.set_transform(*t); }
+            }
+            for t in typed.into_iter().flatten() {
+                let c = t.to_component();
+                let target = node.components.entry(c.name.clone())
+                    .or_insert_with(|| crate::game_engine::Component::new(c.name.clone()));
+                target.properties.extend(c.properties);
+            }
+        }
+        for child in &mut node.children {
+            self.sync_tree(child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::Vector3;
+
+    #[test]
+    fn generations_and_archetype_moves() {
+        let mut world = World::new();
+        let a = world.spawn((Transform::new(), Health::new(10.0)));
+        let b = world.spawn((Transform::new(),));
+        assert!(world.insert(b, Health::new(5.0)));
+        assert!(world.insert(b, Velocity { value: Vector3::one() }));
+        assert_eq!(world.remove::<Health>(a).map(|h| h.max), Some(10.0));
+        assert!(world.get::<Health>(a).is_none());
+        assert_eq!(world.get::<Health>(b).map(|h| h.max), Some(5.0));
+
+        assert!(world.despawn(a));
+        let c = world.spawn((Health::new(1.0),));
+        assert_eq!(c.index, a.index);
+        assert_ne!(c.generation, a.generation);
+        assert!(!world.is_alive(a) && world.get::<Health>(a).is_none());
+        assert_eq!(world.len(), 2);
+    }
+
+    #[test]
+    fn typed_query_mutates_matching_columns() {
+        let mut world = World::new();
+        for i in 0..3 {
+            world.spawn((Transform::new(), Velocity { value: Vector3::new(i as f32, 0.0, 0.0) }));
+        }
+        let still = world.spawn((Transform::new(),));
+        for (t, v) in world.query::<(&mut Transform, &Velocity)>() {
+            t.position.x += v.value.x * 2.0;
+        }
+        let mut xs: Vec<f32> = world.query::<(Entity, &Transform)>()
+            .filter(|(e, _)| *e != still)
+            .map(|(_, t)| t.position.x)
+            .collect();
+        xs.sort_by(f32::total_cmp);
+        assert_eq!(xs, [0.0, 2.0, 4.0]);
+        assert_eq!(world.query::<&Velocity>().count(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "mutably")]
+    fn aliasing_queries_are_rejected() {
+        let mut world = World::new();
+        world.spawn((Transform::new(),));
+        let _ = world.query::<(&mut Transform, &Transform)>();
+    }
+}
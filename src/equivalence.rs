@@ -0,0 +1,239 @@
+//! Symbolic-execution equivalence checker for supercompiler rewrites.
+//!
+//! A handler is executed symbolically: calls to `function`s are unfolded, arithmetic is folded,
+//! and every undecided `if` condition becomes an atom the execution forks on. The observable
+//! result of one path is its trace of effect statements (builtin calls, and bare expressions,
+//! which the interpreter reports as unrecognized). Two handlers are equivalent when every
+//! consistent assignment of the atoms yields the same trace on both sides.
+
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::corpus::{CorpusError, CorpusOptions, load_corpus};
+use crate::ast::{Expr, Stmt, expr_to_string, fold_expr};
+use crate::driving::{FunctionDef, subst_stmt};
+use crate::meta_lang::{parse_entities, parse_functions};
+use crate::runtime::tag_guard;
+
+/// Paths explored per handler pair before giving up.
+const MAX_PATHS: usize = 4096;
+/// Nested `function` unfoldings before a path counts as non-terminating.
+const MAX_UNFOLD: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Equivalence {
+    /// Same trace on every path.
+    Proved { paths: usize },
+    Refuted(Counterexample),
+    /// Neither proved nor refuted within the limits, e.g. symbolic recursion.
+    Unknown(String),
+}
+
+/// Atom assignment under which the two handlers produce different traces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample {
+    pub assumptions: Vec<(String, bool)>,
+    pub original: Vec<String>,
+    pub simplified: Vec<String>,
+}
+
+impl std::fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let when: Vec<String> = self.assumptions.iter()
+            .map(|(atom, v)| if *v { atom.clone() } else { format!("!({})", atom) })
+            .collect();
+        write!(f, "when [{}]: original [{}] vs simplified [{}]",
+            when.join(", "), self.original.join("; "), self.simplified.join("; "))
+    }
+}
+
+/// Result of checking one handler of the corpus.
+#[derive(Debug, Clone)]
+pub struct HandlerCheck {
+    pub file: String,
+    pub entity: String,
+    pub event: String,
+    pub result: Equivalence,
+}
+
+/// Prove or refute that `simplified` (calling `simplified_fns`) behaves like `original`
+/// (calling `original_fns`).
+pub fn check_equivalence(original: &[Stmt], original_fns: &[FunctionDef], simplified: &[Stmt], simplified_fns: &[FunctionDef]) -> Equivalence {
+    let left = Program::new(original, original_fns);
+    let right = Program::new(simplified, simplified_fns);
+
+    let mut pending = vec![Vec::<(String, bool)>::new()];
+    let mut paths = 0usize;
+    while let Some(assumptions) = pending.pop() {
+        if paths == MAX_PATHS {
+            return Equivalence::Unknown(format!("more than {} paths", MAX_PATHS));
+        }
+        let outcome = left.run(&assumptions).and_then(|l| right.run(&assumptions).map(|r| (l, r)));
+        match outcome {
+            Ok((original, simplified)) => {
+                paths += 1;
+                if original != simplified {
+                    return Equivalence::Refuted(Counterexample { assumptions, original, simplified });
+                }
+            }
+            Err(Stop::Undecided(atom)) => {
+                for value in [false, true] {
+                    let mut next = assumptions.clone();
+                    next.push((atom.clone(), value));
+                    pending.push(next);
+                }
+            }
+            Err(Stop::TooDeep(call)) => {
+                return Equivalence::Unknown(format!("`{}` unfolds more than {} levels deep", call, MAX_UNFOLD));
+            }
+        }
+    }
+    Equivalence::Proved { paths }
+}
+
+/// Check every handler of the corpus in `original_dir` against the file at the same relative
+/// path in `simplified_dir`.
+pub fn verify_corpus(original_dir: &str, simplified_dir: &str) -> Result<Vec<HandlerCheck>, CorpusError> {
+    let corpus = load_corpus(original_dir, &CorpusOptions::default());
+    if let Some(e) = corpus.errors.into_iter().next() {
+        return Err(e);
+    }
+    let mut checks = Vec::new();
+    for f in corpus.files {
+        let file = f.relative;
+        let simplified_src = read_to_string(Path::new(simplified_dir).join(&file)).unwrap_or_default();
+        let original_fns: Vec<FunctionDef> = f.functions.iter().map(FunctionDef::from).collect();
+        let simplified_fns: Vec<FunctionDef> = parse_functions(&simplified_src).iter().map(FunctionDef::from).collect();
+        let simplified_entities = parse_entities(&simplified_src);
+        for e in f.entities {
+            for ev in &e.events {
+                let counterpart = simplified_entities.iter()
+                    .find(|s| s.name == e.name)
+                    .and_then(|s| s.events.iter().find(|sev| sev.name == ev.name));
+                let result = match counterpart {
+                    Some(sev) => check_equivalence(
+                        &crate::ast::parse_statements(&ev.body), &original_fns,
+                        &crate::ast::parse_statements(&sev.body), &simplified_fns),
+                    None => Equivalence::Unknown("handler missing from the simplified corpus".to_string()),
+                };
+                checks.push(HandlerCheck { file: file.clone(), entity: e.name.clone(), event: ev.name.clone(), result });
+            }
+        }
+    }
+    Ok(checks)
+}
+
+enum Stop {
+    /// The path needs a value for this atom.
+    Undecided(String),
+    TooDeep(String),
+}
+
+struct Program<'a> {
+    body: &'a [Stmt],
+    functions: HashMap<&'a str, &'a FunctionDef>,
+}
+
+impl<'a> Program<'a> {
+    fn new(body: &'a [Stmt], functions: &'a [FunctionDef]) -> Self {
+        Self { body, functions: functions.iter().map(|f| (f.name.as_str(), f)).collect() }
+    }
+
+    /// Trace of the path selected by `assumptions`.
+    fn run(&self, assumptions: &[(String, bool)]) -> Result<Vec<String>, Stop> {
+        let mut trace = Vec::new();
+        self.exec(self.body, assumptions, 0, &mut trace)?;
+        Ok(trace)
+    }
+
+    fn exec(&self, stmts: &[Stmt], assumptions: &[(String, bool)], depth: usize, trace: &mut Vec<String>) -> Result<(), Stop> {
+        for st in stmts {
+            match st {
+                Stmt::Empty => {}
+                Stmt::If { cond, body } => {
+                    if truth(cond, assumptions)? {
+                        self.exec(body, assumptions, depth, trace)?;
+                    }
+                }
+                Stmt::Expr(Expr::Call { name, args }) if self.functions.contains_key(name.as_str()) => {
+                    if depth == MAX_UNFOLD { return Err(Stop::TooDeep(name.clone())); }
+                    let f = self.functions[name.as_str()];
+                    let bindings: HashMap<String, Expr> = f.params.iter().cloned().zip(args.iter().map(folded)).collect();
+                    let body: Vec<Stmt> = f.body.iter().map(|s| subst_stmt(s, &bindings)).collect();
+                    self.exec(&body, assumptions, depth + 1, trace)?;
+                }
+                Stmt::Expr(e) => trace.push(expr_to_string(&folded(e))),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn folded(e: &Expr) -> Expr {
+    let mut e = e.clone();
+    fold_expr(&mut e);
+    e
+}
+
+/// Value of a condition on the current path; literals decide themselves, everything else is an
+/// atom looked up in the assumptions. Tag guards on the same variable exclude each other.
+fn truth(cond: &Expr, assumptions: &[(String, bool)]) -> Result<bool, Stop> {
+    let atom = match folded(cond) {
+        Expr::Int(i) => return Ok(i != 0),
+        Expr::Float(f) => return Ok(f != 0.0),
+        Expr::Str(s) => return Ok(!s.is_empty()),
+        e => atom(&e),
+    };
+    if let Some((_, v)) = assumptions.iter().find(|(a, _)| *a == atom) {
+        return Ok(*v);
+    }
+    if let Some((var, _)) = tag_guard(&atom) {
+        let other_tag_holds = assumptions.iter()
+            .any(|(a, v)| *v && tag_guard(a).map(|(w, _)| w == var).unwrap_or(false));
+        if other_tag_holds { return Ok(false); }
+    }
+    Err(Stop::Undecided(atom))
+}
+
+/// Canonical text of a condition, so `other.tag=="Enemy"` and `other.tag == "Enemy"` coincide.
+fn atom(e: &Expr) -> String {
+    let text = expr_to_string(e);
+    match tag_guard(&text) {
+        Some((var, tag)) => format!("{}.tag == \"{}\"", var, tag),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::parse_statements;
+
+    fn check(a: &str, b: &str) -> Equivalence {
+        check_equivalence(&parse_statements(a), &[], &parse_statements(b), &[])
+    }
+
+    #[test]
+    fn folding_and_unfolding_are_proved() {
+        let helper = FunctionDef { name: "_helper_1".into(), params: vec!["a".into()], body: parse_statements("takeDamage(a); collide();") };
+        let original = parse_statements(r#"if (other.tag == "Enemy") { takeDamage(5 + 5); collide(); } move(velocity * dt);"#);
+        let simplified = parse_statements(r#"if (other.tag == "Enemy") { _helper_1(10); } move(velocity * dt);"#);
+        assert_eq!(check_equivalence(&original, &[], &simplified, &[helper]), Equivalence::Proved { paths: 2 });
+    }
+
+    #[test]
+    fn dropped_statement_is_refuted_with_its_path() {
+        let Equivalence::Refuted(cex) = check(r#"if (other.tag == "Enemy") { takeDamage(10); 5; }"#, r#"if (other.tag == "Enemy") { takeDamage(10); }"#) else {
+            panic!("expected a counterexample");
+        };
+        assert_eq!(cex.assumptions, vec![("other.tag == \"Enemy\"".to_string(), true)]);
+        assert_eq!(cex.original, vec!["takeDamage(10)", "5"]);
+    }
+
+    #[test]
+    fn exclusive_tags_are_not_explored_together() {
+        let body = r#"if (other.tag == "Enemy") { takeDamage(10); } if (other.tag == "Wall") { collide(); }"#;
+        assert_eq!(check(body, body), Equivalence::Proved { paths: 3 });
+    }
+}
@@ -1,21 +1,43 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::meta_lang::{Entity, parse_entities};
-use crate::runtime::{EntityInstance, Value};
+use crate::components::{Transform, Vector3};
+use crate::runtime::{Effect, EntityInstance, Value, execute_event_traced};
+use crate::scene::{Scene, SceneManager};
+
+/// События, которые движок вызывает у каждой ноды раз в кадр (с параметром `dt`)
+pub const FRAME_EVENTS: &[&str] = &["Process", "PhysicsProcess", "Update", "Tick"];
+
+/// Раз в отрисованный кадр, `dt` — масштабированное время кадра
+pub const PROCESS_EVENTS: &[&str] = &["Process"];
+
+/// Раз в шаг симуляции; `Update` и `Tick` — прежние имена покадровых обработчиков
+pub const PHYSICS_PROCESS_EVENTS: &[&str] = &["PhysicsProcess", "Update", "Tick"];
+
+/// Нода вошла в дерево активной сцены (сверху вниз: родитель раньше детей)
+pub const ENTER_TREE_EVENT: &str = "EnterTree";
+
+/// Нода и все её дети вошли в дерево; вызывается один раз за жизнь ноды, снизу вверх
+pub const READY_EVENT: &str = "Ready";
+
+/// Нода покидает дерево (снизу вверх: дети раньше родителя)
+pub const EXIT_TREE_EVENT: &str = "ExitTree";
+
+/// Сцена загружена (`on SceneEnter(scene)`): получают её ноды и автозагружаемые ноды
+pub const SCENE_ENTER_EVENT: &str = "SceneEnter";
+
+/// Сцена выгружается (`on SceneExit(scene)`), до `ExitTree` её нод
+pub const SCENE_EXIT_EVENT: &str = "SceneExit";
 
 /// Основной игровой движок
 pub struct GameEngine {
-    pub scenes: HashMap<String, Scene>,
-    pub current_scene: Option<String>,
+    /// Сцены движка; загруженная сцена — `scenes.active_scene`
+    pub scenes: SceneManager,
     pub time: GameTime,
     pub running: bool,
-}
-
-/// Сцена содержит ноды и их иерархию
-#[derive(Debug, Clone)]
-pub struct Scene {
-    pub name: String,
-    pub root: Node,
-    pub entities_meta: Vec<Entity>,
+    /// Шаг симуляции для `advance`
+    pub timestep: FixedTimestep,
+    /// Накопленное, но ещё не просимулированное (масштабированное) время
+    accumulator: f64,
 }
 
 /// Нода в сцене (как в Godot)
@@ -28,6 +50,51 @@ pub struct Node {
     pub children: Vec<Node>,
     pub active: bool,
     pub instance: Option<EntityInstance>,
+    /// Имя сущности метаязыка из `Scene::entities_meta`, чьи обработчики выполняет нода
+    pub entity: Option<String>,
+    /// Группы ноды для рассылки сигналов
+    pub groups: Vec<String>,
+    /// ECS-сущность с типизированными компонентами ноды (см. `World::attach_tree`)
+    pub ecs: Option<crate::ecs::Entity>,
+    /// Корень экземпляра префаба: имя префаба и переопределённые свойства
+    pub prefab: Option<crate::prefab::PrefabInstance>,
+    /// ID корня экземпляра префаба, из которого создана нода
+    pub owner: Option<String>,
+    /// Локальный трансформ относительно родителя; свойство `position` компонента
+    /// `Transform` — его копия по оси X для скриптов
+    transform: Transform,
+    /// Мировая матрица, посчитанная `propagate_transforms`
+    world_matrix: glam::Mat4,
+    /// Локальный трансформ изменён после последнего пересчёта
+    transform_dirty: bool,
+    /// Нода в дереве загруженной сцены
+    inside_tree: bool,
+    /// `on Ready()` уже вызван
+    ready: bool,
+    /// Удалить после текущего шага (`queue_free`)
+    queued_free: bool,
+    /// Дети, добавленные во время обхода; входят в дерево после текущего шага
+    pending_children: Vec<Node>,
+}
+
+/// Получатели сигнала при рассылке
+#[derive(Debug, Clone, Copy)]
+pub enum SignalTarget<'a> {
+    /// Одна нода по ID
+    Node(&'a str),
+    /// Все ноды группы
+    Group(&'a str),
+    /// Все ноды сцены
+    All,
+}
+
+/// Спрайт ноды для отрисовки кадра
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderItem {
+    pub node_id: String,
+    pub texture: String,
+    /// Мировой трансформ после последнего `propagate_transforms`
+    pub transform: Transform,
 }
 
 /// Компонент - поведение ноды
@@ -40,22 +107,61 @@ pub struct Component {
 /// Время в игре
 #[derive(Debug, Clone, Copy)]
 pub struct GameTime {
+    /// Шаг последнего обновления симуляции (игровое время)
     pub delta_time: f64,
+    /// Просимулированное игровое время
     pub total_time: f64,
-    pub frame_count: u32,
+    /// Кадры (`update` или `advance`)
+    pub frame_count: u64,
+    /// Шаги симуляции
+    pub step_count: u64,
+    /// Реальное время последнего кадра, без масштаба и паузы
+    pub unscaled_delta_time: f64,
+    pub unscaled_total_time: f64,
+    /// Множитель игрового времени: 0.5 — замедление, 2.0 — ускорение
+    pub time_scale: f64,
+    pub paused: bool,
+    /// Доля следующего шага, уже накопленная к отрисовке: для интерполяции между
+    /// предыдущим и текущим состоянием
+    pub alpha: f64,
+}
+
+/// Настройки фиксированного шага симуляции
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedTimestep {
+    pub step: f64,
+    /// Максимум шагов за кадр; остальное отставание отбрасывается, чтобы медленный кадр
+    /// не вызывал лавину догоняющих шагов
+    pub max_steps: u32,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self { step: 1.0 / 60.0, max_steps: 5 }
+    }
 }
 
 impl GameEngine {
     pub fn new() -> Self {
+        // Корень автозагрузки в дереве с самого начала: добавленные ноды сразу входят в него
+        let mut scenes = SceneManager::new();
+        Self::enter_tree(&mut scenes.autoload.root, &[]);
         Self {
-            scenes: HashMap::new(),
-            current_scene: None,
+            scenes,
             time: GameTime {
                 delta_time: 0.016, // 60 FPS
                 total_time: 0.0,
                 frame_count: 0,
+                step_count: 0,
+                unscaled_delta_time: 0.0,
+                unscaled_total_time: 0.0,
+                time_scale: 1.0,
+                paused: false,
+                alpha: 0.0,
             },
             running: true,
+            timestep: FixedTimestep::default(),
+            accumulator: 0.0,
         }
     }
 
@@ -65,97 +171,485 @@ impl GameEngine {
         scene_name: &str,
         meta_definition: &str,
     ) -> Result<(), String> {
-        let entities = parse_entities(meta_definition);
-
-        // Создаем корневой нод для сцены
-        let root = Node {
-            id: format!("root_{}", scene_name),
-            name: "Root".to_string(),
-            node_type: "Node".to_string(),
-            components: HashMap::new(),
-            children: Vec::new(),
-            active: true,
-            instance: None,
-        };
+        self.scenes.add_scene(Scene::from_meta(scene_name, meta_definition));
+        Ok(())
+    }
 
-        let scene = Scene {
-            name: scene_name.to_string(),
-            root,
-            entities_meta: entities,
-        };
+    /// Загружает сцену в движок: все остальные сцены (стек и аддитивные) выгружаются,
+    /// автозагружаемые ноды остаются
+    pub fn load_scene(&mut self, scene_name: &str) -> Result<(), String> {
+        if self.scenes.get_scene(scene_name).is_none() {
+            return Err(format!("Scene '{}' not found", scene_name));
+        }
+        let was_loaded = self.scenes.is_loaded(scene_name);
+        // Прежние сцены покидают дерево сверху вниз, новая входит в него
+        for name in self.scenes.loaded_scenes().into_iter().rev().filter(|n| n != scene_name) {
+            self.exit_scene(&name);
+        }
+        self.scenes.stack.clear();
+        self.scenes.additive.clear();
+        self.scenes.set_active_scene(scene_name.to_string())?;
+        if !was_loaded {
+            self.enter_scene(scene_name);
+        }
+        Ok(())
+    }
 
-        self.scenes.insert(scene_name.to_string(), scene);
+    /// Кладёт сцену поверх активной (меню паузы над игрой): прежняя активная сцена
+    /// остаётся в дереве, но не обновляется до `pop_scene`
+    pub fn push_scene(&mut self, scene_name: &str) -> Result<(), String> {
+        if self.scenes.get_scene(scene_name).is_none() {
+            return Err(format!("Scene '{}' not found", scene_name));
+        }
+        if self.scenes.is_loaded(scene_name) {
+            return Err(format!("Scene '{}' is already loaded", scene_name));
+        }
+        if let Some(below) = self.scenes.active_scene.take() {
+            self.scenes.stack.push(below);
+        }
+        self.scenes.active_scene = Some(scene_name.to_string());
+        self.enter_scene(scene_name);
         Ok(())
     }
 
-    /// Загружает сцену в движок
-    pub fn load_scene(&mut self, scene_name: &str) -> Result<(), String> {
-        if !self.scenes.contains_key(scene_name) {
+    /// Выгружает активную сцену и возвращает к сцене под ней; возвращает имя выгруженной
+    pub fn pop_scene(&mut self) -> Result<String, String> {
+        if self.scenes.stack.is_empty() {
+            return Err("Scene stack is empty".to_string());
+        }
+        let top = self.scenes.active_scene.take().ok_or("No active scene above the stack")?;
+        self.scenes.active_scene = self.scenes.stack.pop();
+        self.exit_scene(&top);
+        Ok(top)
+    }
+
+    /// Загружает сцену вместе с активной: обе обновляются и рисуются
+    pub fn load_additive(&mut self, scene_name: &str) -> Result<(), String> {
+        if self.scenes.get_scene(scene_name).is_none() {
             return Err(format!("Scene '{}' not found", scene_name));
         }
-        self.current_scene = Some(scene_name.to_string());
+        if self.scenes.is_loaded(scene_name) {
+            return Err(format!("Scene '{}' is already loaded", scene_name));
+        }
+        self.scenes.additive.push(scene_name.to_string());
+        self.enter_scene(scene_name);
         Ok(())
     }
 
-    /// Выполняет один кадр игры
+    /// Выгружает аддитивную сцену
+    pub fn unload_scene(&mut self, scene_name: &str) -> Result<(), String> {
+        let Some(i) = self.scenes.additive.iter().position(|s| s == scene_name) else {
+            return Err(if self.scenes.is_loaded(scene_name) {
+                format!("Scene '{}' is not additive: use pop_scene or load_scene", scene_name)
+            } else {
+                format!("Scene '{}' is not loaded", scene_name)
+            });
+        };
+        self.scenes.additive.remove(i);
+        self.exit_scene(scene_name);
+        Ok(())
+    }
+
+    /// Добавляет ноду, которая переживает смену сцен; она сразу входит в дерево
+    pub fn add_autoload(&mut self, node: Node) {
+        let autoload = &mut self.scenes.autoload;
+        Self::attach(&mut autoload.root, &autoload.entities_meta, node);
+    }
+
+    /// Автозагружаемые ноды из определения метаязыка, по одной на сущность; возвращает их ID
+    pub fn autoload_from_meta(&mut self, meta_definition: &str) -> Vec<String> {
+        let autoload = &mut self.scenes.autoload;
+        let mut ids = Vec::new();
+        for entity in parse_entities(meta_definition) {
+            autoload.entities_meta.retain(|e| e.name != entity.name);
+            let node = autoload.spawner.spawn(&entity, Vector3::zero());
+            ids.push(node.id.clone());
+            autoload.entities_meta.push(entity);
+            Self::attach(&mut autoload.root, &autoload.entities_meta, node);
+        }
+        ids
+    }
+
+    fn enter_scene(&mut self, scene_name: &str) {
+        if let Some(scene) = self.scenes.get_scene_mut(scene_name) {
+            Self::enter_tree(&mut scene.root, &scene.entities_meta);
+        }
+        self.scene_hook(scene_name, SCENE_ENTER_EVENT);
+    }
+
+    fn exit_scene(&mut self, scene_name: &str) {
+        self.scene_hook(scene_name, SCENE_EXIT_EVENT);
+        if let Some(scene) = self.scenes.get_scene_mut(scene_name) {
+            Self::exit_tree(&mut scene.root, &scene.entities_meta);
+        }
+    }
+
+    /// `on SceneEnter(scene)`/`on SceneExit(scene)` у нод сцены и автозагружаемых нод
+    fn scene_hook(&mut self, scene_name: &str, event: &str) {
+        let mut params = HashMap::new();
+        params.insert("scene".to_string(), Value::Str(scene_name.to_string()));
+        if let Some(scene) = self.scenes.get_scene_mut(scene_name) {
+            Self::broadcast_node(&mut scene.root, &scene.entities_meta, &SignalTarget::All, event, &params, true);
+        }
+        let autoload = &mut self.scenes.autoload;
+        Self::broadcast_node(&mut autoload.root, &autoload.entities_meta, &SignalTarget::All, event, &params, true);
+    }
+
+    /// Сцены, которые обновляются в кадре: автозагрузка, активная и аддитивные
+    fn for_each_running(&mut self, mut f: impl FnMut(&mut Scene)) {
+        f(&mut self.scenes.autoload);
+        for name in self.scenes.running_scenes() {
+            if let Some(scene) = self.scenes.get_scene_mut(&name) {
+                f(scene);
+            }
+        }
+    }
+
+    /// Выполняет один кадр игры с переменным шагом: реальное время `delta_time` умножается
+    /// на `time_scale`; на паузе кадр считается, но симуляция и `Process` не выполняются
     pub fn update(&mut self, delta_time: f64) {
+        self.time.frame_count += 1;
+        self.time.unscaled_delta_time = delta_time;
+        self.time.unscaled_total_time += delta_time;
+        if self.time.paused {
+            return;
+        }
+
+        let scaled = delta_time * self.time.time_scale;
+        self.step(scaled);
+        self.process(scaled);
+    }
+
+    /// Кадр игрового цикла с фиксированным шагом: копит реальное время `frame_time`
+    /// (с учётом масштаба и паузы) и выполняет столько шагов `timestep.step`, сколько
+    /// накопилось, но не больше `max_steps`. Возвращает число шагов. При нулевом,
+    /// отрицательном или нечисловом `step` фиксированных шагов нет и накопитель не меняется
+    pub fn advance(&mut self, frame_time: f64) -> u32 {
+        self.time.frame_count += 1;
+        self.time.unscaled_delta_time = frame_time;
+        self.time.unscaled_total_time += frame_time;
+        if self.time.paused {
+            return 0;
+        }
+
+        let step = self.timestep.step;
+        if !(step.is_finite() && step > 0.0) {
+            self.process(frame_time * self.time.time_scale);
+            return 0;
+        }
+        self.accumulator += frame_time * self.time.time_scale;
+        let mut steps = 0;
+        while self.accumulator >= step && steps < self.timestep.max_steps {
+            self.step(step);
+            self.accumulator -= step;
+            steps += 1;
+        }
+        if self.accumulator >= step {
+            // Не успели догнать: оставляем только долю шага
+            self.accumulator %= step;
+        }
+        self.time.alpha = self.accumulator / step;
+        self.process(frame_time * self.time.time_scale);
+        steps
+    }
+
+    pub fn pause(&mut self) {
+        self.time.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.time.paused = false;
+    }
+
+    pub fn set_time_scale(&mut self, scale: f64) {
+        self.time.time_scale = scale.max(0.0);
+    }
+
+    /// Один шаг симуляции: `on PhysicsProcess(dt)` работающих сцен с включённой физикой
+    fn step(&mut self, delta_time: f64) {
         self.time.delta_time = delta_time;
         self.time.total_time += delta_time;
-        self.time.frame_count += 1;
+        self.time.step_count += 1;
 
-        if let Some(scene_name) = self.current_scene.clone() {
-            let scene_opt = self.scenes.get_mut(&scene_name);
-            if let Some(scene) = scene_opt {
-                let root = scene.root.clone();
-                let mut updated_root = root;
-                Self::update_node_static(&mut updated_root, delta_time);
-                scene.root = updated_root;
+        self.for_each_running(|scene| {
+            if scene.physics_enabled {
+                Self::update_node_static(&mut scene.root, &scene.entities_meta, PHYSICS_PROCESS_EVENTS, delta_time);
+                Self::flush_deferred(&mut scene.root, &scene.entities_meta);
+                scene.root.propagate_transforms();
             }
-        }
+        });
     }
 
-    fn update_node_static(node: &mut Node, delta_time: f64) {
+    /// Отрисованный кадр: `on Process(dt)` работающих сцен
+    fn process(&mut self, delta_time: f64) {
+        self.for_each_running(|scene| {
+            Self::update_node_static(&mut scene.root, &scene.entities_meta, PROCESS_EVENTS, delta_time);
+            Self::flush_deferred(&mut scene.root, &scene.entities_meta);
+            scene.root.propagate_transforms();
+        });
+    }
+
+    fn update_node_static(node: &mut Node, entities: &[Entity], events: &[&str], delta_time: f64) {
         if !node.active {
             return;
         }
 
-        // Обновляем текущий нод
-        if let Some(_instance) = &mut node.instance {
+        // Находим сущность метаязыка ноды и выполняем её покадровые события
+        let entity = node.entity_name().and_then(|name| entities.iter().find(|e| e.name == name));
+        if let Some(entity) = entity {
             let mut params = HashMap::new();
             params.insert("dt".to_string(), Value::Float(delta_time));
-
-            // Находим соответствующую сущность в метаязыке
-            // и выполняем события
+            node.run_handlers(entity, events, &params);
         }
 
         // Рекурсивно обновляем детей
         for child in &mut node.children {
-            Self::update_node_static(child, delta_time);
+            Self::update_node_static(child, entities, events, delta_time);
+        }
+    }
+
+    /// Поддерево входит в дерево сцены: `EnterTree` сверху вниз, затем `Ready` снизу вверх
+    fn enter_tree(node: &mut Node, entities: &[Entity]) {
+        Self::propagate_enter(node, entities);
+        Self::propagate_ready(node, entities);
+    }
+
+    fn propagate_enter(node: &mut Node, entities: &[Entity]) {
+        node.inside_tree = true;
+        Self::run_lifecycle(node, entities, ENTER_TREE_EVENT);
+        for child in &mut node.children {
+            Self::propagate_enter(child, entities);
+        }
+    }
+
+    fn propagate_ready(node: &mut Node, entities: &[Entity]) {
+        for child in &mut node.children {
+            Self::propagate_ready(child, entities);
+        }
+        if !node.ready {
+            node.ready = true;
+            Self::run_lifecycle(node, entities, READY_EVENT);
+        }
+    }
+
+    /// Поддерево покидает дерево сцены: `ExitTree` снизу вверх
+    fn exit_tree(node: &mut Node, entities: &[Entity]) {
+        for child in &mut node.children {
+            Self::exit_tree(child, entities);
+        }
+        Self::run_lifecycle(node, entities, EXIT_TREE_EVENT);
+        node.inside_tree = false;
+    }
+
+    fn run_lifecycle(node: &mut Node, entities: &[Entity], event: &str) {
+        let entity = node.entity_name().and_then(|name| entities.iter().find(|e| e.name == name));
+        if let Some(entity) = entity {
+            node.run_handlers(entity, &[event], &HashMap::new());
+        }
+    }
+
+    /// Применяет отложенные изменения дерева после обхода: удаляет ноды с `queue_free`
+    /// и добавляет детей из `add_child_deferred`. Изменения, сделанные обработчиками
+    /// `ExitTree`/`EnterTree`/`Ready`, применяются в том же вызове
+    fn flush_deferred(root: &mut Node, entities: &[Entity]) {
+        root.queued_free = false; // корень сцены не удаляется
+        while root.has_deferred() {
+            Self::flush_node(root, entities);
+        }
+    }
+
+    fn flush_node(node: &mut Node, entities: &[Entity]) {
+        let mut i = 0;
+        while i < node.children.len() {
+            if node.children[i].queued_free {
+                let mut removed = node.children.remove(i);
+                if removed.inside_tree {
+                    Self::exit_tree(&mut removed, entities);
+                }
+            } else {
+                Self::flush_node(&mut node.children[i], entities);
+                i += 1;
+            }
+        }
+        for child in std::mem::take(&mut node.pending_children) {
+            node.add_child(child);
+            if node.inside_tree {
+                let added = node.children.last_mut().expect("just added");
+                Self::enter_tree(added, entities);
+            }
         }
     }
 
     /// Добавляет нод в сцену
     pub fn add_node(&mut self, scene_name: &str, node: Node) -> Result<(), String> {
-        if let Some(scene) = self.scenes.get_mut(scene_name) {
-            scene.root.children.push(node);
+        if let Some(scene) = self.scenes.get_scene_mut(scene_name) {
+            Self::attach(&mut scene.root, &scene.entities_meta, node);
             Ok(())
         } else {
             Err(format!("Scene '{}' not found", scene_name))
         }
     }
 
-    /// Обрабатывает событие для ноды
+    /// Создаёт ещё один экземпляр сущности в текущей сцене, возвращает ID ноды
+    pub fn spawn(&mut self, entity_name: &str, at: Vector3) -> Result<String, String> {
+        let scene_name = self.scenes.active_scene.clone().ok_or("No scene loaded")?;
+        self.spawn_in(&scene_name, entity_name, at)
+    }
+
+    /// Создаёт экземпляр сущности в указанной сцене
+    pub fn spawn_in(&mut self, scene_name: &str, entity_name: &str, at: Vector3) -> Result<String, String> {
+        let scene = self.scenes.get_scene_mut(scene_name)
+            .ok_or_else(|| format!("Scene '{}' not found", scene_name))?;
+        let entity = scene.entities_meta.iter().find(|e| e.name == entity_name)
+            .ok_or_else(|| format!("Entity '{}' is not defined in scene '{}'", entity_name, scene_name))?;
+        let node = scene.spawner.spawn(entity, at);
+        let id = node.id.clone();
+        Self::attach(&mut scene.root, &scene.entities_meta, node);
+        Ok(id)
+    }
+
+    /// Создаёт экземпляр префаба под нодой `parent_id`, возвращает ID вида `enemy_1`.
+    /// Нумерация общая со `Spawner` сцены, поэтому префаб `Enemy` и сущность `Enemy`
+    /// не получат одинаковых ID; в загруженной сцене экземпляр сразу входит в дерево
+    pub fn instance_prefab(&mut self, scene_name: &str, parent_id: &str, prefab: &str) -> Result<String, String> {
+        let scene = self.scenes.scenes.get_mut(scene_name)
+            .ok_or_else(|| format!("Scene '{}' not found", scene_name))?;
+        let instance = self.scenes.prefabs.instantiate(prefab, &scene.spawner.next_id(prefab))?;
+        let parent = scene.root.find_by_id_mut(parent_id)
+            .ok_or_else(|| format!("Node '{}' not found in scene '{}'", parent_id, scene_name))?;
+        Self::attach(parent, &scene.entities_meta, instance);
+        Ok(scene.spawner.take_id(prefab))
+    }
+
+    /// Добавляет ноду к `parent`; в загруженной сцене она сразу входит в дерево
+    fn attach(parent: &mut Node, entities: &[Entity], node: Node) {
+        parent.add_child(node);
+        if parent.inside_tree {
+            let added = parent.children.last_mut().expect("just added");
+            Self::enter_tree(added, entities);
+        }
+    }
+
+    /// Помечает ноду на удаление после текущего шага (с `on ExitTree()` у загруженной сцены)
+    pub fn queue_free(&mut self, scene_name: &str, node_id: &str) -> Result<(), String> {
+        let scene = self.scenes.get_scene_mut(scene_name)
+            .ok_or_else(|| format!("Scene '{}' not found", scene_name))?;
+        if scene.root.id == node_id {
+            return Err(format!("Root node of scene '{}' cannot be freed", scene_name));
+        }
+        let node = scene.root.find_by_id_mut(node_id)
+            .ok_or_else(|| format!("Node '{}' not found in scene '{}'", node_id, scene_name))?;
+        node.queue_free();
+        Ok(())
+    }
+
+    /// Обрабатывает событие для ноды: находит её по ID в любом месте дерева
+    /// и выполняет обработчик `on <Signal>(...)` её сущности
     pub fn emit_signal(
         &mut self,
-        _scene_name: &str,
-        _node_id: &str,
-        _signal_name: &str,
-        _params: HashMap<String, Value>,
+        scene_name: &str,
+        node_id: &str,
+        signal_name: &str,
+        params: HashMap<String, Value>,
     ) -> Result<(), String> {
-        // Находим нод по ID и выполняем его событие
+        let scene = self.scenes.get_scene_mut(scene_name)
+            .ok_or_else(|| format!("Scene '{}' not found", scene_name))?;
+        let node = scene.root.find_by_id_mut(node_id)
+            .ok_or_else(|| format!("Node '{}' not found in scene '{}'", node_id, scene_name))?;
+        if !node.active {
+            return Err(format!("Node '{}' is inactive", node_id));
+        }
+        if node.instance.is_none() {
+            return Err(format!("Node '{}' has no entity instance", node_id));
+        }
+        let entity_name = node.entity_name()
+            .ok_or_else(|| format!("Node '{}' is not linked to a meta entity", node_id))?;
+        let entity = scene.entities_meta.iter().find(|e| e.name == entity_name)
+            .ok_or_else(|| format!("Entity '{}' of node '{}' is not defined in scene '{}'", entity_name, node_id, scene_name))?;
+        if node.run_handlers(entity, &[signal_name], &params) == 0 {
+            return Err(format!("Entity '{}' has no handler for signal '{}'", entity.name, signal_name));
+        }
         Ok(())
     }
 
+    /// Рассылает сигнал группе или всем нодам сцены; ноды без обработчика пропускаются.
+    /// Возвращает число выполненных обработчиков
+    pub fn broadcast_signal(
+        &mut self,
+        scene_name: &str,
+        target: SignalTarget,
+        signal_name: &str,
+        params: HashMap<String, Value>,
+    ) -> Result<usize, String> {
+        let scene = self.scenes.get_scene_mut(scene_name)
+            .ok_or_else(|| format!("Scene '{}' not found", scene_name))?;
+        if let SignalTarget::Node(id) = target {
+            return match scene.root.find_by_id_mut(id) {
+                Some(node) => Ok(Self::broadcast_node(node, &scene.entities_meta, &SignalTarget::All, signal_name, &params, false)),
+                None => Err(format!("Node '{}' not found in scene '{}'", id, scene_name)),
+            };
+        }
+        Ok(Self::broadcast_node(&mut scene.root, &scene.entities_meta, &target, signal_name, &params, true))
+    }
+
+    fn broadcast_node(
+        node: &mut Node,
+        entities: &[Entity],
+        target: &SignalTarget,
+        signal_name: &str,
+        params: &HashMap<String, Value>,
+        recursive: bool,
+    ) -> usize {
+        if !node.active {
+            return 0;
+        }
+        let mut count = 0;
+        let selected = match target {
+            SignalTarget::Group(group) => node.is_in_group(group),
+            _ => true,
+        };
+        let entity = node.entity_name().and_then(|name| entities.iter().find(|e| e.name == name));
+        if let (true, Some(entity)) = (selected, entity) {
+            count += node.run_handlers(entity, &[signal_name], params);
+        }
+        if recursive {
+            for child in &mut node.children {
+                count += Self::broadcast_node(child, entities, target, signal_name, params, true);
+            }
+        }
+        count
+    }
+
+    /// Видимые спрайты активных нод всех загруженных сцен в порядке отрисовки (стек снизу
+    /// вверх, активная, аддитивные, автозагрузка); сцены с выключенным рендером пропускаются
+    pub fn render_items(&self) -> Vec<RenderItem> {
+        let mut items = Vec::new();
+        let loaded = self.scenes.loaded_scenes();
+        let scenes = loaded.iter().filter_map(|name| self.scenes.get_scene(name)).chain([&self.scenes.autoload]);
+        for scene in scenes.filter(|s| s.render_enabled) {
+            Self::collect_render_items(&scene.root, &mut items);
+        }
+        items
+    }
+
+    fn collect_render_items(node: &Node, items: &mut Vec<RenderItem>) {
+        if !node.active {
+            return;
+        }
+        if let Some(sprite) = node.get_component("Sprite") {
+            if node.number_property("Sprite", "visible") != Some(0.0) {
+                let texture = match sprite.get_property("texture") {
+                    Some(Value::Str(t)) => t.clone(),
+                    _ => String::new(),
+                };
+                items.push(RenderItem { node_id: node.id.clone(), texture, transform: node.global_transform() });
+            }
+        }
+        for child in &node.children {
+            Self::collect_render_items(child, items);
+        }
+    }
+
     pub fn is_running(&self) -> bool {
         self.running
     }
@@ -181,6 +675,114 @@ impl Node {
             children: Vec::new(),
             active: true,
             instance: None,
+            entity: None,
+            groups: Vec::new(),
+            ecs: None,
+            prefab: None,
+            owner: None,
+            transform: Transform::default(),
+            world_matrix: glam::Mat4::IDENTITY,
+            transform_dirty: true,
+            inside_tree: false,
+            ready: false,
+            queued_free: false,
+            pending_children: Vec::new(),
+        }
+    }
+
+    /// Связывает ноду с сущностью метаязыка
+    pub fn with_entity(mut self, entity: &str) -> Self {
+        self.entity = Some(entity.to_string());
+        self
+    }
+
+    /// Имя сущности метаязыка: явная связь или имя инстанса
+    pub fn entity_name(&self) -> Option<&str> {
+        self.entity.as_deref().or(self.instance.as_ref().map(|i| i.name.as_str()))
+    }
+
+    /// Добавляет ноду в группу
+    pub fn add_to_group(&mut self, group: &str) {
+        if !self.is_in_group(group) {
+            self.groups.push(group.to_string());
+        }
+    }
+
+    pub fn is_in_group(&self, group: &str) -> bool {
+        self.groups.iter().any(|g| g == group)
+    }
+
+    /// Находит ноду по ID в поддереве (включая саму ноду)
+    pub fn find_by_id(&self, id: &str) -> Option<&Node> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find_by_id(id))
+    }
+
+    pub fn find_by_id_mut(&mut self, id: &str) -> Option<&mut Node> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter_mut().find_map(|c| c.find_by_id_mut(id))
+    }
+
+    /// Выполняет обработчики сущности с указанными именами на инстансе ноды,
+    /// синхронизируя компоненты. Возвращает число выполненных обработчиков
+    fn run_handlers(&mut self, entity: &Entity, events: &[&str], params: &HashMap<String, Value>) -> usize {
+        if self.instance.is_none() {
+            return 0;
+        }
+        let handlers: Vec<_> = entity.events.iter().filter(|ev| events.contains(&ev.name.as_str())).collect();
+        if handlers.is_empty() {
+            return 0;
+        }
+        self.pull_instance_state();
+        if let Some(instance) = &mut self.instance {
+            for event in &handlers {
+                if execute_event_traced(instance, event, params).contains(&Effect::Freed) {
+                    self.queued_free = true;
+                }
+            }
+        }
+        self.push_instance_state();
+        handlers.len()
+    }
+
+    /// Переносит состояние компонентов в инстанс перед выполнением обработчиков
+    fn pull_instance_state(&mut self) {
+        let position = self.components.contains_key("Transform").then_some(self.transform.position.x as f64);
+        let velocity = self.number_property("Physics", "velocity");
+        let hp = self.number_property("Health", "hp");
+        if let Some(instance) = &mut self.instance {
+            if let Some(p) = position { instance.position = p; }
+            if let Some(v) = velocity { instance.velocity = v; }
+            if let Some(hp) = hp { instance.health = hp as i32; }
+        }
+    }
+
+    /// Записывает результат обработчиков обратно в компоненты
+    fn push_instance_state(&mut self) {
+        let Some(instance) = self.instance.clone() else { return };
+        if let Some(c) = self.components.get_mut("Transform") {
+            c.set_property("position".to_string(), Value::Float(instance.position));
+            if self.transform.position.x != instance.position as f32 {
+                self.transform_mut().position.x = instance.position as f32;
+            }
+        }
+        if let Some(c) = self.components.get_mut("Physics") {
+            c.set_property("velocity".to_string(), Value::Float(instance.velocity));
+        }
+        if let Some(c) = self.components.get_mut("Health") {
+            c.set_property("hp".to_string(), Value::Int(instance.health as i64));
+        }
+    }
+
+    fn number_property(&self, component: &str, key: &str) -> Option<f64> {
+        match self.get_component(component)?.get_property(key)? {
+            Value::Float(f) => Some(*f),
+            Value::Int(i) => Some(*i as f64),
+            _ => None,
         }
     }
 
@@ -194,11 +796,269 @@ impl Node {
         self.components.get(name)
     }
 
+    /// Удалить ноду вместе с детьми после текущего шага, не трогая дерево во время обхода
+    pub fn queue_free(&mut self) {
+        self.queued_free = true;
+    }
+
+    pub fn is_queued_for_deletion(&self) -> bool {
+        self.queued_free
+    }
+
+    pub fn is_inside_tree(&self) -> bool {
+        self.inside_tree
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// Добавляет ребёнка после текущего шага (безопасно во время обхода дерева)
+    pub fn add_child_deferred(&mut self, child: Node) {
+        self.pending_children.push(child);
+    }
+
+    fn has_deferred(&self) -> bool {
+        !self.pending_children.is_empty()
+            || self.children.iter().any(|c| c.queued_free || c.has_deferred())
+    }
+
     /// Добавляет дочерний нод
-    pub fn add_child(&mut self, child: Node) {
+    pub fn add_child(&mut self, mut child: Node) {
+        child.transform_dirty = true;
         self.children.push(child);
     }
 
+    /// Локальный трансформ относительно родителя
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.transform_dirty = true;
+    }
+
+    /// Изменяемый доступ к локальному трансформу; помечает ноду для пересчёта
+    pub fn transform_mut(&mut self) -> &mut Transform {
+        self.transform_dirty = true;
+        &mut self.transform
+    }
+
+    /// Мировая матрица на момент последнего `propagate_transforms`
+    pub fn world_matrix(&self) -> glam::Mat4 {
+        self.world_matrix
+    }
+
+    /// Мировой трансформ на момент последнего `propagate_transforms`
+    pub fn global_transform(&self) -> Transform {
+        Transform::from_mat4(self.world_matrix)
+    }
+
+    /// Пересчитывает мировые матрицы поддерева, считая ноду корнем. Заходит только
+    /// в изменённые ноды и их потомков; возвращает число пересчитанных нод
+    pub fn propagate_transforms(&mut self) -> usize {
+        self.propagate_from(glam::Mat4::IDENTITY, false)
+    }
+
+    fn propagate_from(&mut self, parent: glam::Mat4, parent_changed: bool) -> usize {
+        let changed = parent_changed || self.transform_dirty;
+        let mut count = 0;
+        if changed {
+            self.world_matrix = parent * self.transform.to_mat4();
+            self.transform_dirty = false;
+            count += 1;
+        }
+        for child in &mut self.children {
+            count += child.propagate_from(self.world_matrix, changed);
+        }
+        count
+    }
+
+    /// Нода по пути от этой ноды в стиле Godot: `"Level/Enemies/Guard2"`, `".."`, `"."`,
+    /// шаблоны `*`/`?` в имени и `**` для любой глубины. Путь, начинающийся с `/`,
+    /// отсчитывается от этой ноды как от корня. Первая подходящая нода
+    pub fn get_node(&self, path: &str) -> Option<&Node> {
+        let first = self.resolve_paths(Vec::new(), path).into_iter().next()?;
+        self.at_index_path(&first)
+    }
+
+    pub fn get_node_mut(&mut self, path: &str) -> Option<&mut Node> {
+        let first = self.resolve_paths(Vec::new(), path).into_iter().next()?;
+        self.at_index_path_mut(&first)
+    }
+
+    /// Все ноды, подходящие под путь-шаблон, в порядке обхода
+    pub fn get_nodes(&self, path: &str) -> Vec<&Node> {
+        self.resolve_paths(Vec::new(), path).iter().filter_map(|p| self.at_index_path(p)).collect()
+    }
+
+    /// Путь относительно ноды `from_id` (например `"../Guard2"`); `self` — корень дерева
+    pub fn get_node_from(&self, from_id: &str, path: &str) -> Option<&Node> {
+        let start = if path.starts_with('/') { Vec::new() } else { self.index_path_of(from_id)? };
+        let first = self.resolve_paths(start, path).into_iter().next()?;
+        self.at_index_path(&first)
+    }
+
+    /// Путь из имён от этой ноды до потомка с ID `id` (пустой для самой ноды)
+    pub fn path_of(&self, id: &str) -> Option<String> {
+        let indices = self.index_path_of(id)?;
+        let mut node = self;
+        let mut names = Vec::new();
+        for i in indices {
+            node = &node.children[i];
+            names.push(node.name.as_str());
+        }
+        Some(names.join("/"))
+    }
+
+    /// Первая нода поддерева с таким именем (обход в глубину, включая саму ноду)
+    pub fn find_by_name(&self, name: &str) -> Option<&Node> {
+        self.find(|n| n.name == name)
+    }
+
+    /// Все ноды поддерева с типом `node_type`
+    pub fn find_all_by_type(&self, node_type: &str) -> Vec<&Node> {
+        self.find_all(|n| n.node_type == node_type)
+    }
+
+    pub fn find(&self, pred: impl Fn(&Node) -> bool + Copy) -> Option<&Node> {
+        if pred(self) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(pred))
+    }
+
+    pub fn find_all(&self, pred: impl Fn(&Node) -> bool + Copy) -> Vec<&Node> {
+        let mut out = Vec::new();
+        self.collect(pred, &mut out);
+        out
+    }
+
+    fn collect<'a>(&'a self, pred: impl Fn(&Node) -> bool + Copy, out: &mut Vec<&'a Node>) {
+        if pred(self) {
+            out.push(self);
+        }
+        for c in &self.children {
+            c.collect(pred, out);
+        }
+    }
+
+    /// Убирает ноду из группы
+    pub fn remove_from_group(&mut self, group: &str) {
+        self.groups.retain(|g| g != group);
+    }
+
+    /// Все ноды поддерева в группе
+    pub fn get_nodes_in_group(&self, group: &str) -> Vec<&Node> {
+        self.find_all(|n| n.is_in_group(group))
+    }
+
+    /// Мировой трансформ потомка, посчитанный по цепочке родителей (без кэша `propagate_transforms`)
+    pub fn compute_global_transform(&self, id: &str) -> Option<Transform> {
+        Some(Transform::from_mat4(self.world_matrix_at(&self.index_path_of(id)?)))
+    }
+
+    /// Переносит ноду `id` к новому родителю `new_parent_id`, сохраняя её мировой трансформ.
+    /// `self` — корень дерева
+    pub fn reparent(&mut self, id: &str, new_parent_id: &str) -> Result<(), String> {
+        let from = self.index_path_of(id).ok_or_else(|| format!("Node '{}' not found", id))?;
+        let to = self.index_path_of(new_parent_id).ok_or_else(|| format!("Node '{}' not found", new_parent_id))?;
+        if from.is_empty() {
+            return Err(format!("Node '{}' is the root and cannot be reparented", id));
+        }
+        if to.starts_with(&from) {
+            return Err(format!("Node '{}' cannot become a child of its own descendant '{}'", id, new_parent_id));
+        }
+        let world = self.world_matrix_at(&from);
+        let parent_world = self.world_matrix_at(&to);
+
+        let (last, parent_path) = from.split_last().expect("non-root");
+        let mut node = self.at_index_path_mut(parent_path).expect("parent").children.remove(*last);
+        // Индексы нового родителя сдвигаются, если он шёл после удалённой ноды у того же родителя
+        let mut to = to;
+        if to.len() > parent_path.len() && to.starts_with(parent_path) && to[parent_path.len()] > *last {
+            to[parent_path.len()] -= 1;
+        }
+        node.set_transform(Transform::from_mat4(parent_world.inverse() * world));
+        self.at_index_path_mut(&to).expect("new parent").add_child(node);
+        Ok(())
+    }
+
+    fn index_path_of(&self, id: &str) -> Option<Vec<usize>> {
+        if self.id == id {
+            return Some(Vec::new());
+        }
+        self.children.iter().enumerate().find_map(|(i, c)| {
+            let mut p = c.index_path_of(id)?;
+            p.insert(0, i);
+            Some(p)
+        })
+    }
+
+    fn at_index_path(&self, path: &[usize]) -> Option<&Node> {
+        path.iter().try_fold(self, |n, &i| n.children.get(i))
+    }
+
+    fn at_index_path_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        path.iter().try_fold(self, |n, &i| n.children.get_mut(i))
+    }
+
+    fn world_matrix_at(&self, path: &[usize]) -> glam::Mat4 {
+        let mut node = self;
+        let mut m = self.transform.to_mat4();
+        for &i in path {
+            node = &node.children[i];
+            m *= node.transform.to_mat4();
+        }
+        m
+    }
+
+    fn resolve_paths(&self, start: Vec<usize>, path: &str) -> Vec<Vec<usize>> {
+        let mut current = vec![if path.starts_with('/') { Vec::new() } else { start }];
+        for seg in path.split('/').filter(|s| !s.is_empty()) {
+            let mut next: Vec<Vec<usize>> = Vec::new();
+            for p in current {
+                match seg {
+                    "." => next.push(p),
+                    ".." => {
+                        if let Some((_, parent)) = p.split_last() {
+                            next.push(parent.to_vec());
+                        }
+                    }
+                    "**" => self.descendants(p, &mut next),
+                    pattern => {
+                        if let Some(node) = self.at_index_path(&p) {
+                            for (i, c) in node.children.iter().enumerate() {
+                                if crate::corpus::glob_match(pattern, &c.name) {
+                                    let mut q = p.clone();
+                                    q.push(i);
+                                    next.push(q);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            // `**/..` и `**/**` дают одну ноду несколько раз и не подряд
+            let mut seen = HashSet::new();
+            next.retain(|p| seen.insert(p.clone()));
+            current = next;
+        }
+        current
+    }
+
+    /// `p` и все его потомки
+    fn descendants(&self, p: Vec<usize>, out: &mut Vec<Vec<usize>>) {
+        let count = self.at_index_path(&p).map_or(0, |n| n.children.len());
+        out.push(p.clone());
+        for i in 0..count {
+            let mut q = p.clone();
+            q.push(i);
+            self.descendants(q, out);
+        }
+    }
+
     /// Находит дочерний нод по имени
     pub fn find_child(&self, name: &str) -> Option<&Node> {
         self.children.iter().find(|n| n.name == name)
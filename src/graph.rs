@@ -0,0 +1,407 @@
+//! Dependency graph of a corpus: entities → handlers → called builtins, helpers and functions
+//! → emitted signals → handlers of those signals, exported to Graphviz DOT and Mermaid.
+//!
+//! A handler emits a signal with `emit("Name", ...)`; every handler named `Name` receives it.
+//! Helpers (`_helper_N`, shared across files by the supercompiler) are one node for the whole
+//! corpus, so their sharing is visible; other functions are per file.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+
+use crate::ast::{Expr, Stmt, parse_statements};
+use crate::builtins::builtin;
+use crate::corpus::{CorpusFile, CorpusOptions, load_corpus};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeKind {
+    Entity,
+    Handler,
+    Builtin,
+    Helper,
+    Function,
+    Signal,
+    /// Called but neither a builtin nor defined in the file.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeKind {
+    Declares,
+    Calls,
+    Emits,
+    /// Signal delivered to a handler of that name.
+    Dispatches,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    pub id: String,
+    pub label: String,
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<(usize, usize, EdgeKind)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl std::str::FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            other => Err(format!("unknown graph format `{}` (expected dot or mermaid)", other)),
+        }
+    }
+}
+
+pub fn corpus_graph(path: &str) -> Graph {
+    build_graph(&load_corpus(path, &CorpusOptions::default()).files)
+}
+
+pub fn build_graph(files: &[CorpusFile]) -> Graph {
+    let mut g = Graph::default();
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    let mut node = |g: &mut Graph, id: String, label: &str, kind: NodeKind| -> usize {
+        *ids.entry(id.clone()).or_insert_with(|| {
+            g.nodes.push(GraphNode { id, label: label.to_string(), kind });
+            g.nodes.len() - 1
+        })
+    };
+
+    let mut handlers_by_event: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut signals: Vec<(usize, String)> = Vec::new();
+    for file in files {
+        let local: HashMap<&str, &str> = file.functions.iter().map(|f| (f.name.as_str(), f.body.as_str())).collect();
+        let callee = |g: &mut Graph, node: &mut dyn FnMut(&mut Graph, String, &str, NodeKind) -> usize, name: &str| {
+            if name.starts_with("_helper_") {
+                node(g, format!("helper:{}", name), name, NodeKind::Helper)
+            } else if local.contains_key(name) {
+                node(g, format!("fn:{}:{}", file.relative, name), name, NodeKind::Function)
+            } else if builtin(name).is_some() {
+                node(g, format!("builtin:{}", name), name, NodeKind::Builtin)
+            } else {
+                node(g, format!("unknown:{}", name), name, NodeKind::Unknown)
+            }
+        };
+
+        let mut bodies: Vec<(usize, &str)> = Vec::new();
+        for e in &file.entities {
+            let ent = node(&mut g, format!("entity:{}", e.name), &e.name, NodeKind::Entity);
+            for ev in &e.events {
+                let label = format!("{}.{}", e.name, ev.name);
+                let h = node(&mut g, format!("handler:{}", label), &label, NodeKind::Handler);
+                g.edges.push((ent, h, EdgeKind::Declares));
+                handlers_by_event.entry(ev.name.clone()).or_default().push(h);
+                bodies.push((h, &ev.body));
+            }
+        }
+        for f in &file.functions {
+            let from = callee(&mut g, &mut node, &f.name);
+            bodies.push((from, &f.body));
+        }
+        for (from, body) in bodies {
+            let mut calls = Vec::new();
+            collect_calls(&parse_statements(body), &mut calls);
+            for (name, args) in calls {
+                if name == "emit" {
+                    if let Some(signal) = args.first().and_then(signal_name) {
+                        let s = node(&mut g, format!("signal:{}", signal), &signal, NodeKind::Signal);
+                        push_edge(&mut g, from, s, EdgeKind::Emits);
+                        signals.push((s, signal));
+                    }
+                    continue;
+                }
+                let to = callee(&mut g, &mut node, &name);
+                push_edge(&mut g, from, to, EdgeKind::Calls);
+            }
+        }
+    }
+    signals.sort_by_key(|(s, _)| *s);
+    signals.dedup();
+    for (s, name) in signals {
+        for &h in handlers_by_event.get(&name).into_iter().flatten() {
+            push_edge(&mut g, s, h, EdgeKind::Dispatches);
+        }
+    }
+    g
+}
+
+fn push_edge(g: &mut Graph, from: usize, to: usize, kind: EdgeKind) {
+    if !g.edges.contains(&(from, to, kind)) { g.edges.push((from, to, kind)); }
+}
+
+fn collect_calls(stmts: &[Stmt], out: &mut Vec<(String, Vec<Expr>)>) {
+    for st in stmts {
+        match st {
+            Stmt::Expr(Expr::Call { name, args }) => out.push((name.clone(), args.clone())),
+            Stmt::If { body, .. } => collect_calls(body, out),
+            _ => {}
+        }
+    }
+}
+
+fn signal_name(e: &Expr) -> Option<String> {
+    match e {
+        Expr::Str(s) | Expr::Ident(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+impl Graph {
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    fn find(&self, id: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.id == id)
+    }
+
+    /// Helpers called from more than one place, with their callers' labels.
+    pub fn shared_helpers(&self) -> Vec<(String, Vec<String>)> {
+        self.nodes.iter().enumerate()
+            .filter(|(_, n)| n.kind == NodeKind::Helper)
+            .filter_map(|(i, n)| {
+                let callers: BTreeSet<String> = self.edges.iter()
+                    .filter(|(_, to, k)| *to == i && *k == EdgeKind::Calls)
+                    .map(|(from, _, _)| self.nodes[*from].label.clone())
+                    .collect();
+                (callers.len() > 1).then(|| (n.label.clone(), callers.into_iter().collect()))
+            })
+            .collect()
+    }
+
+    /// Handlers that (through calls and signals) end up emitting a signal back to themselves;
+    /// one sorted list of handler labels per strongly connected component.
+    pub fn signal_cycles(&self) -> Vec<Vec<String>> {
+        let n = self.nodes.len();
+        let mut adj = vec![Vec::new(); n];
+        for &(a, b, _) in &self.edges {
+            // declarations are structure, not control flow
+            if self.nodes[a].kind != NodeKind::Entity { adj[a].push(b); }
+        }
+        let mut cycles: Vec<Vec<String>> = strongly_connected(&adj).into_iter()
+            .filter(|c| c.len() > 1 || adj[c[0]].contains(&c[0]))
+            .map(|c| {
+                let mut labels: Vec<String> = c.iter()
+                    .filter(|&&i| self.nodes[i].kind == NodeKind::Handler)
+                    .map(|&i| self.nodes[i].label.clone())
+                    .collect();
+                labels.sort();
+                labels
+            })
+            .filter(|l| !l.is_empty())
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    fn cycle_edges(&self) -> BTreeSet<(usize, usize)> {
+        let in_cycle: BTreeSet<&str> = self.signal_cycles().into_iter().flatten()
+            .filter_map(|l| self.find(&format!("handler:{}", l)))
+            .map(|i| self.nodes[i].id.as_str())
+            .collect();
+        // an edge is on a cycle if both ends reach each other; approximate by the handlers'
+        // emitted signals and the signals they dispatch to
+        let mut out = BTreeSet::new();
+        for &(a, b, k) in &self.edges {
+            let ends_in_cycle = |x: usize| match self.nodes[x].kind {
+                NodeKind::Handler => in_cycle.contains(self.nodes[x].id.as_str()),
+                _ => true,
+            };
+            if matches!(k, EdgeKind::Emits | EdgeKind::Dispatches | EdgeKind::Calls) && ends_in_cycle(a) && ends_in_cycle(b)
+                && self.reaches(b, a) {
+                out.insert((a, b));
+            }
+        }
+        out
+    }
+
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack = vec![from];
+        while let Some(x) = stack.pop() {
+            if x == to { return true; }
+            if std::mem::replace(&mut seen[x], true) { continue; }
+            for &(a, b, k) in &self.edges {
+                if a == x && k != EdgeKind::Declares { stack.push(b); }
+            }
+        }
+        false
+    }
+
+    pub fn to_dot(&self) -> String {
+        let shared: BTreeSet<String> = self.shared_helpers().into_iter().map(|(h, _)| h).collect();
+        let cycle = self.cycle_edges();
+        let mut o = String::from("digraph corpus {\n    rankdir=LR;\n");
+        for (i, n) in self.nodes.iter().enumerate() {
+            let style = match n.kind {
+                NodeKind::Entity => "shape=box, style=bold".to_string(),
+                NodeKind::Handler => "shape=ellipse".to_string(),
+                NodeKind::Builtin => "shape=plaintext".to_string(),
+                NodeKind::Helper if shared.contains(&n.label) => "shape=component, style=filled, fillcolor=gold".to_string(),
+                NodeKind::Helper => "shape=component".to_string(),
+                NodeKind::Function => "shape=component, style=dashed".to_string(),
+                NodeKind::Signal => "shape=diamond".to_string(),
+                NodeKind::Unknown => "shape=plaintext, fontcolor=red".to_string(),
+            };
+            let _ = writeln!(o, "    n{} [label=\"{}\", {}];", i, n.label.replace('"', "\\\""), style);
+        }
+        for &(a, b, k) in &self.edges {
+            let mut attrs: Vec<&str> = Vec::new();
+            match k {
+                EdgeKind::Declares => attrs.push("arrowhead=none"),
+                EdgeKind::Calls => {}
+                EdgeKind::Emits => attrs.push("style=dashed, label=\"emits\""),
+                EdgeKind::Dispatches => attrs.push("style=dotted"),
+            }
+            if cycle.contains(&(a, b)) { attrs.push("color=red"); }
+            if attrs.is_empty() {
+                let _ = writeln!(o, "    n{} -> n{};", a, b);
+            } else {
+                let _ = writeln!(o, "    n{} -> n{} [{}];", a, b, attrs.join(", "));
+            }
+        }
+        o.push_str("}\n");
+        o
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let shared: BTreeSet<String> = self.shared_helpers().into_iter().map(|(h, _)| h).collect();
+        let cycle = self.cycle_edges();
+        let mut o = String::from("flowchart LR\n");
+        for (i, n) in self.nodes.iter().enumerate() {
+            let label = n.label.replace('"', "#quot;");
+            let shape = match n.kind {
+                NodeKind::Entity => format!("n{}[\"{}\"]", i, label),
+                NodeKind::Handler => format!("n{}(\"{}\")", i, label),
+                NodeKind::Builtin | NodeKind::Unknown => format!("n{}>\"{}\"]", i, label),
+                NodeKind::Helper | NodeKind::Function => format!("n{}[[\"{}\"]]", i, label),
+                NodeKind::Signal => format!("n{}{{\"{}\"}}", i, label),
+            };
+            let _ = writeln!(o, "    {}", shape);
+        }
+        let mut red = Vec::new();
+        for (k, &(a, b, kind)) in self.edges.iter().enumerate() {
+            let arrow = match kind {
+                EdgeKind::Declares => "---",
+                EdgeKind::Calls => "-->",
+                EdgeKind::Emits => "-. emits .->",
+                EdgeKind::Dispatches => "-.->",
+            };
+            let _ = writeln!(o, "    n{} {} n{}", a, arrow, b);
+            if cycle.contains(&(a, b)) { red.push(k.to_string()); }
+        }
+        let shared_ids: Vec<String> = self.nodes.iter().enumerate()
+            .filter(|(_, n)| n.kind == NodeKind::Helper && shared.contains(&n.label))
+            .map(|(i, _)| format!("n{}", i))
+            .collect();
+        if !shared_ids.is_empty() {
+            o.push_str("    classDef shared fill:#ffd700\n");
+            let _ = writeln!(o, "    class {} shared", shared_ids.join(","));
+        }
+        if !red.is_empty() {
+            let _ = writeln!(o, "    linkStyle {} stroke:red", red.join(","));
+        }
+        o
+    }
+}
+
+/// Tarjan's strongly connected components.
+fn strongly_connected(adj: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct State<'a> {
+        adj: &'a [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        next: usize,
+        out: Vec<Vec<usize>>,
+    }
+    fn visit(s: &mut State, v: usize) {
+        s.index[v] = Some(s.next);
+        s.low[v] = s.next;
+        s.next += 1;
+        s.stack.push(v);
+        s.on_stack[v] = true;
+        for k in 0..s.adj[v].len() {
+            let w = s.adj[v][k];
+            match s.index[w] {
+                None => { visit(s, w); s.low[v] = s.low[v].min(s.low[w]); }
+                Some(iw) if s.on_stack[w] => s.low[v] = s.low[v].min(iw),
+                _ => {}
+            }
+        }
+        if Some(s.low[v]) == s.index[v] {
+            let mut comp = Vec::new();
+            while let Some(w) = s.stack.pop() {
+                s.on_stack[w] = false;
+                comp.push(w);
+                if w == v { break; }
+            }
+            s.out.push(comp);
+        }
+    }
+    let n = adj.len();
+    let mut s = State { adj, index: vec![None; n], low: vec![0; n], on_stack: vec![false; n], stack: Vec::new(), next: 0, out: Vec::new() };
+    for v in 0..n {
+        if s.index[v].is_none() { visit(&mut s, v); }
+    }
+    s.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(relative: &str, src: &str) -> CorpusFile {
+        CorpusFile {
+            path: relative.into(),
+            relative: relative.to_string(),
+            source: src.to_string(),
+            entities: crate::meta_lang::parse_entities(src),
+            functions: crate::meta_lang::parse_functions(src),
+        }
+    }
+
+    const PING_PONG: &str = r#"
+entity Ping { on Tick() { emit("Pong"); _helper_1(5); } }
+entity Pong { on Pong() { relay(); } }
+function relay() { emit("Tick"); }
+function _helper_1(a) { takeDamage(a); collide(); }
+"#;
+
+    #[test]
+    fn emission_cycles_and_shared_helpers() {
+        let other = "entity Rock { on Hit() { _helper_1(1); } }\nfunction _helper_1(a) { takeDamage(a); collide(); }\n";
+        let g = build_graph(&[file("a.meta", PING_PONG), file("b.meta", other)]);
+        assert_eq!(g.signal_cycles(), vec![vec!["Ping.Tick".to_string(), "Pong.Pong".to_string()]]);
+        assert_eq!(g.shared_helpers(), vec![("_helper_1".to_string(), vec!["Ping.Tick".to_string(), "Rock.Hit".to_string()])]);
+    }
+
+    #[test]
+    fn exports() {
+        let g = build_graph(&[file("a.meta", PING_PONG)]);
+        let dot = g.to_dot();
+        assert!(dot.starts_with("digraph corpus {"));
+        assert!(dot.contains("[label=\"Pong\", shape=diamond];"));
+        assert!(dot.contains("style=dashed, label=\"emits\", color=red"));
+        let mermaid = g.to_mermaid();
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("[[\"relay\"]]"));
+        assert!(mermaid.contains("-. emits .->"));
+        assert!(mermaid.contains("linkStyle"));
+    }
+}
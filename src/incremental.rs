@@ -0,0 +1,185 @@
+//! Content-addressed cache and build manifest for incremental supercompilation.
+//!
+//! Driving a file depends only on its AST and the optimizer configuration, so the driven
+//! bodies and their rewrite logs are stored under `<out>/.cache/<key>.driven` where `key`
+//! hashes both. The manifest (`<out>/manifest.txt`) records the inputs, the written outputs with the helpers each one
+//! uses, and where every helper was extracted from, so an unchanged output is not rewritten.
+
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::Path;
+
+use crate::ast::stmt_to_string;
+use crate::meta_lang::{parse_entities, parse_functions};
+
+pub const MANIFEST: &str = "manifest.txt";
+pub const CACHE_DIR: &str = ".cache";
+/// Bumped whenever a pass changes what it produces, invalidating every cached entry.
+pub const PIPELINE_VERSION: u32 = 2;
+
+/// 64-bit FNV-1a: stable across runs and toolchains, unlike `DefaultHasher`.
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+/// Canonical text of a file's AST: formatting and comments do not change it.
+pub fn ast_fingerprint(src: &str) -> String {
+    let mut out = String::new();
+    for e in parse_entities(src) {
+        out.push_str(&format!("entity {} [{}]\n", e.name, e.components.join(",")));
+        for ev in &e.events {
+            let body: Vec<String> = crate::ast::parse_statements(&ev.body).iter().map(stmt_to_string).collect();
+            out.push_str(&format!("on {}({}) {}\n", ev.name, ev.params.clone().unwrap_or_default(), body.join(" ")));
+        }
+    }
+    for f in parse_functions(src) {
+        let body: Vec<String> = crate::ast::parse_statements(&f.body).iter().map(stmt_to_string).collect();
+        out.push_str(&format!("function {}({}) {}\n", f.name, f.params.unwrap_or_default(), body.join(" ")));
+    }
+    out
+}
+
+/// Key of the cached driving result for a file.
+pub fn cache_key(ast: &str, config: &str) -> u64 {
+    content_hash(format!("v{}\n{}\n{}", PIPELINE_VERSION, config, ast).as_bytes())
+}
+
+fn cache_path(out_dir: &str, key: u64) -> String {
+    format!("{}/{}/{:016x}.driven", out_dir.trim_end_matches('/'), CACHE_DIR, key)
+}
+
+pub fn load_cached(out_dir: &str, key: u64) -> Option<String> {
+    read_to_string(cache_path(out_dir, key)).ok()
+}
+
+pub fn store_cached(out_dir: &str, key: u64, contents: &str) -> std::io::Result<()> {
+    create_dir_all(format!("{}/{}", out_dir.trim_end_matches('/'), CACHE_DIR))?;
+    write(cache_path(out_dir, key), contents)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputRecord {
+    pub path: String,
+    /// Hash of the file's AST fingerprint.
+    pub ast: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputRecord {
+    pub path: String,
+    /// Hash of the written contents.
+    pub content: u64,
+    pub helpers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HelperRecord {
+    pub name: String,
+    /// Hash of the helper definition.
+    pub definition: u64,
+    /// Handlers the helper was extracted from, as `file:Entity.Event`.
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    /// Hash of the optimizer configuration.
+    pub config: u64,
+    pub inputs: Vec<InputRecord>,
+    pub outputs: Vec<OutputRecord>,
+    pub helpers: Vec<HelperRecord>,
+}
+
+impl Manifest {
+    pub fn load(out_dir: &str) -> Option<Manifest> {
+        read_to_string(Path::new(out_dir).join(MANIFEST)).ok().and_then(|s| Manifest::parse(&s))
+    }
+
+    pub fn save(&self, out_dir: &str) -> std::io::Result<()> {
+        write(Path::new(out_dir).join(MANIFEST), self.to_string())
+    }
+
+    pub fn output(&self, path: &str) -> Option<&OutputRecord> {
+        self.outputs.iter().find(|o| o.path == path)
+    }
+
+    pub fn parse(s: &str) -> Option<Manifest> {
+        let mut m = Manifest::default();
+        for line in s.lines() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] | ["#", ..] => {}
+                ["config", h] => m.config = hex(h)?,
+                ["input", path, h] => m.inputs.push(InputRecord { path: path.to_string(), ast: hex(h)? }),
+                ["output", path, h, "helpers", list @ ..] => m.outputs.push(OutputRecord {
+                    path: path.to_string(),
+                    content: hex(h)?,
+                    helpers: split_list(list),
+                }),
+                ["helper", name, h, "from", list @ ..] => m.helpers.push(HelperRecord {
+                    name: name.to_string(),
+                    definition: hex(h)?,
+                    sources: split_list(list),
+                }),
+                _ => return None,
+            }
+        }
+        Some(m)
+    }
+}
+
+impl std::fmt::Display for Manifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# supercompiler manifest v{}", PIPELINE_VERSION)?;
+        writeln!(f, "config {:016x}", self.config)?;
+        for i in &self.inputs {
+            writeln!(f, "input {} {:016x}", i.path, i.ast)?;
+        }
+        for o in &self.outputs {
+            writeln!(f, "output {} {:016x} helpers {}", o.path, o.content, o.helpers.join(","))?;
+        }
+        for h in &self.helpers {
+            writeln!(f, "helper {} {:016x} from {}", h.name, h.definition, h.sources.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+fn hex(s: &str) -> Option<u64> {
+    u64::from_str_radix(s, 16).ok()
+}
+
+fn split_list(words: &[&str]) -> Vec<String> {
+    words.concat().split(',').filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_does_not_change_the_key() {
+        let a = "entity A { on Hit() { takeDamage(10); } }";
+        let b = "entity A {\n    on Hit() {\n        takeDamage(10);\n    }\n}\n";
+        assert_eq!(cache_key(&ast_fingerprint(a), "cfg"), cache_key(&ast_fingerprint(b), "cfg"));
+        assert_ne!(cache_key(&ast_fingerprint(a), "cfg"), cache_key(&ast_fingerprint(a), "other"));
+    }
+
+    #[test]
+    fn manifest_round_trips() {
+        let m = Manifest {
+            config: 7,
+            inputs: vec![InputRecord { path: "a.meta".into(), ast: 1 }],
+            outputs: vec![
+                OutputRecord { path: "a.meta".into(), content: 2, helpers: vec!["_helper_1".into()] },
+                OutputRecord { path: "b.meta".into(), content: 3, helpers: vec![] },
+            ],
+            helpers: vec![HelperRecord { name: "_helper_1".into(), definition: 4, sources: vec!["a.meta:A.Hit".into(), "a.meta:B.Hit".into()] }],
+        };
+        assert_eq!(Manifest::parse(&m.to_string()), Some(m));
+    }
+}
@@ -1,12 +1,28 @@
 pub mod meta_lang;
+pub mod corpus;
 pub mod runtime;
 pub mod analyzer;
+pub mod budget;
+pub mod builtins;
+pub mod lint;
+pub mod clones;
+pub mod graph;
 pub mod ast;
 pub mod supercompiler;
 pub mod aot_generator;
+pub mod differential;
+pub mod driving;
+pub mod cost_model;
+pub mod equivalence;
+pub mod incremental;
 pub mod game_engine;
 pub mod components;
+pub mod ecs;
+pub mod scheduler;
 pub mod scene;
+pub mod scene_file;
+pub mod spawner;
+pub mod prefab;
 pub mod systems;
 pub mod renderer;
 
@@ -0,0 +1,381 @@
+//! Corpus linter built on the analyzer.
+//!
+//! Every rule has an ID and a default severity that `LintConfig` can override or turn off.
+//! A file opts out of rules with a comment such as `// lint: allow(empty-handler, unused-component)`
+//! (or `allow(all)`). Findings whose fix cannot change behaviour carry a `Fix`, applied by
+//! `fix_corpus`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::analyzer::{CorpusStats, analyze_files};
+use crate::ast::{Expr, Stmt, fold_expr, parse_statements};
+use crate::builtins::{ENGINE_COMPONENTS, builtin};
+use crate::corpus::{CorpusError, CorpusFile, CorpusOptions, load_corpus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+pub struct Rule {
+    pub id: &'static str,
+    pub severity: Severity,
+    pub description: &'static str,
+}
+
+pub const RULES: &[Rule] = &[
+    Rule { id: "unused-component", severity: Severity::Info, description: "component declared but needed by no call and not consumed by the engine" },
+    Rule { id: "empty-handler", severity: Severity::Warning, description: "event handler with an empty body" },
+    Rule { id: "unknown-builtin", severity: Severity::Error, description: "call to something that is neither a builtin nor a function of the file" },
+    Rule { id: "missing-component", severity: Severity::Error, description: "handler calls a builtin needing a component the entity does not declare" },
+    Rule { id: "duplicate-entity", severity: Severity::Error, description: "entity name declared more than once in the corpus" },
+    Rule { id: "unreachable-branch", severity: Severity::Warning, description: "`if` whose condition is constant after folding" },
+];
+
+/// Per-rule severity overrides; `None` disables the rule.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    overrides: HashMap<String, Option<Severity>>,
+}
+
+impl LintConfig {
+    pub fn set(&mut self, rule: &str, severity: Option<Severity>) -> &mut Self {
+        self.overrides.insert(rule.to_string(), severity);
+        self
+    }
+
+    pub fn severity(&self, rule: &str) -> Option<Severity> {
+        match self.overrides.get(rule) {
+            Some(s) => *s,
+            None => RULES.iter().find(|r| r.id == rule).map(|r| r.severity),
+        }
+    }
+}
+
+/// Replace `start..end` of the file's source with `replacement`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub file: String,
+    /// `Entity` or `Entity.Event` the finding is about.
+    pub location: String,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}[{}] {}: {}", self.file, self.severity, self.rule, self.location, self.message)?;
+        if self.fix.is_some() { write!(f, " (fixable)")?; }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LintReport {
+    pub diagnostics: Vec<Diagnostic>,
+    pub errors: Vec<CorpusError>,
+}
+
+impl LintReport {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+pub fn lint_corpus(path: &str, config: &LintConfig) -> LintReport {
+    let corpus = load_corpus(path, &CorpusOptions::default());
+    LintReport { diagnostics: lint_files(&corpus.files, config), errors: corpus.errors }
+}
+
+pub fn lint_files(files: &[CorpusFile], config: &LintConfig) -> Vec<Diagnostic> {
+    let stats = analyze_files(files);
+    let mut out = Vec::new();
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for file in files {
+        let allowed = suppressions(&file.source);
+        let mut emit = |rule: &'static str, location: String, message: String, fix: Option<Fix>| {
+            if allowed.contains(rule) || allowed.contains("all") { return; }
+            if let Some(severity) = config.severity(rule) {
+                out.push(Diagnostic { rule, severity, file: file.relative.clone(), location, message, fix });
+            }
+        };
+        let functions: HashSet<&str> = file.functions.iter().map(|f| f.name.as_str()).collect();
+
+        for e in &file.entities {
+            match seen.get(e.name.as_str()) {
+                Some(first) => emit("duplicate-entity", e.name.clone(), format!("also declared in {}", first), None),
+                None => { seen.insert(&e.name, &file.relative); }
+            }
+
+            let mut needed: HashSet<&str> = HashSet::new();
+            for ev in &e.events {
+                let location = format!("{}.{}", e.name, ev.name);
+                if ev.body.trim().is_empty() {
+                    let fix = handler_span(&file.source, &e.name, &ev.name)
+                        .map(|(start, _, close)| removal(&file.source, start, close + 1));
+                    emit("empty-handler", location.clone(), "handler does nothing".to_string(), fix);
+                }
+                for callee in reachable_calls(&stats, &file.relative, &location) {
+                    match builtin(&callee) {
+                        Some(b) => {
+                            for req in b.requires {
+                                needed.insert(req);
+                                if !e.components.iter().any(|c| c == req) {
+                                    emit("missing-component", location.clone(),
+                                        format!("`{}` needs component {}", callee, req), None);
+                                }
+                            }
+                        }
+                        None if functions.contains(callee.as_str()) => {}
+                        None => emit("unknown-builtin", location.clone(), format!("unknown call `{}`", callee), None),
+                    }
+                }
+                if let Some((_, open, close)) = handler_span(&file.source, &e.name, &ev.name) {
+                    for (taken, fix) in constant_branches(&file.source, open + 1, close) {
+                        let message = if taken { "condition is always true" } else { "branch is never taken" };
+                        emit("unreachable-branch", location.clone(), message.to_string(), Some(fix));
+                    }
+                }
+            }
+            for c in &e.components {
+                if !needed.contains(c.as_str()) && !ENGINE_COMPONENTS.contains(&c.as_str()) {
+                    emit("unused-component", e.name.clone(), format!("component {} is never used", c), None);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Apply fixes to `source`; fixes overlapping an already applied one are skipped.
+pub fn apply_fixes(source: &str, fixes: &[&Fix]) -> String {
+    let mut sorted: Vec<&Fix> = fixes.to_vec();
+    sorted.sort_by_key(|f| (f.start, f.end));
+    let mut out = String::new();
+    let mut pos = 0;
+    for f in sorted {
+        if f.start < pos { continue; }
+        out.push_str(&source[pos..f.start]);
+        out.push_str(&f.replacement);
+        pos = f.end;
+    }
+    out.push_str(&source[pos..]);
+    out
+}
+
+/// Lint the corpus and rewrite every file with fixable findings. Returns the files changed.
+pub fn fix_corpus(path: &str, config: &LintConfig) -> std::io::Result<Vec<String>> {
+    let corpus = load_corpus(path, &CorpusOptions::default());
+    let diagnostics = lint_files(&corpus.files, config);
+    let mut changed = Vec::new();
+    for file in &corpus.files {
+        let fixes: Vec<&Fix> = diagnostics.iter()
+            .filter(|d| d.file == file.relative)
+            .filter_map(|d| d.fix.as_ref())
+            .collect();
+        if fixes.is_empty() { continue; }
+        std::fs::write(&file.path, apply_fixes(&file.source, &fixes))?;
+        changed.push(file.relative.clone());
+    }
+    Ok(changed)
+}
+
+/// Rules named in `// lint: allow(...)` comments.
+fn suppressions(src: &str) -> HashSet<String> {
+    let mut out = HashSet::new();
+    for line in src.lines() {
+        let Some(comment) = line.split_once("//").map(|(_, c)| c.trim()) else { continue };
+        let Some(rest) = comment.strip_prefix("lint:") else { continue };
+        let Some(list) = rest.trim().strip_prefix("allow(").and_then(|r| r.split_once(')')).map(|(l, _)| l) else { continue };
+        out.extend(list.split(',').map(|r| r.trim().to_string()).filter(|r| !r.is_empty()));
+    }
+    out
+}
+
+/// Everything a handler calls, directly or through the file's `function`s.
+fn reachable_calls(stats: &CorpusStats, file: &str, from: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    let mut stack = vec![from.to_string()];
+    while let Some(node) = stack.pop() {
+        for edge in stats.call_graph.iter().filter(|c| c.file == file && c.from == node) {
+            if !out.contains(&edge.to) {
+                out.push(edge.to.clone());
+                stack.push(edge.to.clone());
+            }
+        }
+    }
+    out
+}
+
+/// `if`s in `src[start..end]` whose folded condition is a literal: never-taken branches are
+/// removed, always-taken ones replaced by their body.
+fn constant_branches(src: &str, start: usize, end: usize) -> Vec<(bool, Fix)> {
+    let b = src.as_bytes();
+    let mut out = Vec::new();
+    let mut i = start;
+    while let Some(off) = src[i..end].find("if") {
+        let at = i + off;
+        i = at + 2;
+        let boundary = at == 0 || !(b[at - 1].is_ascii_alphanumeric() || b[at - 1] == b'_');
+        let rest = src[at + 2..end].trim_start();
+        if !boundary || !rest.starts_with('(') { continue; }
+        let open_paren = end - rest.len();
+        let Some(close_paren) = matching(b, open_paren, b'(', b')') else { continue };
+        let Some(open) = src[close_paren..end].find('{').map(|o| close_paren + o) else { continue };
+        let Some(close) = matching(b, open, b'{', b'}') else { continue };
+        let mut cond = match parse_statements(&format!("{};", &src[open_paren + 1..close_paren])).pop() {
+            Some(Stmt::Expr(e)) => e,
+            _ => continue,
+        };
+        fold_expr(&mut cond);
+        let taken = match cond {
+            Expr::Int(v) => v != 0,
+            Expr::Float(v) => v != 0.0,
+            Expr::Str(s) => !s.is_empty(),
+            _ => continue,
+        };
+        let fix = if taken {
+            Fix { start: at, end: close + 1, replacement: src[open + 1..close].trim().to_string() }
+        } else {
+            removal(src, at, close + 1)
+        };
+        out.push((taken, fix));
+    }
+    out
+}
+
+/// Removal of `start..end`, widened to whole lines when nothing else is on them.
+fn removal(src: &str, start: usize, end: usize) -> Fix {
+    let line_start = src[..start].rfind('\n').map(|p| p + 1).unwrap_or(0);
+    let line_end = src[end..].find('\n').map(|p| end + p + 1).unwrap_or(src.len());
+    if src[line_start..start].trim().is_empty() && src[end..line_end].trim().is_empty() {
+        Fix { start: line_start, end: line_end, replacement: String::new() }
+    } else {
+        Fix { start, end, replacement: String::new() }
+    }
+}
+
+/// Byte offsets of `on <event>`, its body's `{` and matching `}` inside `entity <name>`.
+fn handler_span(src: &str, entity: &str, event: &str) -> Option<(usize, usize, usize)> {
+    let b = src.as_bytes();
+    let (open, close) = src.match_indices("entity").find_map(|(at, _)| {
+        let line = &src[src[..at].rfind('\n').map(|p| p + 1).unwrap_or(0)..at];
+        if line.contains("//") { return None; }
+        let rest = src[at + "entity".len()..].trim_start();
+        let name_end = rest.find(|c: char| c.is_whitespace() || c == '{')?;
+        if &rest[..name_end] != entity { return None; }
+        let open = src.len() - rest.len() + rest.find('{')?;
+        Some((open, matching(b, open, b'{', b'}')?))
+    })?;
+    src[open..close].match_indices("on ").find_map(|(off, _)| {
+        let at = open + off;
+        if b[at - 1].is_ascii_alphanumeric() { return None; }
+        let rest = src[at + 3..close].trim_start();
+        let name_end = rest.find(|c: char| c.is_whitespace() || c == '(')?;
+        if &rest[..name_end] != event { return None; }
+        let body_open = close - rest.len() + rest.find('{')?;
+        Some((at, body_open, matching(b, body_open, b'{', b'}')?))
+    })
+}
+
+fn matching(b: &[u8], open: usize, o: u8, c: u8) -> Option<usize> {
+    let mut depth = 0usize;
+    for (k, &ch) in b.iter().enumerate().skip(open) {
+        if ch == o { depth += 1; }
+        if ch == c {
+            depth -= 1;
+            if depth == 0 { return Some(k); }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(relative: &str, source: &str) -> CorpusFile {
+        CorpusFile {
+            path: relative.into(),
+            relative: relative.to_string(),
+            source: source.to_string(),
+            entities: crate::meta_lang::parse_entities(source),
+            functions: crate::meta_lang::parse_functions(source),
+        }
+    }
+
+    fn rules(ds: &[Diagnostic]) -> Vec<(&str, &str)> {
+        ds.iter().map(|d| (d.rule, d.location.as_str())).collect()
+    }
+
+    const CRATE: &str = "entity Crate {
+    components: [Transform, Health];
+    on Update(dt) {
+        move(velocity * dt);
+        if (1 - 1) { takeDamage(1); }
+    }
+    on Hit() { }
+}
+";
+
+    #[test]
+    fn every_rule_fires() {
+        let files = [file("a.meta", CRATE), file("b.meta", "entity Crate { components: [Physics]; on Tick() { collide(); } }")];
+        let ds = lint_files(&files, &LintConfig::default());
+        assert_eq!(rules(&ds), vec![
+            ("missing-component", "Crate.Update"),
+            ("unreachable-branch", "Crate.Update"),
+            ("empty-handler", "Crate.Hit"),
+            ("unused-component", "Crate"),
+            ("duplicate-entity", "Crate"),
+        ]);
+    }
+
+    #[test]
+    fn suppressions_and_config_silence_rules() {
+        let src = format!("// lint: allow(empty-handler, unused-component)\n{}", CRATE);
+        let mut config = LintConfig::default();
+        config.set("unreachable-branch", None).set("missing-component", Some(Severity::Info));
+        let ds = lint_files(&[file("a.meta", &src)], &config);
+        assert_eq!(rules(&ds), vec![("missing-component", "Crate.Update")]);
+        assert_eq!(ds[0].severity, Severity::Info);
+    }
+
+    #[test]
+    fn fixes_remove_dead_code() {
+        let ds = lint_files(&[file("a.meta", CRATE)], &LintConfig::default());
+        let fixes: Vec<&Fix> = ds.iter().filter_map(|d| d.fix.as_ref()).collect();
+        assert_eq!(apply_fixes(CRATE, &fixes), "entity Crate {
+    components: [Transform, Health];
+    on Update(dt) {
+        move(velocity * dt);
+    }
+}
+");
+        let src = "entity A { components: [AI]; on Tick() { if (2) { patrol(); } } }";
+        let ds = lint_files(&[file("a.meta", src)], &LintConfig::default());
+        let fixes: Vec<&Fix> = ds.iter().filter_map(|d| d.fix.as_ref()).collect();
+        assert_eq!(apply_fixes(src, &fixes), "entity A { components: [AI]; on Tick() { patrol(); } }");
+    }
+}
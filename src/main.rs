@@ -1,6 +1,12 @@
 use experiment::meta_lang::parse_entities;
 use experiment::runtime::{EntityInstance, Value, execute_event};
-use experiment::game_engine::{GameEngine, Node, Component};
+use experiment::game_engine::GameEngine;
+use experiment::components::Vector3;
+use experiment::analyzer::{ReportFormat, analyze_corpus};
+use experiment::lint::{LintConfig, Severity, fix_corpus, lint_corpus};
+use experiment::clones::{CloneOptions, Suggestion, find_clones};
+use experiment::graph::{GraphFormat, corpus_graph};
+use experiment::budget::{Budget, check_budget};
 use std::process::{Command, Stdio};
 use std::env;
 
@@ -46,6 +52,104 @@ fn main() {
         return;
     }
 
+    if let Some(i) = args.iter().position(|a| a == "--analyze") {
+        // --analyze [dir] [--format text|json|csv]
+        let dir = args.get(i + 1).filter(|a| !a.starts_with("--")).map(String::as_str).unwrap_or("corpus");
+        let format = match args.iter().position(|a| a == "--format").and_then(|j| args.get(j + 1)) {
+            Some(f) => match f.parse::<ReportFormat>() {
+                Ok(format) => format,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            },
+            None => ReportFormat::Text,
+        };
+        print!("{}", analyze_corpus(dir).render(format));
+        return;
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--graph") {
+        // --graph [dir] [--format dot|mermaid]
+        let dir = args.get(i + 1).filter(|a| !a.starts_with("--")).map(String::as_str).unwrap_or("corpus");
+        let format = match args.iter().position(|a| a == "--format").and_then(|j| args.get(j + 1)) {
+            Some(f) => match f.parse::<GraphFormat>() {
+                Ok(format) => format,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            },
+            None => GraphFormat::Dot,
+        };
+        let graph = corpus_graph(dir);
+        for cycle in graph.signal_cycles() { eprintln!("signal cycle: {}", cycle.join(" -> ")); }
+        print!("{}", graph.render(format));
+        return;
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--clones") {
+        // --clones [dir]
+        let dir = args.get(i + 1).filter(|a| !a.starts_with("--")).map(String::as_str).unwrap_or("corpus");
+        for g in find_clones(dir, &CloneOptions::default()) {
+            let members: Vec<String> = g.members.iter().map(|m| m.to_string()).collect();
+            println!("clone group (structure {:.2}, text {:.2}): {}", g.structural, g.textual, members.join(", "));
+            match g.suggestion {
+                Suggestion::Mixin { event, body } => println!("  share one `on {}` handler: {}", event, body),
+                Suggestion::Function { definition, calls } => {
+                    println!("  extract: {}", definition);
+                    for (m, c) in calls { println!("  {} => {}", m, c); }
+                }
+                Suggestion::Review => println!("  similar but structurally different, review manually"),
+            }
+        }
+        return;
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--check") {
+        // --check [dir] [--budget file]: lint errors and per-frame cost budgets
+        let dir = args.get(i + 1).filter(|a| !a.starts_with("--")).map(String::as_str).unwrap_or("corpus");
+        let budget_path = args.iter().position(|a| a == "--budget").and_then(|j| args.get(j + 1)).cloned()
+            .unwrap_or_else(|| format!("{}/budget.txt", dir));
+        let budget = if std::path::Path::new(&budget_path).exists() {
+            match Budget::load(&budget_path) {
+                Ok(b) => b,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(2);
+                }
+            }
+        } else {
+            Budget::default()
+        };
+        let report = lint_corpus(dir, &LintConfig::default());
+        for d in report.diagnostics.iter().filter(|d| d.severity == Severity::Error) { println!("{}", d); }
+        let stats = analyze_corpus(dir);
+        for e in &stats.errors { eprintln!("{}", e); }
+        let violations = check_budget(&stats, &budget);
+        for v in &violations { println!("budget: {}", v); }
+        if report.has_errors() || !violations.is_empty() || !stats.errors.is_empty() { std::process::exit(1); }
+        println!("check passed ({} handlers)", stats.events.len());
+        return;
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--lint") {
+        // --lint [dir] [--fix]
+        let dir = args.get(i + 1).filter(|a| !a.starts_with("--")).map(String::as_str).unwrap_or("corpus");
+        let config = LintConfig::default();
+        if args.iter().any(|a| a == "--fix") {
+            match fix_corpus(dir, &config) {
+                Ok(changed) => for f in changed { println!("fixed {}", f); },
+                Err(e) => eprintln!("Failed to apply fixes: {}", e),
+            }
+        }
+        let report = lint_corpus(dir, &config);
+        for e in &report.errors { eprintln!("{}", e); }
+        for d in &report.diagnostics { println!("{}", d); }
+        if report.has_errors() { std::process::exit(1); }
+        return;
+    }
+
     if args.iter().any(|a| a == "--3d") {
         println!("Starting 3D Airplane Viewer...");
         experiment::renderer::run();
@@ -116,7 +220,7 @@ entity Player {
 
     // ============= PART 4: Corpus analysis (commented out for now) =============
     // println!("\n--- Part 4: Corpus Analysis ---");
-    // analyze_corpus("corpus");
+    // print!("{}", analyze_corpus("corpus"));
     // 
     // println!("\n--- Supercompiler: simplifying corpus ---");
     // let (rep, chosen) = supercompiler::simplify_corpus("corpus", "corpus_simplified");
@@ -155,75 +259,34 @@ entity Enemy {
         println!("✓ Scene loaded");
     }
 
-    // Создаем игровые объекты (ноды)
-    {
-        if let Some(scene) = engine.scenes.get_mut("GameScene") {
-            // Player объект
-            let mut player_node = Node::new(
-                "player_1".to_string(),
-                "Player".to_string(),
-                "CharacterBody2D".to_string(),
-            );
-
-            // Добавляем компоненты
-            let mut transform = Component::new("Transform".to_string());
-            transform.set_property("position".to_string(), Value::Float(0.0));
-            player_node.add_component(transform);
-
-            let mut sprite = Component::new("Sprite".to_string());
-            sprite.set_property("texture".to_string(), Value::Str("player.png".to_string()));
-            player_node.add_component(sprite);
-
-            let mut physics = Component::new("Physics".to_string());
+    // Ноды Player и Enemy созданы из определений сущностей; добавляем ещё одного врага
+    match engine.spawn("Enemy", Vector3::new(10.0, 0.0, 0.0)) {
+        Ok(id) => println!("✓ Spawned {}", id),
+        Err(e) => eprintln!("Failed to spawn: {}", e),
+    }
+    if let Some(player) = engine.scenes.get_scene_mut("GameScene").and_then(|s| s.root.find_by_id_mut("player_1")) {
+        if let Some(physics) = player.components.get_mut("Physics") {
             physics.set_property("velocity".to_string(), Value::Float(5.0));
-            player_node.add_component(physics);
-
-            // Добавляем инстанс для выполнения событий метаязыка
-            player_node.instance = Some(EntityInstance::new("Player", "player"));
-
-            // Enemy объект
-            let mut enemy_node = Node::new(
-                "enemy_1".to_string(),
-                "Enemy".to_string(),
-                "CharacterBody2D".to_string(),
-            );
-
-            let mut enemy_transform = Component::new("Transform".to_string());
-            enemy_transform.set_property("position".to_string(), Value::Float(10.0));
-            enemy_node.add_component(enemy_transform);
-
-            let mut enemy_health = Component::new("Health".to_string());
-            enemy_health.set_property("hp".to_string(), Value::Int(50));
-            enemy_node.add_component(enemy_health);
-
-            enemy_node.instance = Some(EntityInstance::new("Enemy", "enemy"));
-
-            // Добавляем ноды в сцену
-            scene.root.add_child(player_node);
-            scene.root.add_child(enemy_node);
-
-            println!("✓ Game objects created: Player, Enemy");
         }
     }
+    println!("✓ Game objects created: Player, Enemy x2");
 
     // Симуляция игровых кадров
     println!("✓ Starting game loop simulation (5 frames)...\n");
     for frame in 0..5 {
-        engine.update(0.016); // 60 FPS = 16.6ms per frame
+        let steps = engine.advance(0.016); // кадр 16 мс, шаг симуляции 1/60 с
         
-        if let Some(scene_name) = &engine.current_scene.clone() {
-            if let Some(scene) = engine.scenes.get(scene_name) {
-                print!("Frame {}: ", frame);
-                print!("Time={:.3}s ", engine.time.total_time);
-                print!("Root children={} ", scene.root.children.len());
-                
-                // Выводим информацию об объектах
-                for (idx, child) in scene.root.children.iter().enumerate() {
-                    if idx > 0 { print!(", "); }
-                    print!("{}", child.name);
-                }
-                println!();
+        if let Some(scene) = engine.scenes.get_active_scene() {
+            print!("Frame {}: ", frame);
+            print!("Time={:.3}s Steps={} Alpha={:.2} ", engine.time.total_time, steps, engine.time.alpha);
+            print!("Root children={} ", scene.root.children.len());
+            
+            // Выводим информацию об объектах
+            for (idx, child) in scene.root.children.iter().enumerate() {
+                if idx > 0 { print!(", "); }
+                print!("{}", child.name);
             }
+            println!();
         }
     }
 
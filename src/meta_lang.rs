@@ -12,12 +12,27 @@ pub struct Event {
     pub body: String,
 }
 
-fn trim_semicolon(s: &str) -> &str {
-    let s = s.trim();
-    if s.ends_with(';') {
-        &s[..s.len() - 1]
-    } else {
-        s
+/// Top-level `function <name>(...) { ... }` definition, e.g. helpers emitted by the supercompiler.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub params: Option<String>,
+    pub body: String,
+}
+
+impl Entity {
+    /// Corpus syntax that `parse_entities` reads back into an equal entity.
+    pub fn to_source(&self) -> String {
+        let mut out = format!("entity {} {{\n", self.name);
+        if !self.components.is_empty() {
+            out.push_str(&format!("    components: [{}];\n", self.components.join(", ")));
+        }
+        for ev in &self.events {
+            let params = ev.params.as_ref().map(|p| format!("({})", p)).unwrap_or_default();
+            out.push_str(&format!("    on {}{} {{ {} }}\n", ev.name, params, ev.body));
+        }
+        out.push_str("}\n");
+        out
     }
 }
 
@@ -28,8 +43,14 @@ pub fn parse_entities(input: &str) -> Vec<Entity> {
     let n = bytes.len();
 
     while i < n {
+        // skip line comments, they may mention entities
+        if input[i..].starts_with("//") {
+            while i < n && bytes[i] != b'\n' { i += 1; }
+            continue;
+        }
         // find "entity"
-        if input[i..].starts_with("entity") {
+        let at_word = i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_');
+        if at_word && input[i..].starts_with("entity") {
             i += "entity".len();
             // skip whitespace
             while i < n && input.as_bytes()[i].is_ascii_whitespace() {
@@ -85,7 +106,9 @@ pub fn parse_entities(input: &str) -> Vec<Entity> {
             let mut events = Vec::new();
             let mut j = 0usize;
             while j < block.len() {
-                if block[j..].starts_with("on ") {
+                if block[j..].starts_with("//") {
+                    while j < block.len() && block.as_bytes()[j] != b'\n' { j += 1; }
+                } else if block[j..].starts_with("on ") {
                     j += 3;
                     while j < block.len() && block.as_bytes()[j].is_ascii_whitespace() { j += 1; }
                     let en_start = j;
@@ -135,6 +158,56 @@ pub fn parse_entities(input: &str) -> Vec<Entity> {
     res
 }
 
+pub fn parse_functions(input: &str) -> Vec<Function> {
+    let mut res = Vec::new();
+    let mut i = 0usize;
+    let n = input.len();
+
+    while i < n {
+        // find "function" at the start of a word
+        let at_word = i == 0 || !input.as_bytes()[i - 1].is_ascii_alphanumeric();
+        if at_word && input.as_bytes()[i..].starts_with(b"function ") {
+            i += "function".len();
+            while i < n && input.as_bytes()[i].is_ascii_whitespace() { i += 1; }
+            let start = i;
+            while i < n {
+                let c = input.as_bytes()[i] as char;
+                if c.is_whitespace() || c == '(' || c == '{' { break; }
+                i += 1;
+            }
+            let name = input[start..i].trim().to_string();
+            while i < n && input.as_bytes()[i].is_ascii_whitespace() { i += 1; }
+            let mut params = None;
+            if i < n && input.as_bytes()[i] == b'(' {
+                i += 1;
+                let pstart = i;
+                while i < n && input.as_bytes()[i] != b')' { i += 1; }
+                params = Some(input[pstart..i].trim().to_string());
+                if i < n { i += 1; }
+            }
+            while i < n && input.as_bytes()[i] != b'{' { i += 1; }
+            if i >= n { break; }
+            i += 1; // skip '{'
+            let mut lev = 1usize;
+            let bstart = i;
+            while i < n && lev > 0 {
+                match input.as_bytes()[i] {
+                    b'{' => lev += 1,
+                    b'}' => lev -= 1,
+                    _ => {}
+                }
+                i += 1;
+            }
+            let body = input[bstart..i - 1].trim().to_string();
+            res.push(Function { name, params, body });
+        } else {
+            i += 1;
+        }
+    }
+
+    res
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,4 +223,14 @@ mod tests {
         assert_eq!(e.events.len(), 1);
         assert_eq!(e.events[0].name, "Tick");
     }
+
+    #[test]
+    fn parse_helper_functions() {
+        let s = "entity A { on Tick() { _helper_1(); } }\nfunction _helper_1() { move(); collide();  }\n";
+        let fs = parse_functions(s);
+        assert_eq!(fs.len(), 1);
+        assert_eq!(fs[0].name, "_helper_1");
+        assert_eq!(fs[0].params.as_deref(), Some(""));
+        assert_eq!(fs[0].body, "move(); collide();");
+    }
 }
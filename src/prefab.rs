@@ -0,0 +1,286 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::components::Vector3;
+use crate::game_engine::Node;
+use crate::meta_lang::Entity;
+use crate::runtime::Value;
+use crate::scene_file::{Arg, Item, SceneFile, node_item, scene_root};
+use crate::spawner::{TypedComponent, node_from_entity};
+
+/// Шаблон поддерева нод, который можно создать в сцене много раз
+#[derive(Debug, Clone)]
+pub struct Prefab {
+    pub name: String,
+    /// Дерево шаблона; ноды с `prefab` внутри — вложенные префабы
+    pub root: Node,
+}
+
+/// Связь корня экземпляра с префабом
+#[derive(Debug, Clone)]
+pub struct PrefabInstance {
+    pub prefab: String,
+    /// Применяются поверх шаблона при создании и при `propagate`
+    pub overrides: Vec<PropertyOverride>,
+}
+
+/// Значение свойства компонента ноды экземпляра, отличное от шаблона
+#[derive(Debug, Clone)]
+pub struct PropertyOverride {
+    /// Путь ноды от корня экземпляра (`"Body/Gun"`), пустой — сам корень
+    pub path: String,
+    pub component: String,
+    pub property: String,
+    pub value: Value,
+}
+
+impl Prefab {
+    pub fn new(name: &str, root: Node) -> Self {
+        Self { name: name.to_string(), root }
+    }
+
+    /// Префаб из сущности метаязыка: та же нода, что создаёт `Spawner`
+    pub fn from_entity(entity: &Entity) -> Self {
+        Self::new(&entity.name, node_from_entity(entity, entity.name.to_lowercase(), Vector3::zero()))
+    }
+}
+
+impl SceneFile for Prefab {
+    fn to_items(&self) -> Vec<Item> {
+        vec![Item::new("prefab", vec![Arg::Str(self.name.clone())]), node_item(&self.root)]
+    }
+
+    fn from_items(items: &[Item]) -> Result<Self, String> {
+        let name = items.iter().find(|i| i.key == "prefab").ok_or("Prefab has no name")?;
+        let Some(Arg::Str(name)) = name.args.first() else {
+            return Err("'prefab' must be followed by its name".to_string());
+        };
+        Ok(Prefab::new(name, scene_root(items)?))
+    }
+}
+
+/// Итог `SceneManager::propagate_prefab`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PropagateReport {
+    /// Пересозданные экземпляры в незагруженных сценах
+    pub updated: usize,
+    /// ID экземпляров в загруженных сценах (и автозагрузке): они остались по старому шаблону
+    pub skipped_live: Vec<String>,
+}
+
+/// Библиотека префабов: создание экземпляров и обновление их после изменения шаблона
+#[derive(Debug, Clone, Default)]
+pub struct PrefabLibrary {
+    prefabs: HashMap<String, Prefab>,
+}
+
+impl PrefabLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавляет префаб или заменяет префаб с тем же именем
+    pub fn add(&mut self, prefab: Prefab) {
+        self.prefabs.insert(prefab.name.clone(), prefab);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Prefab> {
+        self.prefabs.get_mut(name)
+    }
+
+    /// Новый экземпляр префаба с корнем `id`; ID нод шаблона становятся `<id>.<id в шаблоне>`
+    pub fn instantiate(&self, name: &str, id: &str) -> Result<Node, String> {
+        self.build(name, id, &mut Vec::new())
+    }
+
+    fn build(&self, name: &str, id: &str, stack: &mut Vec<String>) -> Result<Node, String> {
+        if stack.iter().any(|p| p == name) {
+            return Err(format!("Prefab '{}' contains itself: {} -> {}", name, stack.join(" -> "), name));
+        }
+        let prefab = self.get(name).ok_or_else(|| format!("Prefab '{}' not found", name))?;
+        stack.push(name.to_string());
+        let node = self.expand(&prefab.root, id, id, stack);
+        stack.pop();
+        let mut node = node?;
+        node.owner = None;
+        node.prefab = Some(PrefabInstance { prefab: name.to_string(), overrides: Vec::new() });
+        Ok(node)
+    }
+
+    fn expand(&self, template: &Node, id: &str, owner: &str, stack: &mut Vec<String>) -> Result<Node, String> {
+        let is_root = id == owner;
+        if let (false, Some(link)) = (is_root, &template.prefab) {
+            // Вложенный префаб разворачивается из библиотеки со своими переопределениями
+            let mut nested = self.build(&link.prefab, id, stack)?;
+            nested.name = template.name.clone();
+            nested.active = template.active;
+            nested.groups = template.groups.clone();
+            nested.set_transform(*template.transform());
+            for o in &link.overrides {
+                apply_override(&mut nested, o)?;
+            }
+            nested.prefab = Some(link.clone());
+            nested.owner = Some(owner.to_string());
+            return Ok(nested);
+        }
+        let mut node = template.clone();
+        node.id = id.to_string();
+        node.owner = Some(owner.to_string());
+        node.prefab = None;
+        node.children = Vec::new();
+        for child in &template.children {
+            let child_id = format!("{}.{}", id, child.id);
+            node.add_child(self.expand(child, &child_id, owner, stack)?);
+        }
+        Ok(node)
+    }
+
+    /// Префабы, которые содержат `name` напрямую или через другие префабы
+    fn dependents(&self, name: &str) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        let mut frontier = vec![name.to_string()];
+        while let Some(current) = frontier.pop() {
+            for prefab in self.prefabs.values() {
+                let uses = !prefab.root.find_all(|n| n.prefab.as_ref().is_some_and(|l| l.prefab == current)).is_empty();
+                if uses && prefab.name != current && found.insert(prefab.name.clone()) {
+                    frontier.push(prefab.name.clone());
+                }
+            }
+        }
+        found
+    }
+
+    /// Пересоздаёт в дереве `tree` все экземпляры префаба `name` (и префабов, которые его
+    /// содержат) из текущего шаблона. Сохраняются ID, имя, трансформ, активность и группы
+    /// корня экземпляра, его переопределения и дети, добавленные к корню после создания;
+    /// состояние скриптов нод шаблона начинается заново. Поэтому живые экземпляры — в дереве
+    /// загруженной сцены или связанные с ECS — не пересоздаются: вернётся ошибка, а `tree`
+    /// останется без изменений. Возвращает число обновлённых экземпляров
+    pub fn propagate(&self, tree: &mut Node, name: &str) -> Result<usize, String> {
+        let affected = self.affected(name);
+        let is_affected = |n: &Node| n.prefab.as_ref().is_some_and(|l| affected.contains(&l.prefab));
+        let is_live = |n: &Node| n.is_inside_tree() || n.ecs.is_some();
+        if let Some(live) = tree.find_all(is_affected).into_iter().find(|i| !i.find_all(is_live).is_empty()) {
+            return Err(format!("Prefab instance '{}' is live (in a loaded scene or an ECS world) and cannot be rebuilt", live.id));
+        }
+        self.refresh(tree, &affected)
+    }
+
+    /// ID всех нод дерева `tree`, которые `propagate(tree, name)` пересоздал бы
+    pub fn instances_of(&self, tree: &Node, name: &str) -> Vec<String> {
+        let affected = self.affected(name);
+        tree.find_all(|n| n.prefab.as_ref().is_some_and(|l| affected.contains(&l.prefab)))
+            .into_iter()
+            .map(|n| n.id.clone())
+            .collect()
+    }
+
+    /// Сам префаб `name` и все префабы, которые его содержат
+    fn affected(&self, name: &str) -> BTreeSet<String> {
+        let mut affected = self.dependents(name);
+        affected.insert(name.to_string());
+        affected
+    }
+
+    fn refresh(&self, node: &mut Node, affected: &BTreeSet<String>) -> Result<usize, String> {
+        let link = node.prefab.clone().filter(|l| affected.contains(&l.prefab));
+        let Some(link) = link else {
+            let mut count = 0;
+            for child in &mut node.children {
+                count += self.refresh(child, affected)?;
+            }
+            return Ok(count);
+        };
+
+        let mut fresh = self.instantiate(&link.prefab, &node.id)?;
+        fresh.name = node.name.clone();
+        fresh.active = node.active;
+        fresh.groups = node.groups.clone();
+        fresh.owner = node.owner.clone();
+        fresh.set_transform(*node.transform());
+        for o in &link.overrides {
+            apply_override(&mut fresh, o)?;
+        }
+        fresh.prefab = Some(link);
+        let own_id = node.id.clone();
+        let mut count = 1;
+        for mut extra in std::mem::take(&mut node.children).into_iter().filter(|c| c.owner.as_deref() != Some(own_id.as_str())) {
+            count += self.refresh(&mut extra, affected)?;
+            fresh.add_child(extra);
+        }
+        *node = fresh;
+        Ok(count)
+    }
+}
+
+/// Переопределяет свойство ноды экземпляра `instance` и запоминает переопределение,
+/// чтобы оно пережило `PrefabLibrary::propagate`
+pub fn set_override(instance: &mut Node, path: &str, component: &str, property: &str, value: Value) -> Result<(), String> {
+    if instance.prefab.is_none() {
+        return Err(format!("Node '{}' is not a prefab instance", instance.id));
+    }
+    let o = PropertyOverride { path: path.to_string(), component: component.to_string(), property: property.to_string(), value };
+    apply_override(instance, &o)?;
+    let link = instance.prefab.as_mut().expect("checked above");
+    link.overrides.retain(|e| (&e.path, &e.component, &e.property) != (&o.path, &o.component, &o.property));
+    link.overrides.push(o);
+    Ok(())
+}
+
+fn apply_override(instance: &mut Node, o: &PropertyOverride) -> Result<(), String> {
+    let instance_id = instance.id.clone();
+    let target = if o.path.is_empty() { Some(instance) } else { instance.get_node_mut(&o.path) };
+    let target = target.ok_or_else(|| format!("Prefab instance '{}' has no node '{}'", instance_id, o.path))?;
+    let component = target.components.get_mut(&o.component)
+        .ok_or_else(|| format!("Node '{}' has no component '{}'", target.id, o.component))?;
+    component.set_property(o.property.clone(), o.value.clone());
+    // Типизированный трансформ ноды — источник положения для скриптов
+    if let Some(TypedComponent::Transform(t)) = TypedComponent::from_component(component) {
+        if target.transform().position != t.position {
+            target.transform_mut().position = t.position;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(id: &str, name: &str) -> Node {
+        Node::new(id.to_string(), name.to_string(), "Node".to_string())
+    }
+
+    #[test]
+    fn nested_prefab_cycles_are_reported() {
+        let mut library = PrefabLibrary::new();
+        let mut a = leaf("a", "A");
+        let mut b_link = leaf("b", "B");
+        b_link.prefab = Some(PrefabInstance { prefab: "B".to_string(), overrides: Vec::new() });
+        a.add_child(b_link);
+        let mut b = leaf("b", "B");
+        let mut a_link = leaf("a", "A");
+        a_link.prefab = Some(PrefabInstance { prefab: "A".to_string(), overrides: Vec::new() });
+        b.add_child(a_link);
+        library.add(Prefab::new("A", a));
+        library.add(Prefab::new("B", b));
+        assert!(library.instantiate("A", "a_1").unwrap_err().contains("A -> B -> A"));
+        assert!(library.instantiate("C", "c_1").unwrap_err().contains("not found"));
+    }
+
+    #[test]
+    fn overrides_need_an_existing_component() {
+        let library = {
+            let mut l = PrefabLibrary::new();
+            l.add(Prefab::new("Crate", leaf("crate", "Crate")));
+            l
+        };
+        let mut instance = library.instantiate("Crate", "crate_1").unwrap();
+        let err = set_override(&mut instance, "", "Health", "hp", Value::Int(5)).unwrap_err();
+        assert!(err.contains("no component 'Health'"));
+        assert!(set_override(&mut leaf("x", "X"), "", "Health", "hp", Value::Int(5)).is_err());
+    }
+}
@@ -74,7 +74,7 @@ impl Renderer {
         // The window is owned in run() and outlives the renderer.
         let surface = unsafe {
             let window_ptr = &*window as *const Window;
-            let window_ref: &'static Window = std::mem::transmute(window_ptr);
+            let window_ref: &'static Window = &*window_ptr;
             instance.create_surface(window_ref).unwrap()
         };
         let adapter = instance
@@ -35,9 +35,36 @@ impl EntityInstance {
     }
 }
 
+/// Observable side effect produced by a builtin call while executing a handler.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    Moved(f64),
+    Collided,
+    Damaged(i32),
+    /// `queueFree()`: the node asked to be removed once the current frame finishes.
+    Freed,
+    Unrecognized(String),
+}
+
 pub fn execute_event(entity: &mut EntityInstance, event: &Event, params: &HashMap<String, Value>) {
+    execute_event_traced(entity, event, params);
+}
+
+/// Same as `execute_event`, but also returns the effects emitted by the handler in order.
+pub fn execute_event_traced(entity: &mut EntityInstance, event: &Event, params: &HashMap<String, Value>) -> Vec<Effect> {
+    let mut effects = Vec::new();
+    let (guard, stmts) = split_guard(&event.body);
+    if guard.map(|cond| eval_condition(cond, params)).unwrap_or(true) {
+        exec_statements(entity, stmts, params, &mut effects);
+    }
+    effects
+}
+
+/// Decompose a handler body the way the executor sees it: an optional leading
+/// `if (cond) { ... }` guard and the statement text that runs when it holds.
+pub(crate) fn split_guard(body: &str) -> (Option<&str>, &str) {
     // naive executor: handle simple statements and one-level if conditions
-    let body = event.body.trim();
+    let body = body.trim();
     if body.starts_with("if ") {
         // parse condition and inner body
         if let Some(cond_start) = body.find('(') {
@@ -45,39 +72,36 @@ pub fn execute_event(entity: &mut EntityInstance, event: &Event, params: &HashMa
                 let cond = body[cond_start+1..cond_end].trim();
                 let inner_start = body.find('{').unwrap_or(body.len());
                 let inner_end = body.rfind('}').unwrap_or(body.len());
-                let inner = &body[inner_start+1..inner_end];
-                if eval_condition(cond, params) {
-                    exec_statements(entity, inner, params);
-                }
-                return;
+                return (Some(cond), &body[inner_start+1..inner_end]);
             }
         }
     }
-
-    exec_statements(entity, body, params);
+    (None, body)
 }
 
 fn eval_condition(cond: &str, params: &HashMap<String, Value>) -> bool {
-    // only supports patterns like: other.tag == "Enemy"
+    match tag_guard(cond) {
+        Some((var, tag)) => matches!(params.get(var), Some(Value::EntitySnapshot(se)) if se.tag == tag),
+        None => false,
+    }
+}
+
+/// Recognize the only supported condition shape, `other.tag == "Enemy"`, as `("other", "Enemy")`.
+pub(crate) fn tag_guard(cond: &str) -> Option<(&str, &str)> {
     if cond.contains("==") {
         let parts: Vec<&str> = cond.split("==").map(|s| s.trim()).collect();
         if parts.len() == 2 {
             let left = parts[0];
             let right = parts[1].trim_matches('"');
             if left.ends_with(".tag") {
-                let var = left.trim_end_matches(".tag");
-                if let Some(v) = params.get(var) {
-                    if let Value::EntitySnapshot(se) = v {
-                        return se.tag == right;
-                    }
-                }
+                return Some((left.trim_end_matches(".tag"), right));
             }
         }
     }
-    false
+    None
 }
 
-fn exec_statements(entity: &mut EntityInstance, body: &str, params: &HashMap<String, Value>) {
+fn exec_statements(entity: &mut EntityInstance, body: &str, params: &HashMap<String, Value>, effects: &mut Vec<Effect>) {
     // split by semicolon; handle simple function calls and expressions
     for stmt in body.split(';') {
         let s = stmt.trim();
@@ -87,10 +111,12 @@ fn exec_statements(entity: &mut EntityInstance, body: &str, params: &HashMap<Str
                 if let Some(Value::Float(dt)) = params.get("dt") {
                     let dist = entity.velocity * dt;
                     entity.position += dist;
+                    effects.push(Effect::Moved(dist));
                     println!("{} moves by {} (velocity {} * dt {})", entity.name, dist, entity.velocity, dt);
                 }
             }
         } else if s.starts_with("collide(") || s == "collide()" {
+            effects.push(Effect::Collided);
             println!("{} collided (simulated)", entity.name);
         } else if s.starts_with("takeDamage(") {
             if let Some(open) = s.find('(') {
@@ -98,11 +124,15 @@ fn exec_statements(entity: &mut EntityInstance, body: &str, params: &HashMap<Str
                     let num = s[open+1..close].trim().trim_matches('"');
                     if let Ok(v) = num.parse::<i32>() {
                         entity.health -= v;
+                        effects.push(Effect::Damaged(v));
                         println!("{} takes {} damage, health -> {}", entity.name, v, entity.health);
                     }
                 }
             }
+        } else if s == "queueFree()" {
+            effects.push(Effect::Freed);
         } else {
+            effects.push(Effect::Unrecognized(s.to_string()));
             println!("Unrecognized stmt: '{}'", s);
         }
     }
@@ -1,19 +1,45 @@
 use std::collections::HashMap;
+use std::ops::Index;
+use crate::components::Vector3;
 use crate::game_engine::Node;
-use crate::meta_lang::Entity;
+use crate::meta_lang::{Entity, parse_entities};
+use crate::prefab::{PrefabLibrary, PropagateReport, set_override};
+use crate::runtime::Value;
+use crate::spawner::Spawner;
 
-/// Система сцен для управления игровыми объектами
+/// Имя сцены с автозагружаемыми нодами (`SceneManager::autoload`)
+pub const AUTOLOAD_SCENE: &str = "autoload";
+
+/// Система сцен для управления игровыми объектами. Переходы между сценами
+/// (со скриптовыми событиями) выполняет `GameEngine`: `load_scene`, `push_scene`,
+/// `pop_scene`, `load_additive`, `unload_scene`
+#[derive(Debug, Clone)]
 pub struct SceneManager {
-    pub scenes: HashMap<String, GameScene>,
+    pub scenes: HashMap<String, Scene>,
+    /// Верхняя сцена стека
     pub active_scene: Option<String>,
+    /// Сцены под активной, от нижней к верхней: остаются в дереве и рисуются,
+    /// но не обновляются, пока их не вернёт `pop_scene`
+    pub stack: Vec<String>,
+    /// Сцены, загруженные вместе с активной и обновляемые каждый кадр
+    pub additive: Vec<String>,
+    /// Ноды, которые живут вне сцен и переживают любую смену сцены
+    pub autoload: Scene,
+    /// Префабы, общие для всех сцен
+    pub prefabs: PrefabLibrary,
 }
 
-/// Полное описание сцены
-pub struct GameScene {
+/// Сцена: дерево нод, сущности метаязыка и включённые подсистемы
+#[derive(Debug, Clone)]
+pub struct Scene {
     pub name: String,
     pub root: Node,
-    pub meta_entities: Vec<Entity>,
+    pub entities_meta: Vec<Entity>,
+    /// Счётчики ID для нод, созданных из сущностей
+    pub spawner: Spawner,
+    /// `GameEngine` выполняет шаги симуляции (`on PhysicsProcess`) только при включённой физике
     pub physics_enabled: bool,
+    /// При выключенном рендере `GameEngine::render_items` пуст
     pub render_enabled: bool,
 }
 
@@ -22,21 +48,26 @@ impl SceneManager {
         Self {
             scenes: HashMap::new(),
             active_scene: None,
+            stack: Vec::new(),
+            additive: Vec::new(),
+            autoload: Scene::new(AUTOLOAD_SCENE.to_string()),
+            prefabs: PrefabLibrary::new(),
         }
     }
 
-    pub fn add_scene(&mut self, scene: GameScene) {
+    pub fn add_scene(&mut self, scene: Scene) {
         self.scenes.insert(scene.name.clone(), scene);
     }
 
-    pub fn get_scene(&self, name: &str) -> Option<&GameScene> {
+    pub fn get_scene(&self, name: &str) -> Option<&Scene> {
         self.scenes.get(name)
     }
 
-    pub fn get_scene_mut(&mut self, name: &str) -> Option<&mut GameScene> {
+    pub fn get_scene_mut(&mut self, name: &str) -> Option<&mut Scene> {
         self.scenes.get_mut(name)
     }
 
+    /// Переключает активную сцену без переходов и событий
     pub fn set_active_scene(&mut self, name: String) -> Result<(), String> {
         if self.scenes.contains_key(&name) {
             self.active_scene = Some(name);
@@ -46,14 +77,76 @@ impl SceneManager {
         }
     }
 
-    pub fn get_active_scene(&self) -> Option<&GameScene> {
+    pub fn get_active_scene(&self) -> Option<&Scene> {
         self.active_scene.as_ref().and_then(|name| self.get_scene(name))
     }
 
-    pub fn get_active_scene_mut(&mut self) -> Option<&mut GameScene> {
+    pub fn get_active_scene_mut(&mut self) -> Option<&mut Scene> {
         let name = self.active_scene.clone()?;
         self.get_scene_mut(&name)
     }
+
+    /// Сцены, которые обновляются в кадре: активная и аддитивные
+    pub fn running_scenes(&self) -> Vec<String> {
+        self.active_scene.iter().chain(&self.additive).cloned().collect()
+    }
+
+    /// Все сцены в дереве в порядке отрисовки: стек снизу вверх, активная, аддитивные
+    pub fn loaded_scenes(&self) -> Vec<String> {
+        self.stack.iter().chain(&self.active_scene).chain(&self.additive).cloned().collect()
+    }
+
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.loaded_scenes().iter().any(|s| s == name)
+    }
+
+    /// Переопределяет свойство в экземпляре префаба (`path` — от корня экземпляра)
+    pub fn set_prefab_override(
+        &mut self,
+        scene_name: &str,
+        instance_id: &str,
+        path: &str,
+        component: &str,
+        property: &str,
+        value: Value,
+    ) -> Result<(), String> {
+        let scene = self.scenes.get_mut(scene_name)
+            .ok_or_else(|| format!("Scene '{}' not found", scene_name))?;
+        let instance = scene.root.find_by_id_mut(instance_id)
+            .ok_or_else(|| format!("Node '{}' not found in scene '{}'", instance_id, scene_name))?;
+        set_override(instance, path, component, property, value)
+    }
+
+    /// Обновляет экземпляры изменённого префаба во всех незагруженных сценах. Живые ноды
+    /// загруженных сцен и автозагрузки не пересоздаются (см. `PrefabLibrary::propagate`):
+    /// их ID возвращаются в `skipped_live`, а обновит их повторный вызов после выгрузки.
+    /// Сначала обновляются копии всех сцен; при ошибке ни одна сцена не меняется
+    pub fn propagate_prefab(&mut self, prefab: &str) -> Result<PropagateReport, String> {
+        let loaded = self.loaded_scenes();
+        let mut names: Vec<&String> = self.scenes.keys().collect();
+        names.sort();
+        let mut report = PropagateReport::default();
+        report.skipped_live.extend(self.prefabs.instances_of(&self.autoload.root, prefab));
+        let mut rebuilt = Vec::new();
+        for name in names {
+            let root = &self.scenes[name].root;
+            if loaded.contains(name) {
+                report.skipped_live.extend(self.prefabs.instances_of(root, prefab));
+                continue;
+            }
+            if self.prefabs.instances_of(root, prefab).is_empty() {
+                continue;
+            }
+            let mut root = root.clone();
+            report.updated += self.prefabs.propagate(&mut root, prefab)
+                .map_err(|e| format!("Scene '{}': {}", name, e))?;
+            rebuilt.push((name.clone(), root));
+        }
+        for (name, root) in rebuilt {
+            self.scenes.get_mut(&name).expect("scene listed above").root = root;
+        }
+        Ok(report)
+    }
 }
 
 impl Default for SceneManager {
@@ -62,17 +155,36 @@ impl Default for SceneManager {
     }
 }
 
-impl GameScene {
+impl Index<&str> for SceneManager {
+    type Output = Scene;
+
+    fn index(&self, name: &str) -> &Scene {
+        self.get_scene(name).unwrap_or_else(|| panic!("Scene '{}' not found", name))
+    }
+}
+
+impl Scene {
     pub fn new(name: String) -> Self {
         Self {
+            root: Node::new(format!("root_{}", name), "Root".to_string(), "Node".to_string()),
             name,
-            root: Node::new("root".to_string(), "Root".to_string(), "Node".to_string()),
-            meta_entities: Vec::new(),
+            entities_meta: Vec::new(),
+            spawner: Spawner::new(),
             physics_enabled: true,
             render_enabled: true,
         }
     }
 
+    /// Сцена из определения метаязыка: по одному экземпляру каждой сущности в начале координат
+    pub fn from_meta(name: &str, meta_definition: &str) -> Self {
+        let mut scene = Scene::new(name.to_string());
+        scene.entities_meta = parse_entities(meta_definition);
+        for entity in &scene.entities_meta {
+            scene.root.add_child(scene.spawner.spawn(entity, Vector3::zero()));
+        }
+        scene
+    }
+
     pub fn with_physics(mut self, enabled: bool) -> Self {
         self.physics_enabled = enabled;
         self
@@ -0,0 +1,734 @@
+use std::collections::HashMap;
+
+use crate::components::{Quaternion, Transform, Vector3};
+use crate::game_engine::{Component, Node};
+use crate::meta_lang::{Entity, parse_entities};
+use crate::prefab::{PrefabInstance, PropertyOverride};
+use crate::runtime::{EntityInstance, SimpleEntity, Value};
+use crate::scene::Scene;
+use crate::spawner::Spawner;
+
+/// Миграции схемы: `MIGRATIONS[i]` переводит документ версии `i + 1` в версию `i + 2`
+const MIGRATIONS: &[fn(&mut [Item])] = &[migrate_v1_typed_transform, migrate_v2_prefabs];
+
+/// Версия, которую пишут `to_text`/`to_binary`
+pub const SCENE_FORMAT_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+/// Расширение файлов в бинарном формате; остальные пути — текст (`.scene`)
+pub const BINARY_EXTENSION: &str = "sceneb";
+
+const BINARY_MAGIC: &[u8; 4] = b"MSCN";
+
+/// Документ сцены: дерево записей `ключ аргументы { дети }` с номером версии схемы.
+/// Текстовый и бинарный форматы — два представления одного документа
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub version: u32,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub key: String,
+    pub args: Vec<Arg>,
+    pub children: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    /// Слово без кавычек: `true`, `snapshot`
+    Word(String),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    /// Поля типизированных компонентов; в бинарном формате занимает 4 байта
+    Float32(f32),
+}
+
+impl Item {
+    pub fn new(key: &str, args: Vec<Arg>) -> Self {
+        Self { key: key.to_string(), args, children: Vec::new() }
+    }
+
+    fn with_children(mut self, children: Vec<Item>) -> Self {
+        self.children = children;
+        self
+    }
+
+    fn child(&self, key: &str) -> Option<&Item> {
+        self.children.iter().find(|c| c.key == key)
+    }
+
+    fn str(&self, i: usize) -> Result<&str, String> {
+        match self.args.get(i) {
+            Some(Arg::Str(s)) | Some(Arg::Word(s)) => Ok(s),
+            other => Err(format!("'{}': argument {} must be a string, got {:?}", self.key, i + 1, other)),
+        }
+    }
+
+    fn int(&self, i: usize) -> Result<i64, String> {
+        match self.args.get(i) {
+            Some(Arg::Int(v)) => Ok(*v),
+            other => Err(format!("'{}': argument {} must be an integer, got {:?}", self.key, i + 1, other)),
+        }
+    }
+
+    fn float(&self, i: usize) -> Result<f64, String> {
+        match self.args.get(i) {
+            Some(Arg::Float(v)) => Ok(*v),
+            Some(Arg::Float32(v)) => Ok(*v as f64),
+            Some(Arg::Int(v)) => Ok(*v as f64),
+            other => Err(format!("'{}': argument {} must be a number, got {:?}", self.key, i + 1, other)),
+        }
+    }
+
+    fn bool(&self, i: usize) -> Result<bool, String> {
+        match self.args.get(i) {
+            Some(Arg::Word(w)) if w == "true" => Ok(true),
+            Some(Arg::Word(w)) if w == "false" => Ok(false),
+            other => Err(format!("'{}': argument {} must be true or false, got {:?}", self.key, i + 1, other)),
+        }
+    }
+}
+
+impl Document {
+    /// Приводит документ к `SCENE_FORMAT_VERSION`
+    pub fn migrate(mut self) -> Result<Document, String> {
+        if self.version == 0 || self.version > SCENE_FORMAT_VERSION {
+            return Err(format!("Scene format version {} is not supported (latest is {})", self.version, SCENE_FORMAT_VERSION));
+        }
+        while self.version < SCENE_FORMAT_VERSION {
+            MIGRATIONS[self.version as usize - 1](&mut self.items);
+            self.version += 1;
+        }
+        Ok(self)
+    }
+}
+
+/// Сцена, которую можно сохранить в файл и загрузить обратно
+pub trait SceneFile: Sized {
+    fn to_items(&self) -> Vec<Item>;
+    fn from_items(items: &[Item]) -> Result<Self, String>;
+
+    fn to_document(&self) -> Document {
+        Document { version: SCENE_FORMAT_VERSION, items: self.to_items() }
+    }
+
+    fn to_text(&self) -> String {
+        write_text(&self.to_document())
+    }
+
+    fn from_text(text: &str) -> Result<Self, String> {
+        Self::from_items(&parse_text(text)?.migrate()?.items)
+    }
+
+    fn to_binary(&self) -> Vec<u8> {
+        write_binary(&self.to_document())
+    }
+
+    fn from_binary(bytes: &[u8]) -> Result<Self, String> {
+        Self::from_items(&parse_binary(bytes)?.migrate()?.items)
+    }
+
+    /// Сохраняет в бинарном формате, если расширение `BINARY_EXTENSION`, иначе текстом
+    fn save(&self, path: &str) -> Result<(), String> {
+        let bytes = if is_binary_path(path) { self.to_binary() } else { self.to_text().into_bytes() };
+        std::fs::write(path, bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn load(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        let scene = if is_binary_path(path) {
+            Self::from_binary(&bytes)
+        } else {
+            String::from_utf8(bytes).map_err(|e| e.to_string()).and_then(|text| Self::from_text(&text))
+        };
+        scene.map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+fn is_binary_path(path: &str) -> bool {
+    std::path::Path::new(path).extension().is_some_and(|e| e == BINARY_EXTENSION)
+}
+
+impl SceneFile for Scene {
+    fn to_items(&self) -> Vec<Item> {
+        let flag = |b: bool| Arg::Word(b.to_string());
+        let mut items = vec![
+            Item::new("name", vec![Arg::Str(self.name.clone())]),
+            Item::new("physics", vec![flag(self.physics_enabled)]),
+            Item::new("render", vec![flag(self.render_enabled)]),
+        ];
+        for (entity, count) in self.spawner.counters() {
+            items.push(Item::new("counter", vec![Arg::Str(entity.to_string()), Arg::Int(count as i64)]));
+        }
+        items.extend(self.entities_meta.iter().map(script_item));
+        items.push(node_item(&self.root));
+        items
+    }
+
+    fn from_items(items: &[Item]) -> Result<Self, String> {
+        let mut spawner = Spawner::new();
+        for item in items.iter().filter(|i| i.key == "counter") {
+            let entity = item.str(0)?;
+            let count = usize::try_from(item.int(1)?)
+                .map_err(|_| format!("counter '{}' must be a non-negative integer", entity))?;
+            spawner.set_counter(entity, count);
+        }
+        let flag = |key: &str| items.iter().find(|i| i.key == key).map_or(Ok(true), |i| i.bool(0));
+        Ok(Scene {
+            name: scene_name(items)?,
+            root: scene_root(items)?,
+            entities_meta: scene_entities(items)?,
+            spawner,
+            physics_enabled: flag("physics")?,
+            render_enabled: flag("render")?,
+        })
+    }
+}
+
+fn scene_name(items: &[Item]) -> Result<String, String> {
+    items.iter().find(|i| i.key == "name").ok_or("Scene has no name")?.str(0).map(str::to_string)
+}
+
+pub(crate) fn scene_root(items: &[Item]) -> Result<Node, String> {
+    let mut roots = items.iter().filter(|i| i.key == "node");
+    match (roots.next(), roots.next()) {
+        (Some(root), None) => read_node(root),
+        (None, _) => Err("Scene has no root node".to_string()),
+        (Some(_), Some(_)) => Err("Scene has more than one root node".to_string()),
+    }
+}
+
+fn scene_entities(items: &[Item]) -> Result<Vec<Entity>, String> {
+    items.iter().filter(|i| i.key == "script").map(|item| {
+        let source = item.str(0)?;
+        let mut entities = parse_entities(source);
+        match (entities.pop(), entities.is_empty()) {
+            (Some(entity), true) => Ok(entity),
+            _ => Err(format!("'script' must define exactly one entity: {}", source.lines().next().unwrap_or(""))),
+        }
+    }).collect()
+}
+
+fn script_item(entity: &Entity) -> Item {
+    Item::new("script", vec![Arg::Str(entity.to_source().trim_end().to_string())])
+}
+
+// ---- Node ----
+
+pub(crate) fn node_item(node: &Node) -> Item {
+    let mut children = Vec::new();
+    if let Some(entity) = &node.entity {
+        children.push(Item::new("entity", vec![Arg::Str(entity.clone())]));
+    }
+    if let Some(owner) = &node.owner {
+        children.push(Item::new("owner", vec![Arg::Str(owner.clone())]));
+    }
+    if let Some(link) = &node.prefab {
+        let overrides = link.overrides.iter().map(|o| {
+            let mut args = vec![Arg::Str(o.path.clone()), Arg::Str(o.component.clone()), Arg::Str(o.property.clone())];
+            args.extend(value_args(&o.value));
+            Item::new("override", args)
+        }).collect();
+        children.push(Item::new("prefab", vec![Arg::Str(link.prefab.clone())]).with_children(overrides));
+    }
+    if !node.active {
+        children.push(Item::new("active", vec![Arg::Word("false".to_string())]));
+    }
+    if !node.groups.is_empty() {
+        children.push(Item::new("groups", node.groups.iter().map(|g| Arg::Str(g.clone())).collect()));
+    }
+    let t = node.transform();
+    if *t != Transform::default() {
+        let (p, r, s) = (t.position, t.rotation, t.scale);
+        let args = [p.x, p.y, p.z, r.x, r.y, r.z, r.w, s.x, s.y, s.z];
+        children.push(Item::new("transform", args.into_iter().map(Arg::Float32).collect()));
+    }
+    if let Some(i) = &node.instance {
+        children.push(Item::new("instance", vec![
+            Arg::Str(i.name.clone()),
+            Arg::Str(i.tag.clone()),
+            Arg::Int(i.health as i64),
+            Arg::Float(i.velocity),
+            Arg::Float(i.position),
+        ]));
+    }
+    let mut components: Vec<&Component> = node.components.values().collect();
+    components.sort_by(|a, b| a.name.cmp(&b.name));
+    for c in components {
+        let mut props: Vec<_> = c.properties.iter().collect();
+        props.sort_by(|a, b| a.0.cmp(b.0));
+        let props = props.into_iter().map(|(k, v)| Item::new(k, value_args(v))).collect();
+        children.push(Item::new("component", vec![Arg::Str(c.name.clone())]).with_children(props));
+    }
+    children.extend(node.children.iter().map(node_item));
+    Item::new("node", vec![Arg::Str(node.id.clone()), Arg::Str(node.name.clone()), Arg::Str(node.node_type.clone())])
+        .with_children(children)
+}
+
+fn read_node(item: &Item) -> Result<Node, String> {
+    let mut node = Node::new(item.str(0)?.to_string(), item.str(1)?.to_string(), item.str(2)?.to_string());
+    for child in &item.children {
+        match child.key.as_str() {
+            "entity" => node.entity = Some(child.str(0)?.to_string()),
+            "owner" => node.owner = Some(child.str(0)?.to_string()),
+            "prefab" => {
+                let mut link = PrefabInstance { prefab: child.str(0)?.to_string(), overrides: Vec::new() };
+                for o in &child.children {
+                    let value = Item::new(o.str(2)?, o.args.get(3..).unwrap_or_default().to_vec());
+                    link.overrides.push(PropertyOverride {
+                        path: o.str(0)?.to_string(),
+                        component: o.str(1)?.to_string(),
+                        property: o.str(2)?.to_string(),
+                        value: read_value(&value)?,
+                    });
+                }
+                node.prefab = Some(link);
+            }
+            "active" => node.active = child.bool(0)?,
+            "groups" => {
+                for i in 0..child.args.len() {
+                    node.add_to_group(child.str(i)?);
+                }
+            }
+            "transform" => {
+                let f = |i: usize| child.float(i).map(|v| v as f32);
+                node.set_transform(Transform {
+                    position: Vector3::new(f(0)?, f(1)?, f(2)?),
+                    rotation: Quaternion { x: f(3)?, y: f(4)?, z: f(5)?, w: f(6)? },
+                    scale: Vector3::new(f(7)?, f(8)?, f(9)?),
+                });
+            }
+            "instance" => {
+                let mut instance = EntityInstance::new(child.str(0)?, child.str(1)?);
+                instance.health = child.int(2)? as i32;
+                instance.velocity = child.float(3)?;
+                instance.position = child.float(4)?;
+                node.instance = Some(instance);
+            }
+            "component" => {
+                let mut component = Component::new(child.str(0)?.to_string());
+                for prop in &child.children {
+                    component.set_property(prop.key.clone(), read_value(prop)?);
+                }
+                node.add_component(component);
+            }
+            "node" => node.add_child(read_node(child)?),
+            other => return Err(format!("Node '{}': unknown field '{}'", node.id, other)),
+        }
+    }
+    Ok(node)
+}
+
+fn value_args(value: &Value) -> Vec<Arg> {
+    match value {
+        Value::Float(f) => vec![Arg::Float(*f)],
+        Value::Int(i) => vec![Arg::Int(*i)],
+        Value::Str(s) => vec![Arg::Str(s.clone())],
+        Value::EntitySnapshot(e) => vec![
+            Arg::Word("snapshot".to_string()),
+            Arg::Str(e.name.clone()),
+            Arg::Str(e.tag.clone()),
+            Arg::Int(e.health as i64),
+        ],
+    }
+}
+
+fn read_value(item: &Item) -> Result<Value, String> {
+    match item.args.as_slice() {
+        [Arg::Float(f)] => Ok(Value::Float(*f)),
+        [Arg::Float32(f)] => Ok(Value::Float(*f as f64)),
+        [Arg::Int(i)] => Ok(Value::Int(*i)),
+        [Arg::Str(s)] => Ok(Value::Str(s.clone())),
+        [Arg::Word(w), _, _, _] if w == "snapshot" => Ok(Value::EntitySnapshot(SimpleEntity {
+            name: item.str(1)?.to_string(),
+            tag: item.str(2)?.to_string(),
+            health: item.int(3)? as i32,
+        })),
+        other => Err(format!("Property '{}' has no value of a known type: {:?}", item.key, other)),
+    }
+}
+
+// ---- Миграции ----
+
+/// v1 хранил положение только в свойствах компонента `Transform` (`position`, `y`, `z`);
+/// v2 добавил типизированный `transform` ноды с поворотом и масштабом
+fn migrate_v1_typed_transform(items: &mut [Item]) {
+    for item in items.iter_mut().filter(|i| i.key == "node") {
+        migrate_v1_typed_transform(&mut item.children);
+        if item.child("transform").is_some() {
+            continue;
+        }
+        let Some(component) = item.children.iter().find(|c| c.key == "component" && c.str(0) == Ok("Transform")) else {
+            continue;
+        };
+        let coord = |key: &str| component.child(key).and_then(|p| p.float(0).ok()).unwrap_or(0.0) as f32;
+        let position = [coord("position"), coord("y"), coord("z")];
+        let args = position.into_iter().chain([0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]).map(Arg::Float32).collect();
+        item.children.push(Item::new("transform", args));
+    }
+}
+
+/// v3 добавил `owner` и `prefab` нод; в документах v2 их нет, менять нечего
+fn migrate_v2_prefabs(_items: &mut [Item]) {}
+
+// ---- Текстовый формат ----
+
+/// Текст документа: `version N`, затем по записи на строку, дети в `{ }` с отступом.
+/// Многострочные строки пишутся как `"""` без экранирования
+pub fn write_text(doc: &Document) -> String {
+    let mut out = format!("version {}\n", doc.version);
+    for item in &doc.items {
+        write_item(&mut out, item, 0);
+    }
+    out
+}
+
+fn write_item(out: &mut String, item: &Item, depth: usize) {
+    let indent = "    ".repeat(depth);
+    out.push_str(&indent);
+    out.push_str(&text_key(&item.key));
+    for arg in &item.args {
+        out.push(' ');
+        match arg {
+            Arg::Word(w) => out.push_str(w),
+            Arg::Str(s) if s.contains('\n') && !s.contains("\"\"\"") => {
+                out.push_str("\"\"\"\n");
+                out.push_str(s);
+                out.push_str("\n\"\"\"");
+            }
+            Arg::Str(s) => out.push_str(&quote(s)),
+            Arg::Int(i) => out.push_str(&i.to_string()),
+            Arg::Float(f) => out.push_str(&format!("{:?}", f)),
+            Arg::Float32(f) => out.push_str(&format!("{:?}", f)),
+        }
+    }
+    if item.children.is_empty() {
+        out.push('\n');
+        return;
+    }
+    out.push_str(" {\n");
+    for child in &item.children {
+        write_item(out, child, depth + 1);
+    }
+    out.push_str(&indent);
+    out.push_str("}\n");
+}
+
+fn text_key(key: &str) -> String {
+    let bare = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    if bare { key.to_string() } else { quote(key) }
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Arg(Arg),
+    Open,
+    Close,
+    Newline,
+}
+
+pub fn parse_text(text: &str) -> Result<Document, String> {
+    let tokens = tokenize(text)?;
+    let mut pos = 0;
+    let mut items = parse_items(&tokens, &mut pos, false)?;
+    if items.first().map(|i| i.key.as_str()) != Some("version") {
+        return Err("Scene file must start with `version <n>`".to_string());
+    }
+    let header = items.remove(0);
+    let version = header.int(0).ok().and_then(|v| u32::try_from(v).ok())
+        .ok_or_else(|| "`version` must be a positive integer".to_string())?;
+    Ok(Document { version, items })
+}
+
+fn parse_items(tokens: &[(Token, usize)], pos: &mut usize, nested: bool) -> Result<Vec<Item>, String> {
+    let mut items = Vec::new();
+    loop {
+        let Some((token, line)) = tokens.get(*pos) else {
+            return if nested { Err("unexpected end of file: missing `}`".to_string()) } else { Ok(items) };
+        };
+        *pos += 1;
+        let key = match token {
+            Token::Newline => continue,
+            Token::Close if nested => return Ok(items),
+            Token::Arg(Arg::Word(k)) | Token::Arg(Arg::Str(k)) => k.clone(),
+            other => return Err(format!("line {}: expected a key, got {:?}", line, other)),
+        };
+        let mut item = Item::new(&key, Vec::new());
+        while let Some((Token::Arg(arg), _)) = tokens.get(*pos) {
+            item.args.push(arg.clone());
+            *pos += 1;
+        }
+        if let Some((Token::Open, _)) = tokens.get(*pos) {
+            *pos += 1;
+            item.children = parse_items(tokens, pos, true)?;
+        }
+        items.push(item);
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut line = 1;
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\n' => {
+                tokens.push((Token::Newline, line));
+                line += 1;
+            }
+            c if c.is_whitespace() => {}
+            '#' => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            }
+            '{' => tokens.push((Token::Open, line)),
+            '}' => tokens.push((Token::Close, line)),
+            '"' if text[start..].starts_with("\"\"\"") => {
+                let body_start = start + 3;
+                let len = text[body_start..].find("\"\"\"").ok_or(format!("line {}: unterminated \"\"\"", line))?;
+                let raw = &text[body_start..body_start + len];
+                let raw = raw.strip_prefix('\n').unwrap_or(raw);
+                let raw = raw.strip_suffix('\n').unwrap_or(raw);
+                tokens.push((Token::Arg(Arg::Str(raw.to_string())), line));
+                line += text[body_start..body_start + len].matches('\n').count();
+                while chars.next_if(|&(i, _)| i < body_start + len + 3).is_some() {}
+            }
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, 'r')) => s.push('\r'),
+                            Some((_, 't')) => s.push('\t'),
+                            Some((_, c)) => s.push(c),
+                            None => return Err(format!("line {}: unterminated string", line)),
+                        },
+                        Some((_, '\n')) | None => return Err(format!("line {}: unterminated string", line)),
+                        Some((_, c)) => s.push(c),
+                    }
+                }
+                tokens.push((Token::Arg(Arg::Str(s)), line));
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.next_if(|&(_, c)| !c.is_whitespace() && !matches!(c, '{' | '}' | '"' | '#')) {
+                    end = i + c.len_utf8();
+                }
+                let word = &text[start..end];
+                let arg = if let Ok(i) = word.parse::<i64>() {
+                    Arg::Int(i)
+                } else if let Ok(f) = word.parse::<f64>() {
+                    Arg::Float(f)
+                } else {
+                    Arg::Word(word.to_string())
+                };
+                tokens.push((Token::Arg(arg), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// ---- Бинарный формат ----
+
+/// `MSCN`, версия, таблица строк (ключи и строковые аргументы), затем записи.
+/// Числа — LEB128, знаковые через zigzag
+pub fn write_binary(doc: &Document) -> Vec<u8> {
+    let mut strings: Vec<&str> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    collect_strings(&doc.items, &mut strings, &mut index);
+
+    let mut out = BINARY_MAGIC.to_vec();
+    put_varint(&mut out, doc.version as u64);
+    put_varint(&mut out, strings.len() as u64);
+    for s in &strings {
+        put_varint(&mut out, s.len() as u64);
+        out.extend_from_slice(s.as_bytes());
+    }
+    put_items(&mut out, &doc.items, &index);
+    out
+}
+
+fn collect_strings<'a>(items: &'a [Item], strings: &mut Vec<&'a str>, index: &mut HashMap<&'a str, usize>) {
+    let mut add = |s: &'a str| {
+        index.entry(s).or_insert_with(|| {
+            strings.push(s);
+            strings.len() - 1
+        });
+    };
+    for item in items {
+        add(&item.key);
+        for arg in &item.args {
+            if let Arg::Word(s) | Arg::Str(s) = arg {
+                add(s);
+            }
+        }
+    }
+    for item in items {
+        collect_strings(&item.children, strings, index);
+    }
+}
+
+fn put_items(out: &mut Vec<u8>, items: &[Item], index: &HashMap<&str, usize>) {
+    put_varint(out, items.len() as u64);
+    for item in items {
+        put_varint(out, index[item.key.as_str()] as u64);
+        put_varint(out, item.args.len() as u64);
+        for arg in &item.args {
+            match arg {
+                Arg::Word(s) => { out.push(0); put_varint(out, index[s.as_str()] as u64); }
+                Arg::Str(s) => { out.push(1); put_varint(out, index[s.as_str()] as u64); }
+                Arg::Int(i) => { out.push(2); put_varint(out, ((i << 1) ^ (i >> 63)) as u64); }
+                Arg::Float(f) => { out.push(3); out.extend_from_slice(&f.to_le_bytes()); }
+                Arg::Float32(f) => { out.push(4); out.extend_from_slice(&f.to_le_bytes()); }
+            }
+        }
+        put_items(out, &item.children, index);
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.bytes.len())
+            .ok_or_else(|| format!("truncated scene at byte {}", self.pos))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.take(1)?[0];
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(format!("varint too long at byte {}", self.pos))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        let n = self.varint()? as usize;
+        // Каждому элементу нужен хотя бы байт: защита от огромных длин в битых файлах
+        if n > self.bytes.len() - self.pos {
+            return Err(format!("length {} exceeds the file at byte {}", n, self.pos));
+        }
+        Ok(n)
+    }
+
+    fn string(&mut self, strings: &[String]) -> Result<String, String> {
+        let i = self.varint()? as usize;
+        strings.get(i).cloned().ok_or_else(|| format!("string index {} out of range", i))
+    }
+
+    fn items(&mut self, strings: &[String]) -> Result<Vec<Item>, String> {
+        let count = self.len()?;
+        let mut items = Vec::with_capacity(count);
+        for _ in 0..count {
+            let key = self.string(strings)?;
+            let argc = self.len()?;
+            let mut args = Vec::with_capacity(argc);
+            for _ in 0..argc {
+                args.push(match self.take(1)?[0] {
+                    0 => Arg::Word(self.string(strings)?),
+                    1 => Arg::Str(self.string(strings)?),
+                    2 => { let v = self.varint()?; Arg::Int((v >> 1) as i64 ^ -((v & 1) as i64)) }
+                    3 => Arg::Float(f64::from_le_bytes(self.take(8)?.try_into().expect("8 bytes"))),
+                    4 => Arg::Float32(f32::from_le_bytes(self.take(4)?.try_into().expect("4 bytes"))),
+                    tag => return Err(format!("unknown argument tag {} at byte {}", tag, self.pos - 1)),
+                });
+            }
+            let children = self.items(strings)?;
+            items.push(Item { key, args, children });
+        }
+        Ok(items)
+    }
+}
+
+pub fn parse_binary(bytes: &[u8]) -> Result<Document, String> {
+    let mut r = Reader { bytes, pos: 0 };
+    if r.take(4).ok() != Some(BINARY_MAGIC.as_slice()) {
+        return Err("not a binary scene file".to_string());
+    }
+    let version = u32::try_from(r.varint()?).map_err(|_| "scene format version is out of range".to_string())?;
+    let count = r.len()?;
+    let mut strings = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len = r.len()?;
+        let s = std::str::from_utf8(r.take(len)?).map_err(|e| e.to_string())?;
+        strings.push(s.to_string());
+    }
+    let items = r.items(&strings)?;
+    if r.pos != bytes.len() {
+        return Err(format!("trailing bytes after scene at byte {}", r.pos));
+    }
+    Ok(Document { version, items })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_and_binary_encode_the_same_document() {
+        let doc = Document {
+            version: SCENE_FORMAT_VERSION,
+            items: vec![
+                Item::new("script", vec![Arg::Str("entity A {\n    on Ready() { }\n}\n".to_string())]),
+                Item::new("node", vec![Arg::Str("a \"b\"".to_string()), Arg::Int(-3), Arg::Float(2.5)])
+                    .with_children(vec![Item::new("weird key", vec![Arg::Float32(0.1), Arg::Word("true".to_string())])]),
+            ],
+        };
+        let text = write_text(&doc);
+        assert!(text.contains("\"\"\"\nentity A {"), "{}", text);
+        assert_eq!(parse_text(&text).unwrap().items[0], doc.items[0]);
+        assert_eq!(parse_binary(&write_binary(&doc)).unwrap(), doc);
+        // text reads f32 back as a plain number with the same value
+        assert_eq!(parse_text(&text).unwrap().items[1].children[0].float(0).unwrap() as f32, 0.1);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert!(parse_text("node \"a\" {").unwrap_err().contains("missing `}`"));
+        assert!(parse_text("name \"x\"").unwrap_err().contains("version"));
+        assert!(parse_text("version 99\n").unwrap().migrate().unwrap_err().contains("not supported"));
+        let bytes = write_binary(&Document { version: 2, items: vec![Item::new("k", vec![Arg::Int(1)])] });
+        assert!(parse_binary(&bytes[..bytes.len() - 1]).is_err());
+        assert!(parse_binary(b"MSCN\x02\xff\xff\xff\x7f").is_err());
+        assert!(parse_binary(b"MSCN\x83\x80\x80\x80\x10\x00\x00").unwrap_err().contains("out of range"));
+    }
+}
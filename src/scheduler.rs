@@ -0,0 +1,421 @@
+use std::any::TypeId;
+use std::panic::{AssertUnwindSafe, catch_unwind, resume_unwind};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::ecs::{Component, Query, SharedQuery, World};
+
+/// Стадии кадра, выполняются строго по порядку
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    Input,
+    Script,
+    Physics,
+    RenderPrep,
+}
+
+/// Как выполнять системы одной стадии
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// Непересекающиеся по доступу системы выполняются на пуле потоков
+    Parallel { threads: usize },
+    /// Все системы в одном потоке в порядке расписания (для воспроизводимых прогонов)
+    SingleThreaded,
+}
+
+impl Default for ExecutionMode {
+    fn default() -> Self {
+        let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        ExecutionMode::Parallel { threads }
+    }
+}
+
+type SystemFn = Box<dyn FnMut(&SystemContext) + Send>;
+
+/// Система: функция над миром с объявленными чтениями/записями компонентов
+pub struct System {
+    pub name: String,
+    pub stage: Stage,
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+    before: Vec<String>,
+    after: Vec<String>,
+    run: Mutex<SystemFn>,
+}
+
+impl System {
+    pub fn new(name: &str, run: impl FnMut(&SystemContext) + Send + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            stage: Stage::Script,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            run: Mutex::new(Box::new(run)),
+        }
+    }
+
+    pub fn stage(mut self, stage: Stage) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    pub fn reads<T: Component>(mut self) -> Self {
+        self.reads.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn writes<T: Component>(mut self) -> Self {
+        self.writes.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Выполнять раньше системы `other`
+    pub fn before(mut self, other: &str) -> Self {
+        self.before.push(other.to_string());
+        self
+    }
+
+    /// Выполнять позже системы `other`
+    pub fn after(mut self, other: &str) -> Self {
+        self.after.push(other.to_string());
+        self
+    }
+
+    fn conflicts(&self, other: &System) -> bool {
+        self.writes.iter().any(|t| other.writes.contains(t) || other.reads.contains(t))
+            || other.writes.iter().any(|t| self.reads.contains(t))
+    }
+}
+
+/// Доступ системы к миру: только объявленные компоненты
+pub struct SystemContext<'w> {
+    world: &'w World,
+    system: &'w System,
+    pub delta_time: f64,
+}
+
+impl<'w> SystemContext<'w> {
+    pub fn system(&self) -> &str {
+        &self.system.name
+    }
+
+    /// Запрос к миру: `for h in ctx.query::<&mut Health>().iter() { ... }`. Колонки заняты,
+    /// пока жив результат, так что запросы одной системы идут друг за другом без конфликтов.
+    /// Паника, если запрос трогает компоненты, не объявленные в `reads`/`writes`
+    pub fn query<Q: Query>(&self) -> SharedQuery<'_, Q> {
+        let mut access = Vec::new();
+        Q::access(&mut access);
+        for (t, write) in access {
+            let declared = self.system.writes.contains(&t) || (!write && self.system.reads.contains(&t));
+            assert!(declared, "system '{}' queries {:?} without declaring {} access", self.system.name, t, if write { "write" } else { "read" });
+        }
+        self.world.query_shared()
+    }
+}
+
+/// Расписание систем по стадиям с учётом before/after и конфликтов доступа
+#[derive(Default)]
+pub struct Scheduler {
+    systems: Vec<System>,
+    pub mode: ExecutionMode,
+    /// Пачки индексов систем по стадиям; пересчитываются после `add_system`
+    plan: Option<Vec<Vec<usize>>>,
+    /// Потоки параллельного режима: создаются при первом `run` и живут, пока жив планировщик
+    pool: Option<WorkerPool>,
+}
+
+impl Scheduler {
+    pub fn new(mode: ExecutionMode) -> Self {
+        Self { systems: Vec::new(), mode, plan: None, pool: None }
+    }
+
+    pub fn add_system(&mut self, system: System) {
+        self.systems.push(system);
+        self.plan = None;
+    }
+
+    /// Порядок выполнения: пачки систем, идущие друг за другом. Системы одной пачки
+    /// не конфликтуют по доступу и могут выполняться параллельно; конфликтующие системы
+    /// идут в порядке расписания, так что результат совпадает с однопоточным режимом
+    pub fn plan(&mut self) -> Result<&[Vec<usize>], String> {
+        if self.plan.is_none() {
+            self.plan = Some(self.build_plan()?);
+        }
+        Ok(self.plan.as_deref().unwrap_or_default())
+    }
+
+    /// Имена систем в порядке однопоточного выполнения
+    pub fn order(&mut self) -> Result<Vec<String>, String> {
+        let plan = self.plan()?.to_vec();
+        Ok(plan.into_iter().flatten().map(|i| self.systems[i].name.clone()).collect())
+    }
+
+    /// Выполняет один кадр всех систем
+    pub fn run(&mut self, world: &mut World, delta_time: f64) -> Result<(), String> {
+        let plan = self.plan()?.to_vec();
+        if let ExecutionMode::Parallel { threads } = self.mode {
+            if threads > 1 && self.pool.as_ref().map(|p| p.threads()) != Some(threads) {
+                self.pool = Some(WorkerPool::new(threads));
+            }
+        }
+        let world: &World = world;
+        let systems = &self.systems;
+        for batch in &plan {
+            match (self.mode, &self.pool) {
+                (ExecutionMode::Parallel { threads }, Some(pool)) if threads > 1 && batch.len() > 1 => {
+                    let next = AtomicUsize::new(0);
+                    pool.run_on(threads.min(batch.len()), &|| {
+                        while let Some(&i) = batch.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            Self::run_system(&systems[i], world, delta_time);
+                        }
+                    });
+                }
+                _ => {
+                    for &i in batch {
+                        Self::run_system(&systems[i], world, delta_time);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn run_system(system: &System, world: &World, delta_time: f64) {
+        let ctx = SystemContext { world, system, delta_time };
+        let mut run = system.run.lock().expect("system panicked earlier");
+        run(&ctx);
+    }
+
+    fn build_plan(&self) -> Result<Vec<Vec<usize>>, String> {
+        let n = self.systems.len();
+        let index = |name: &str| self.systems.iter().position(|s| s.name == name);
+        for (i, s) in self.systems.iter().enumerate() {
+            if self.systems[..i].iter().any(|o| o.name == s.name) {
+                return Err(format!("System '{}' is added twice", s.name));
+            }
+        }
+
+        // Рёбра «a раньше b»
+        let mut preds: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for (i, s) in self.systems.iter().enumerate() {
+            for other in &s.after {
+                let j = index(other).ok_or_else(|| format!("System '{}' runs after unknown system '{}'", s.name, other))?;
+                preds[i].push(j);
+            }
+            for other in &s.before {
+                let j = index(other).ok_or_else(|| format!("System '{}' runs before unknown system '{}'", s.name, other))?;
+                preds[j].push(i);
+            }
+        }
+        for (i, p) in preds.iter().enumerate() {
+            for &j in p {
+                if self.systems[j].stage > self.systems[i].stage {
+                    return Err(format!(
+                        "System '{}' ({:?}) cannot run after '{}' ({:?}) from a later stage",
+                        self.systems[i].name, self.systems[i].stage, self.systems[j].name, self.systems[j].stage
+                    ));
+                }
+            }
+        }
+
+        // Топологический порядок: стадия, затем зависимости, затем порядок добавления
+        let mut order = Vec::with_capacity(n);
+        let mut done = vec![false; n];
+        while order.len() < n {
+            let next = (0..n)
+                .filter(|&i| !done[i] && preds[i].iter().all(|&j| done[j]))
+                .min_by_key(|&i| (self.systems[i].stage, i));
+            let Some(i) = next else {
+                let stuck: Vec<&str> = (0..n).filter(|&i| !done[i]).map(|i| self.systems[i].name.as_str()).collect();
+                return Err(format!("Ordering cycle between systems: {}", stuck.join(", ")));
+            };
+            done[i] = true;
+            order.push(i);
+        }
+
+        // Уровень системы — после её зависимостей и после всех раньше идущих конфликтующих систем
+        let mut level = vec![0usize; n];
+        let mut batches: Vec<Vec<usize>> = Vec::new();
+        let mut stage_start = 0;
+        for (pos, &i) in order.iter().enumerate() {
+            if pos > 0 && self.systems[order[pos - 1]].stage != self.systems[i].stage {
+                stage_start = batches.len();
+            }
+            let mut l = stage_start;
+            for &j in &order[..pos] {
+                if preds[i].contains(&j) || self.systems[i].conflicts(&self.systems[j]) {
+                    l = l.max(level[j] + 1);
+                }
+            }
+            level[i] = l;
+            if batches.len() <= l {
+                batches.resize_with(l + 1, Vec::new);
+            }
+            batches[l].push(i);
+        }
+        Ok(batches)
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Постоянные рабочие потоки: пачки систем раздаются им без создания потоков в каждом кадре
+struct WorkerPool {
+    jobs: Option<Sender<Job>>,
+    done: Receiver<thread::Result<()>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    fn new(threads: usize) -> Self {
+        let (jobs, queue) = channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let (report, done) = channel();
+        let workers = (0..threads).map(|_| {
+            let (queue, report) = (Arc::clone(&queue), report.clone());
+            thread::spawn(move || loop {
+                let job = match queue.lock() {
+                    Ok(queue) => queue.recv(),
+                    Err(_) => return,
+                };
+                let Ok(job) = job else { return };
+                if report.send(catch_unwind(AssertUnwindSafe(job))).is_err() {
+                    return;
+                }
+            })
+        }).collect();
+        Self { jobs: Some(jobs), done, workers }
+    }
+
+    fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Выполняет `job` на `copies` потоках и ждёт, пока все копии закончатся; паника
+    /// в задаче продолжается в вызывающем потоке
+    fn run_on<'a>(&self, copies: usize, job: &'a (dyn Fn() + Sync + 'a)) {
+        // SAFETY: ссылка не переживает вызов: ниже дожидаемся всех `copies` отчётов, а если
+        // канал закрыт, все рабочие потоки уже завершились и задач не выполняют
+        let job: &'static (dyn Fn() + Sync) = unsafe { std::mem::transmute(job) };
+        let jobs = self.jobs.as_ref().expect("pool is running");
+        let sent = (0..copies).take_while(|_| jobs.send(Box::new(job)).is_ok()).count();
+        let mut panic = None;
+        for _ in 0..sent {
+            match self.done.recv() {
+                Ok(Ok(())) => {}
+                Ok(Err(payload)) => { panic.get_or_insert(payload); }
+                Err(_) => break,
+            }
+        }
+        if let Some(payload) = panic {
+            resume_unwind(payload);
+        }
+        assert_eq!(sent, copies, "scheduler worker threads have stopped");
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // Закрытый канал задач завершает циклы рабочих потоков
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Health, Transform, Vector3, Velocity};
+
+    fn schedule(mode: ExecutionMode) -> Scheduler {
+        let mut s = Scheduler::new(mode);
+        s.add_system(System::new("integrate", |ctx| {
+            for (t, v) in ctx.query::<(&mut Transform, &Velocity)>().iter() {
+                t.position.x += v.value.x * ctx.delta_time as f32;
+            }
+        }).stage(Stage::Physics).writes::<Transform>().reads::<Velocity>());
+        s.add_system(System::new("steer", |ctx| {
+            for v in ctx.query::<&mut Velocity>().iter() { v.value.x += 1.0; }
+        }).writes::<Velocity>());
+        s.add_system(System::new("poison", |ctx| {
+            for h in ctx.query::<&mut Health>().iter() { h.take_damage(2.0); }
+        }).writes::<Health>());
+        s.add_system(System::new("regen", |ctx| {
+            for h in ctx.query::<&mut Health>().iter() { h.heal(1.0); }
+        }).writes::<Health>().after("steer"));
+        s.add_system(System::new("read_input", |_| {}).stage(Stage::Input).before("steer"));
+        s
+    }
+
+    #[test]
+    fn stages_dependencies_and_conflicts_shape_the_plan() {
+        let mut s = schedule(ExecutionMode::SingleThreaded);
+        assert_eq!(s.order().unwrap(), ["read_input", "steer", "poison", "regen", "integrate"]);
+        // steer and poison share a batch; regen follows steer explicitly and poison by conflict
+        assert_eq!(s.plan().unwrap(), &[vec![4], vec![1, 2], vec![3], vec![0]]);
+
+        let mut bad = Scheduler::new(ExecutionMode::SingleThreaded);
+        bad.add_system(System::new("a", |_| {}).stage(Stage::Input).after("b"));
+        bad.add_system(System::new("b", |_| {}).stage(Stage::Physics));
+        assert!(bad.plan().unwrap_err().contains("later stage"));
+    }
+
+    #[test]
+    fn parallel_and_single_threaded_runs_agree() {
+        let run = |mode| {
+            let mut world = World::new();
+            for i in 0..64 {
+                world.spawn((Transform::new(), Velocity { value: Vector3::new(i as f32, 0.0, 0.0) }, Health::new(10.0)));
+            }
+            let mut s = schedule(mode);
+            for _ in 0..3 { s.run(&mut world, 0.5).unwrap(); }
+            world.query::<(&Transform, &Health)>().map(|(t, h)| t.position.x + h.current).sum::<f32>()
+        };
+        assert_eq!(run(ExecutionMode::Parallel { threads: 4 }), run(ExecutionMode::SingleThreaded));
+    }
+
+    #[test]
+    fn two_queries_in_one_system_run_one_after_another() {
+        let mut s = Scheduler::new(ExecutionMode::SingleThreaded);
+        s.add_system(System::new("regen_and_count", |ctx| {
+            for h in ctx.query::<&mut Health>().iter() { h.heal(1.0); }
+            assert_eq!(ctx.query::<&Health>().iter().count(), 2);
+        }).writes::<Health>());
+        let mut world = World::new();
+        world.spawn((Health::new(10.0),));
+        world.spawn((Health::new(10.0), Velocity { value: Vector3::zero() }));
+        s.run(&mut world, 0.0).unwrap();
+    }
+
+    #[test]
+    fn parallel_batches_reuse_the_same_worker_threads() {
+        use std::collections::HashSet;
+        let seen = Arc::new(Mutex::new(HashSet::new()));
+        let mut s = Scheduler::new(ExecutionMode::Parallel { threads: 2 });
+        for name in ["poison", "steer"] {
+            let seen = Arc::clone(&seen);
+            let system = System::new(name, move |_| { seen.lock().unwrap().insert(thread::current().id()); });
+            s.add_system(if name == "poison" { system.writes::<Health>() } else { system.writes::<Velocity>() });
+        }
+        assert_eq!(s.plan().unwrap(), &[vec![0, 1]]);
+        let mut world = World::new();
+        for _ in 0..10 { s.run(&mut world, 0.0).unwrap(); }
+        assert!(seen.lock().unwrap().len() <= 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "without declaring")]
+    fn undeclared_access_is_rejected() {
+        let mut s = Scheduler::new(ExecutionMode::SingleThreaded);
+        s.add_system(System::new("sneaky", |ctx| { let _ = ctx.query::<&mut Transform>().iter().count(); }).reads::<Transform>());
+        let mut world = World::new();
+        world.spawn((Transform::new(),));
+        s.run(&mut world, 0.0).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::components::{Health, Input, Physics, Sprite, Transform, Vector3, Velocity};
+use crate::game_engine::{Component, Node};
+use crate::meta_lang::Entity;
+use crate::runtime::{EntityInstance, Value};
+
+/// Компонент из `components: [...]` сущности, сопоставленный типизированной структуре
+#[derive(Debug, Clone)]
+pub enum TypedComponent {
+    Transform(Transform),
+    Velocity(Velocity),
+    Health(Health),
+    Sprite(Sprite),
+    Physics(Physics),
+    Input(Input),
+}
+
+impl TypedComponent {
+    /// Компонент по имени со значениями по умолчанию; `None` для неизвестных движку имён
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "Transform" => TypedComponent::Transform(Transform::default()),
+            "Velocity" => TypedComponent::Velocity(Velocity::default()),
+            "Health" => TypedComponent::Health(Health::default()),
+            "Sprite" => TypedComponent::Sprite(Sprite::default()),
+            "Physics" => TypedComponent::Physics(Physics::default()),
+            "Input" => TypedComponent::Input(Input::default()),
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TypedComponent::Transform(_) => "Transform",
+            TypedComponent::Velocity(_) => "Velocity",
+            TypedComponent::Health(_) => "Health",
+            TypedComponent::Sprite(_) => "Sprite",
+            TypedComponent::Physics(_) => "Physics",
+            TypedComponent::Input(_) => "Input",
+        }
+    }
+
+    /// Обратное к `to_component`: читает типизированный компонент из свойств ноды,
+    /// отсутствующие свойства берутся по умолчанию
+    pub fn from_component(component: &Component) -> Option<Self> {
+        let num = |k: &str| match component.get_property(k) {
+            Some(Value::Float(f)) => Some(*f as f32),
+            Some(Value::Int(i)) => Some(*i as f32),
+            _ => None,
+        };
+        let mut typed = Self::from_name(&component.name)?;
+        match &mut typed {
+            TypedComponent::Transform(t) => {
+                t.position = Vector3::new(
+                    num("position").unwrap_or(t.position.x),
+                    num("y").unwrap_or(t.position.y),
+                    num("z").unwrap_or(t.position.z),
+                );
+            }
+            TypedComponent::Velocity(v) => v.value.x = num("value").unwrap_or(v.value.x),
+            TypedComponent::Health(h) => {
+                h.max = num("max").unwrap_or(h.max);
+                h.current = num("hp").unwrap_or(h.current);
+            }
+            TypedComponent::Sprite(s) => {
+                if let Some(Value::Str(path)) = component.get_property("texture") { s.texture_path = path.clone(); }
+                s.visible = num("visible").map(|v| v != 0.0).unwrap_or(s.visible);
+            }
+            TypedComponent::Physics(p) => {
+                p.velocity.x = num("velocity").unwrap_or(p.velocity.x);
+                p.mass = num("mass").unwrap_or(p.mass);
+                p.gravity = num("gravity").unwrap_or(p.gravity);
+            }
+            TypedComponent::Input(_) => {}
+        }
+        Some(typed)
+    }
+
+    /// Свойства ноды в тех же ключах, что читает и пишет `GameEngine::update`
+    pub fn to_component(&self) -> Component {
+        let mut c = Component::new(self.name().to_string());
+        let mut set = |k: &str, v: Value| c.set_property(k.to_string(), v);
+        match self {
+            TypedComponent::Transform(t) => {
+                set("position", Value::Float(t.position.x as f64));
+                set("y", Value::Float(t.position.y as f64));
+                set("z", Value::Float(t.position.z as f64));
+            }
+            TypedComponent::Velocity(v) => set("value", Value::Float(v.value.x as f64)),
+            TypedComponent::Health(h) => {
+                set("hp", Value::Int(h.current as i64));
+                set("max", Value::Int(h.max as i64));
+            }
+            TypedComponent::Sprite(s) => {
+                set("texture", Value::Str(s.texture_path.clone()));
+                set("visible", Value::Int(s.visible as i64));
+            }
+            TypedComponent::Physics(p) => {
+                set("velocity", Value::Float(p.velocity.x as f64));
+                set("mass", Value::Float(p.mass as f64));
+                set("gravity", Value::Float(p.gravity as f64));
+            }
+            TypedComponent::Input(_) => {}
+        }
+        c
+    }
+}
+
+/// Создаёт ноду для сущности метаязыка: компоненты из `components: [...]`,
+/// связь с сущностью и `EntityInstance` с начальными значениями компонентов
+pub fn node_from_entity(entity: &Entity, id: String, at: Vector3) -> Node {
+    let typed: Vec<TypedComponent> = entity.components.iter().filter_map(|c| TypedComponent::from_name(c)).collect();
+    let node_type = if typed.iter().any(|c| matches!(c, TypedComponent::Physics(_))) {
+        "CharacterBody2D"
+    } else if typed.iter().any(|c| matches!(c, TypedComponent::Sprite(_))) {
+        "Sprite2D"
+    } else {
+        "Node"
+    };
+    let mut node = Node::new(id, entity.name.clone(), node_type.to_string()).with_entity(&entity.name);
+
+    let mut instance = EntityInstance::new(&entity.name, &entity.name.to_lowercase());
+    instance.position = at.x as f64;
+    for name in &entity.components {
+        let component = match TypedComponent::from_name(name) {
+            Some(mut typed) => {
+                match &mut typed {
+                    TypedComponent::Transform(t) => {
+                        t.position = at;
+                        node.set_transform(*t);
+                    }
+                    TypedComponent::Health(h) => instance.health = h.current as i32,
+                    TypedComponent::Physics(p) => instance.velocity = p.velocity.x as f64,
+                    _ => {}
+                }
+                typed.to_component()
+            }
+            // Компоненты, о которых движок не знает (AI, Vision...), сохраняются без свойств
+            None => Component::new(name.clone()),
+        };
+        node.add_component(component);
+    }
+    node.instance = Some(instance);
+    node
+}
+
+/// Выдаёт ноды сущностей с уникальными ID вида `enemy_1`, `enemy_2`
+#[derive(Debug, Clone, Default)]
+pub struct Spawner {
+    counters: HashMap<String, usize>,
+}
+
+impl Spawner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Сколько нод сущности уже выдано, по возрастанию имени
+    pub fn counters(&self) -> Vec<(&str, usize)> {
+        let mut counters: Vec<_> = self.counters.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        counters.sort();
+        counters
+    }
+
+    /// Продолжить нумерацию сущности с `count + 1` (например, после загрузки сцены)
+    pub fn set_counter(&mut self, entity: &str, count: usize) {
+        self.counters.insert(entity.to_string(), count);
+    }
+
+    /// ID, который получит следующая нода `name` (сущности или префаба)
+    pub fn next_id(&self, name: &str) -> String {
+        format!("{}_{}", name.to_lowercase(), self.counters.get(name).copied().unwrap_or(0) + 1)
+    }
+
+    /// Занимает `next_id(name)`
+    pub fn take_id(&mut self, name: &str) -> String {
+        let id = self.next_id(name);
+        *self.counters.entry(name.to_string()).or_default() += 1;
+        id
+    }
+
+    pub fn spawn(&mut self, entity: &Entity, at: Vector3) -> Node {
+        let id = self.take_id(&entity.name);
+        node_from_entity(entity, id, at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta_lang::parse_entities;
+
+    #[test]
+    fn declared_components_become_typed_properties() {
+        let entities = parse_entities("entity Enemy { components: [Transform, Health, AI]; on Update(dt) { patrol(); } }");
+        let mut spawner = Spawner::new();
+        let first = spawner.spawn(&entities[0], Vector3::new(3.0, 1.0, 0.0));
+        let second = spawner.spawn(&entities[0], Vector3::zero());
+        assert_eq!((first.id.as_str(), second.id.as_str()), ("enemy_1", "enemy_2"));
+        assert_eq!(first.entity.as_deref(), Some("Enemy"));
+        assert!(matches!(first.get_component("Transform").unwrap().get_property("position"), Some(Value::Float(x)) if *x == 3.0));
+        assert!(matches!(first.get_component("Health").unwrap().get_property("hp"), Some(Value::Int(100))));
+        assert!(first.get_component("AI").unwrap().properties.is_empty());
+        let instance = first.instance.unwrap();
+        assert_eq!((instance.tag.as_str(), instance.position), ("enemy", 3.0));
+    }
+}
@@ -1,135 +1,679 @@
-use std::collections::HashMap;
-use std::fs::{create_dir_all, read_to_string, write, read_dir};
-use crate::meta_lang::parse_entities;
-use crate::ast::{parse_statements, stmt_to_string, fold_constants, inline_helpers, dce};
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, remove_file, write, read_dir};
+use std::path::Path;
+use crate::meta_lang::{Entity, parse_entities, parse_functions};
+use crate::ast::{Expr, Rewrite, Stmt, expr_to_string, parse_statements, stmt_to_string, fold_constants, dce};
+use crate::driving::{FunctionDef, KnownEnv, Residual, Supercompiler, static_cost, supercompile};
+use crate::cost_model::{Candidate, CostModel, DefaultCostModel, Verdict, select};
+use crate::equivalence::{HandlerCheck, check_equivalence};
+use crate::corpus::{CorpusError, CorpusOptions, load_corpus};
+use crate::incremental::{
+    CACHE_DIR, HelperRecord, InputRecord, Manifest, OutputRecord,
+    ast_fingerprint, cache_key, content_hash, load_cached, store_cached,
+};
 
-/// Improved supercompiler: build AST for bodies, run transformations (const-folding, inlining, DCE),
+/// One parsed corpus file with every handler body already driven by the supercompiler.
+struct DrivenFile {
+    name: String,
+    entities: Vec<Entity>,
+    /// Driven body per entity, per event (same order as `entities`).
+    bodies: Vec<Vec<Vec<Stmt>>>,
+    /// Parsed bodies before any rewrite, and the rewrites applied so far (same shape as `bodies`).
+    originals: Vec<Vec<Vec<Stmt>>>,
+    logs: Vec<Vec<Vec<Rewrite>>>,
+    functions: Vec<FunctionDef>,
+    residuals: Vec<FunctionDef>,
+}
+
+/// Knobs of `simplify_corpus_with`.
+pub struct SimplifyOptions {
+    /// Total statements all extracted helpers may contain.
+    pub helper_budget: usize,
+    /// Profile weights (e.g. calls per frame) keyed by `Entity.Event`; missing handlers weigh 1.
+    pub profile: HashMap<String, f64>,
+    pub cost_model: Box<dyn CostModel>,
+    /// Check every rewritten handler against its original with `check_equivalence`.
+    pub verify: bool,
+    /// Which files below the input directory make up the corpus.
+    pub corpus: CorpusOptions,
+}
+
+impl Default for SimplifyOptions {
+    fn default() -> Self {
+        Self {
+            helper_budget: 10,
+            profile: HashMap::new(),
+            cost_model: Box::new(DefaultCostModel::default()),
+            verify: true,
+            corpus: CorpusOptions::default(),
+        }
+    }
+}
+
+impl SimplifyOptions {
+    /// Everything that influences the produced corpus, for cache keys.
+    pub fn fingerprint(&self) -> String {
+        let mut profile: Vec<String> = self.profile.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        profile.sort();
+        format!("budget={};profile={};model={}", self.helper_budget, profile.join(","), self.cost_model.fingerprint())
+    }
+}
+
+/// Improved supercompiler: build AST for bodies, drive them (unfolding `function`s, folding and
+/// generalizing recursion), run transformations (const-folding, inlining, DCE),
 /// and write simplified corpus.
 pub fn simplify_corpus(in_dir: &str, out_dir: &str) -> (SimplifyReport, Vec<(String,String)>) {
-    let mut seq_count: HashMap<String, usize> = HashMap::new();
+    simplify_corpus_with(in_dir, out_dir, &SimplifyOptions::default())
+}
+
+/// Incremental: files whose AST and configuration were driven before are loaded, with their
+/// rewrite log, from the cache in `out_dir`, and outputs whose contents did not change (no edit,
+/// no change in the helpers they call) are not rewritten. Every handler is still logged and
+/// verified, so the report is the same warm or cold. See `incremental` for the layout.
+pub fn simplify_corpus_with(in_dir: &str, out_dir: &str, opts: &SimplifyOptions) -> (SimplifyReport, Vec<(String,String)>) {
+    let config = opts.fingerprint();
+    let mut manifest = Manifest { config: content_hash(config.as_bytes()), ..Default::default() };
+    let previous = Manifest::load(out_dir).filter(|m| m.config == manifest.config).unwrap_or_default();
+    let mut incremental = IncrementalReport::default();
     let mut files = Vec::new();
+    let mut cost_before = 0usize;
+    let mut keys = HashSet::new();
+
+    let corpus = load_corpus(in_dir, &opts.corpus);
+    for file in corpus.files {
+        let (name, s, entities) = (file.relative, file.source, file.entities);
+        let functions: Vec<FunctionDef> = file.functions.iter().map(FunctionDef::from).collect();
+        let originals: Vec<Vec<Vec<Stmt>>> = entities.iter()
+            .map(|e| e.events.iter().map(|ev| parse_statements(&ev.body)).collect())
+            .collect();
+        cost_before += originals.iter().flatten().map(|b| static_cost(b, &functions)).sum::<usize>();
+
+        let ast = ast_fingerprint(&s);
+        manifest.inputs.push(InputRecord { path: name.clone(), ast: content_hash(ast.as_bytes()) });
+        let key = cache_key(&ast, &config);
+        keys.insert(key);
 
-    if let Ok(entries) = read_dir(in_dir) {
-        for ent in entries.flatten() {
-            let p = ent.path();
-            if p.is_file() && p.extension().map(|e| e == "meta").unwrap_or(false) {
-                if let Ok(s) = read_to_string(&p) {
-                    files.push((p.file_name().unwrap().to_string_lossy().to_string(), s.clone()));
-                    let entities = parse_entities(&s);
-                    for e in entities {
-                        for ev in e.events {
-                            let stmts = parse_statements(&ev.body);
-                            let seq = stmts.iter().filter_map(|st| match st {
-                                crate::ast::Stmt::Expr(crate::ast::Expr::Call { name, .. }) => Some(name.clone()),
-                                _ => None,
-                            }).collect::<Vec<_>>();
-                            if seq.len() >= 2 {
-                                let key = seq.join(";");
-                                *seq_count.entry(key).or_default() += 1;
-                            }
-                        }
+        let cached = load_cached(out_dir, key).and_then(|c| from_cache(&c, &entities));
+        let (bodies, logs, residuals) = match cached {
+            Some((bodies, logs, residuals)) => {
+                incremental.cached.push(name.clone());
+                (bodies, logs, residuals)
+            }
+            None => {
+                let mut sc = Supercompiler::new(&functions);
+                let (mut bodies, mut logs) = (Vec::new(), Vec::new());
+                for per_entity in &originals {
+                    let (mut per_event, mut log) = (Vec::new(), Vec::new());
+                    for parsed in per_entity {
+                        let mut rewrites = Vec::new();
+                        // Fold constants
+                        let mut stmts = parsed.clone();
+                        fold_constants(&mut stmts, &mut rewrites);
+                        let (mut driven, _tree) = sc.drive(&stmts, &KnownEnv::new());
+                        let (before, after) = (block_to_string(&stmts), block_to_string(&driven));
+                        if before != after { rewrites.push(Rewrite { pass: "drive", before, after }); }
+                        // DCE
+                        dce(&mut driven, &mut rewrites);
+                        per_event.push(driven);
+                        log.push(rewrites);
                     }
+                    bodies.push(per_event);
+                    logs.push(log);
                 }
+                let residuals = sc.residual_functions().to_vec();
+                let _ = store_cached(out_dir, key, &to_cache(&entities, &bodies, &logs, &residuals));
+                incremental.driven.push(name.clone());
+                (bodies, logs, residuals)
             }
-        }
+        };
+        files.push(DrivenFile { name, entities, bodies, originals, logs, functions, residuals });
     }
 
-    // select repeated sequences
-    let mut candidates: Vec<(String, usize)> = seq_count.into_iter().filter(|(_,c)| *c > 1).collect();
-    candidates.sort_by(|a,b| b.1.cmp(&a.1));
-
-    // map sequence -> helper name and store helper AST bodies
-    let mut helper_map_seq_to_name: HashMap<String,String> = HashMap::new();
-    let mut helper_bodies: HashMap<String, Vec<crate::ast::Stmt>> = HashMap::new();
-    let mut helper_idx = 0usize;
-    // Aggressive inlining selection: choose sequences by heuristic budget
-    // heuristic: benefit = occurrences * length - cost; pick until budget
-    let mut budget = 10usize; // budget units (tunable)
-    let mut chosen: Vec<(String, usize)> = Vec::new();
-    for (seq, count) in &candidates {
-        let len = seq.split(';').count();
-        let _benefit = (*count).saturating_mul(len) as isize - 1; // simple heuristic
-        if budget >= len {
-            chosen.push((seq.clone(), *count));
-            budget = budget.saturating_sub(len);
+    // collect repeated call sequences (any contiguous run, not only whole bodies), grouped by shape
+    let mut occurrences: HashMap<String, Vec<Occurrence>> = HashMap::new();
+    let mut next_block = 0usize;
+    for file in &files {
+        for (e, bodies) in file.entities.iter().zip(&file.bodies) {
+            for (ev, body) in e.events.iter().zip(bodies) {
+                let weight = opts.profile.get(&format!("{}.{}", e.name, ev.name)).copied().unwrap_or(1.0);
+                let site = format!("{}:{}.{}", file.name, e.name, ev.name);
+                collect_sequences(body, weight, &site, &mut next_block, &mut occurrences);
+            }
         }
     }
 
-    for (seq, _count) in &chosen {
-        helper_idx += 1;
-        let helper_name = format!("_helper_{}", helper_idx);
-        helper_map_seq_to_name.insert(seq.clone(), helper_name.clone());
-        // build helper AST body from seq
-        let parts: Vec<&str> = seq.split(';').collect();
-        let mut body_stmts = Vec::new();
-        for p in parts { body_stmts.push(crate::ast::Stmt::Expr(crate::ast::Expr::Call { name: p.to_string(), args: vec![] })); }
-        helper_bodies.insert(helper_name.clone(), body_stmts);
-    }
+    // describe repeated sequences to the cost model
+    let mut shapes: Vec<(String, Vec<Occurrence>)> = occurrences.into_iter()
+        .map(|(shape, occs)| (shape, non_overlapping(occs)))
+        .filter(|(_, occs)| occs.len() > 1)
+        .collect();
+    shapes.sort_by(|a, b| a.0.cmp(&b.0));
+    let candidates: Vec<Candidate> = shapes.iter().map(|(shape, occs)| {
+        let stmts: Vec<Vec<Stmt>> = occs.iter().map(|o| o.stmts.clone()).collect();
+        Candidate {
+            shape: shape.clone(),
+            statements: occs[0].stmts.len(),
+            size: occs[0].stmts.iter().map(stmt_size).sum(),
+            params: anti_unify("", &stmts).params.len(),
+            occurrences: occs.len(),
+            hotness: occs.iter().map(|o| o.weight).sum(),
+        }
+    }).collect();
+    let conflicts = |i: usize, j: usize| shapes[i].1.iter().any(|a| shapes[j].1.iter().any(|b| a.overlaps(b)));
+    let verdicts = select(&candidates, opts.cost_model.as_ref(), opts.helper_budget, &conflicts);
 
-    // refal-like reduction: resolve wrapper helpers (helpers that simply call another helper)
-    crate::ast::resolve_wrappers(&mut helper_bodies);
+    // extract chosen sequences, most beneficial first
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    let benefits: Vec<f64> = candidates.iter().map(|c| opts.cost_model.benefit(c)).collect();
+    order.sort_by(|&a, &b| benefits[b].total_cmp(&benefits[a]).then(a.cmp(&b)));
+    let mut helpers: Vec<FunctionDef> = Vec::new();
+    let mut chosen_helpers: Vec<(String, String)> = Vec::new();
+    let mut names: HashMap<usize, String> = HashMap::new();
+    for &i in &order {
+        if verdicts[i] == Verdict::Extract {
+            // anti-unify the occurrences into a helper with parameters
+            let stmts: Vec<Vec<Stmt>> = shapes[i].1.iter().map(|o| o.stmts.clone()).collect();
+            let helper = anti_unify(&format!("_helper_{}", helpers.len() + 1), &stmts);
+            chosen_helpers.push((call_names(&helper.body).join(";"), helper.name.clone()));
+            names.insert(i, helper.name.clone());
+            let mut sources: Vec<String> = shapes[i].1.iter().map(|o| o.site.clone()).collect();
+            sources.dedup();
+            manifest.helpers.push(HelperRecord {
+                name: helper.name.clone(),
+                definition: content_hash(function_to_string(&helper.name, &helper.params, &helper.body).as_bytes()),
+                sources,
+            });
+            helpers.push(helper);
+        }
+    }
+    let decisions: Vec<HelperDecision> = order.iter().map(|&i| {
+        let c = &candidates[i];
+        let justification = match &verdicts[i] {
+            Verdict::Extract => format!(
+                "extracted: benefit {:.2} from {} occurrences of {} statements ({} params, hotness {:.1})",
+                benefits[i], c.occurrences, c.statements, c.params, c.hotness),
+            Verdict::Unprofitable => format!("kept inline: benefit {:.2} is not positive", benefits[i]),
+            Verdict::OverBudget => format!("kept inline: {} statements exceed the remaining helper budget", c.statements),
+            Verdict::Conflicts(j) => format!("kept inline: overlaps {}", names.get(j).cloned().unwrap_or_default()),
+        };
+        HelperDecision { name: names.get(&i).cloned(), candidate: c.clone(), benefit: benefits[i], justification }
+    }).collect();
+    // try longer helpers first when rewriting
+    let mut by_length: Vec<&FunctionDef> = helpers.iter().collect();
+    by_length.sort_by_key(|h| std::cmp::Reverse(h.body.len()));
 
     let _ = create_dir_all(out_dir);
     let mut total_replacements = 0usize;
 
-    for (fname, content) in files {
-        let mut out = String::new();
-        let entities = parse_entities(&content);
-        for e in entities {
-            out.push_str(&format!("entity {} {{\n", e.name));
-            if !e.components.is_empty() {
-                out.push_str(&format!("    components: [{}];\n\n", e.components.join(", ")));
-            }
-            for ev in e.events {
-                let mut stmts = parse_statements(&ev.body);
-
-                // Fold constants
-                fold_constants(&mut stmts);
-                // Inline helpers where present
-                // find matching seq key for this event
-                let seq_key = stmts.iter().filter_map(|st| match st {
-                    crate::ast::Stmt::Expr(crate::ast::Expr::Call { name, .. }) => Some(name.clone()),
-                    _ => None,
-                }).collect::<Vec<_>>().join(";");
-                if let Some(helper_name) = helper_map_seq_to_name.get(&seq_key) {
-                    // replace entire body with single helper call
-                    let new_body = format!("{}();", helper_name);
-                    out.push_str(&format!("    on {}({}) {{\n        {}\n    }}\n\n", ev.name, ev.params.unwrap_or_default(), new_body));
-                    total_replacements += 1;
-                    continue;
-                }
-
-                // Else inline helper calls inside
-                inline_helpers(&mut stmts, &helper_bodies);
-                // DCE
-                dce(&mut stmts);
+    let mut cost_after = 0usize;
+    let mut rewrites = Vec::new();
+    let mut equivalence = Vec::new();
+    for file in files {
+        // everything the emitted bodies may call, for costing
+        let mut callable: Vec<FunctionDef> = file.functions.iter().chain(&file.residuals).cloned().collect();
+        callable.extend(helpers.iter().cloned());
 
-                // stringify
-                out.push_str(&format!("    on {}({}) {{\n", ev.name, ev.params.unwrap_or_default()));
-                for st in &stmts {
-                    let s = stmt_to_string(st);
-                    if !s.is_empty() { out.push_str(&format!("        {}\n", s)); }
-                }
-                out.push_str("    }\n\n");
+        // replace repeated sequences with helper calls
+        let mut rewritten = Vec::new();
+        let mut logs = Vec::new();
+        for (bodies, entity_logs) in file.bodies.into_iter().zip(file.logs) {
+            let mut per_event = Vec::new();
+            for (stmts, mut log) in bodies.into_iter().zip(entity_logs) {
+                let stmts = extract_helpers(stmts, &by_length, &mut total_replacements, &mut log);
+                cost_after += static_cost(&stmts, &callable);
+                per_event.push(stmts);
+                logs.push(log);
             }
-            out.push_str("}\n\n");
+            rewritten.push(per_event);
         }
 
-        if !helper_bodies.is_empty() {
+        // only the helpers this file calls, so other helpers changing leaves it alone
+        let mut called = HashSet::new();
+        for st in rewritten.iter().flatten().flatten() { called_names(st, &mut called); }
+        let used: Vec<&FunctionDef> = helpers.iter().filter(|h| called.contains(&h.name)).collect();
+
+        let mut out = render_entities(&file.entities, &rewritten);
+        if !file.functions.is_empty() || !file.residuals.is_empty() {
+            out.push_str("// Functions (residual ones specialized by the supercompiler)\n");
+            for f in file.functions.iter().chain(&file.residuals) {
+                out.push_str(&function_to_string(&f.name, &f.params, &f.body));
+            }
+            out.push('\n');
+        }
+        if !used.is_empty() {
             out.push_str("// Helpers generated by supercompiler\n");
-            for (name, body) in &helper_bodies {
-                out.push_str(&format!("function {}() {{ ", name));
-                for st in body { out.push_str(&expr_stmt_block_to_string(st)); }
-                out.push_str(" }\n");
+            for h in &used {
+                out.push_str(&function_to_string(&h.name, &h.params, &h.body));
             }
         }
 
-        let out_path = format!("{}/{}", out_dir.trim_end_matches('/'), fname);
+        let content = content_hash(out.as_bytes());
+        let out_path = format!("{}/{}", out_dir.trim_end_matches('/'), file.name);
+        manifest.outputs.push(OutputRecord {
+            path: file.name.clone(),
+            content,
+            helpers: used.iter().map(|h| h.name.clone()).collect(),
+        });
+        let sites = file.entities.iter().flat_map(|e| e.events.iter().map(move |ev| (e.name.clone(), ev.name.clone())));
+        let handlers = rewritten.iter().flatten().zip(file.originals.iter().flatten());
+        for (((entity, event), (stmts, original)), log) in sites.zip(handlers).zip(logs) {
+            if opts.verify {
+                let result = check_equivalence(original, &file.functions, stmts, &callable);
+                equivalence.push(HandlerCheck { file: file.name.clone(), entity: entity.clone(), event: event.clone(), result });
+            }
+            let site = format!("{}:{}.{}", file.name, entity, event);
+            rewrites.extend(log.into_iter().map(|r| (site.clone(), r)));
+        }
+        let unchanged = previous.output(&file.name).map(|o| o.content == content).unwrap_or(false)
+            && Path::new(&out_path).is_file();
+        if unchanged {
+            incremental.unchanged.push(file.name);
+            continue;
+        }
+        if let Some(parent) = Path::new(&out_path).parent() { let _ = create_dir_all(parent); }
         let _ = write(out_path, out);
+        incremental.written.push(file.name);
     }
 
-    let chosen_helpers: Vec<(String,String)> = helper_map_seq_to_name.into_iter().map(|(k,v)| (k,v)).collect();
-    (SimplifyReport { helper_count: helper_bodies.len(), total_replacements }, chosen_helpers)
+    // outputs of inputs that no longer exist, and cache entries nobody refers to
+    for old in &previous.outputs {
+        if manifest.output(&old.path).is_none() && remove_file(Path::new(out_dir).join(&old.path)).is_ok() {
+            incremental.removed.push(old.path.clone());
+        }
+    }
+    if let Ok(entries) = read_dir(Path::new(out_dir).join(CACHE_DIR)) {
+        for p in entries.flatten().map(|e| e.path()) {
+            let key = p.file_stem().and_then(|s| u64::from_str_radix(&s.to_string_lossy(), 16).ok());
+            if key.map(|k| !keys.contains(&k)).unwrap_or(false) { let _ = remove_file(p); }
+        }
+    }
+    let _ = manifest.save(out_dir);
+
+    let report = SimplifyReport {
+        helper_count: helpers.len(),
+        total_replacements,
+        cost_before,
+        cost_after,
+        helpers: decisions,
+        rewrites,
+        equivalence,
+        incremental,
+        errors: corpus.errors,
+    };
+    (report, chosen_helpers)
+}
+
+/// Entities with the given bodies, in corpus syntax.
+fn render_entities(entities: &[Entity], bodies: &[Vec<Vec<Stmt>>]) -> String {
+    let mut out = String::new();
+    for (e, bodies) in entities.iter().zip(bodies) {
+        out.push_str(&format!("entity {} {{\n", e.name));
+        if !e.components.is_empty() {
+            out.push_str(&format!("    components: [{}];\n\n", e.components.join(", ")));
+        }
+        for (ev, stmts) in e.events.iter().zip(bodies) {
+            out.push_str(&format!("    on {}({}) {{\n", ev.name, ev.params.clone().unwrap_or_default()));
+            for st in stmts {
+                let s = stmt_to_string(st);
+                if !s.is_empty() { out.push_str(&format!("        {}\n", s)); }
+            }
+            out.push_str("    }\n\n");
+        }
+        out.push_str("}\n\n");
+    }
+    out
+}
+
+const RESIDUALS_MARKER: &str = "// Residuals\n";
+const REWRITES_MARKER: &str = "// Rewrites\n";
+/// Passes that run before helper extraction, i.e. whose rewrites are cached with the driven bodies.
+const DRIVE_PASSES: [&str; 3] = ["fold_constants", "drive", "dce"];
+
+fn to_cache(entities: &[Entity], bodies: &[Vec<Vec<Stmt>>], logs: &[Vec<Vec<Rewrite>>], residuals: &[FunctionDef]) -> String {
+    let mut out = render_entities(entities, bodies);
+    out.push_str(RESIDUALS_MARKER);
+    for f in residuals {
+        out.push_str(&function_to_string(&f.name, &f.params, &f.body));
+    }
+    // one rewrite per line: `<entity> <event> <pass>\t<before>\t<after>`
+    out.push_str(REWRITES_MARKER);
+    for (e, per_entity) in logs.iter().enumerate() {
+        for (ev, log) in per_entity.iter().enumerate() {
+            for r in log {
+                out.push_str(&format!("{} {} {}\t{}\t{}\n", e, ev, r.pass, escape_line(&r.before), escape_line(&r.after)));
+            }
+        }
+    }
+    out
+}
+
+/// Driven bodies, their rewrite logs and residual functions of a cache entry, if it matches `entities`.
+#[allow(clippy::type_complexity)]
+fn from_cache(cached: &str, entities: &[Entity]) -> Option<(Vec<Vec<Vec<Stmt>>>, Vec<Vec<Vec<Rewrite>>>, Vec<FunctionDef>)> {
+    let (driven, rest) = cached.split_once(RESIDUALS_MARKER)?;
+    let (residuals, rewrites) = rest.split_once(REWRITES_MARKER)?;
+    let cached_entities = parse_entities(driven);
+    if cached_entities.len() != entities.len() { return None; }
+    let mut bodies = Vec::new();
+    for (c, e) in cached_entities.iter().zip(entities) {
+        if c.name != e.name || c.events.len() != e.events.len() { return None; }
+        bodies.push(c.events.iter().map(|ev| parse_statements(&ev.body)).collect());
+    }
+    let mut logs: Vec<Vec<Vec<Rewrite>>> = entities.iter().map(|e| vec![Vec::new(); e.events.len()]).collect();
+    for line in rewrites.lines() {
+        let (head, texts) = line.split_once('\t')?;
+        let (before, after) = texts.split_once('\t')?;
+        let mut head = head.split(' ');
+        let (e, ev) = (head.next()?.parse::<usize>().ok()?, head.next()?.parse::<usize>().ok()?);
+        let pass = head.next()?;
+        let pass = DRIVE_PASSES.into_iter().find(|p| *p == pass)?;
+        logs.get_mut(e)?.get_mut(ev)?.push(Rewrite { pass, before: unescape_line(before), after: unescape_line(after) });
+    }
+    Some((bodies, logs, parse_functions(residuals).iter().map(FunctionDef::from).collect()))
+}
+
+fn escape_line(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape_line(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' { out.push(c); continue; }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => break,
+        }
+    }
+    out
+}
+
+fn called_names(st: &Stmt, out: &mut HashSet<String>) {
+    match st {
+        Stmt::Expr(Expr::Call { name, .. }) => { out.insert(name.clone()); }
+        Stmt::If { body, .. } => { for s in body { called_names(s, out); } }
+        _ => {}
+    }
+}
+
+/// Shape of a statement sequence: call names with their arity, or `None` if some statement
+/// is not a plain call.
+fn shape(seq: &[Stmt]) -> Option<String> {
+    seq.iter().map(|st| match st {
+        Stmt::Expr(Expr::Call { name, args }) => Some(format!("{}/{}", name, args.len())),
+        _ => None,
+    }).collect::<Option<Vec<_>>>().map(|v| v.join(";"))
+}
+
+fn call_names(seq: &[Stmt]) -> Vec<String> {
+    seq.iter().filter_map(|st| match st {
+        Stmt::Expr(Expr::Call { name, .. }) => Some(name.clone()),
+        _ => None,
+    }).collect()
+}
+
+/// One place a candidate sequence occurs. Every handler body and nested `if` body is its
+/// own block; `weight` is the profile weight of the enclosing handler.
+struct Occurrence {
+    block: usize,
+    start: usize,
+    stmts: Vec<Stmt>,
+    weight: f64,
+    /// Enclosing handler, as `file:Entity.Event`.
+    site: String,
+}
+
+impl Occurrence {
+    fn overlaps(&self, other: &Occurrence) -> bool {
+        self.block == other.block
+            && self.start < other.start + other.stmts.len()
+            && other.start < self.start + self.stmts.len()
+    }
+}
+
+/// Record every contiguous run of at least two calls in `body` (and in nested `if` bodies).
+fn collect_sequences(body: &[Stmt], weight: f64, site: &str, next_block: &mut usize, out: &mut HashMap<String, Vec<Occurrence>>) {
+    let block = *next_block;
+    *next_block += 1;
+    for i in 0..body.len() {
+        if let Stmt::If { body: inner, .. } = &body[i] { collect_sequences(inner, weight, site, next_block, out); }
+        for j in i + 2..=body.len() {
+            match shape(&body[i..j]) {
+                Some(key) => out.entry(key).or_default().push(Occurrence { block, start: i, stmts: body[i..j].to_vec(), weight, site: site.to_string() }),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Drop occurrences overlapping an earlier one of the same shape (e.g. `a; a; a`).
+fn non_overlapping(mut occs: Vec<Occurrence>) -> Vec<Occurrence> {
+    occs.sort_by_key(|o| (o.block, o.start));
+    let mut kept: Vec<Occurrence> = Vec::new();
+    for o in occs {
+        if !kept.last().map(|k| k.overlaps(&o)).unwrap_or(false) { kept.push(o); }
+    }
+    kept
+}
+
+fn stmt_size(st: &Stmt) -> usize {
+    match st {
+        Stmt::Expr(e) => expr_size(e),
+        Stmt::If { cond, body } => 1 + expr_size(cond) + body.iter().map(stmt_size).sum::<usize>(),
+        Stmt::Empty => 0,
+    }
+}
+
+fn expr_size(e: &Expr) -> usize {
+    match e {
+        Expr::Call { args, .. } => 1 + args.iter().map(expr_size).sum::<usize>(),
+        Expr::BinaryOp { lhs, rhs, .. } => 1 + expr_size(lhs) + expr_size(rhs),
+        _ => 1,
+    }
+}
+
+/// Most specific function whose body instantiates to every occurrence: subterms that agree
+/// everywhere stay in the helper, the rest become parameters (`a`, `b`, ...), and equal
+/// argument tuples share a parameter. The occurrences must have the same statement
+/// structure (same length, `if`s in the same places).
+pub(crate) fn anti_unify(name: &str, occs: &[Vec<Stmt>]) -> FunctionDef {
+    let mut taken = HashSet::new();
+    for occ in occs { stmt_idents(occ, &mut taken); }
+    let mut state = AntiUnifier { taken, vars: Vec::new() };
+    let blocks: Vec<&[Stmt]> = occs.iter().map(|o| o.as_slice()).collect();
+    let body = state.generalize_block(&blocks);
+    FunctionDef { name: name.to_string(), params: state.vars.into_iter().map(|(_, v)| v).collect(), body }
+}
+
+fn stmt_idents(stmts: &[Stmt], out: &mut HashSet<String>) {
+    for st in stmts {
+        match st {
+            Stmt::Expr(e) => idents(e, out),
+            Stmt::If { cond, body } => { idents(cond, out); stmt_idents(body, out); }
+            Stmt::Empty => {}
+        }
+    }
+}
+
+struct AntiUnifier {
+    taken: HashSet<String>,
+    vars: Vec<(Vec<String>, String)>,
+}
+
+impl AntiUnifier {
+    fn generalize_block(&mut self, blocks: &[&[Stmt]]) -> Vec<Stmt> {
+        (0..blocks[0].len()).map(|k| match &blocks[0][k] {
+            Stmt::If { .. } => {
+                let (conds, bodies): (Vec<&Expr>, Vec<&[Stmt]>) = blocks.iter().filter_map(|b| match &b[k] {
+                    Stmt::If { cond, body } => Some((cond, body.as_slice())),
+                    _ => None,
+                }).unzip();
+                Stmt::If { cond: self.generalize(&conds), body: self.generalize_block(&bodies) }
+            }
+            Stmt::Empty => Stmt::Empty,
+            Stmt::Expr(_) => {
+                let exprs: Vec<&Expr> = blocks.iter().filter_map(|b| match &b[k] { Stmt::Expr(e) => Some(e), _ => None }).collect();
+                Stmt::Expr(self.generalize(&exprs))
+            }
+        }).collect()
+    }
+
+    fn generalize(&mut self, es: &[&Expr]) -> Expr {
+        let texts: Vec<String> = es.iter().map(|e| expr_to_string(e)).collect();
+        if texts.iter().all(|t| *t == texts[0]) { return es[0].clone(); }
+        match es[0] {
+            Expr::Call { name, args } if es.iter().all(|e| matches!(e, Expr::Call { name: n, args: a } if n == name && a.len() == args.len())) => {
+                let args = (0..args.len()).map(|i| {
+                    let column: Vec<&Expr> = es.iter().filter_map(|e| match e { Expr::Call { args, .. } => Some(&args[i]), _ => None }).collect();
+                    self.generalize(&column)
+                }).collect();
+                Expr::Call { name: name.clone(), args }
+            }
+            Expr::BinaryOp { op, .. } if es.iter().all(|e| matches!(e, Expr::BinaryOp { op: o, .. } if o == op)) => {
+                let lhs: Vec<&Expr> = es.iter().filter_map(|e| match e { Expr::BinaryOp { lhs, .. } => Some(&**lhs), _ => None }).collect();
+                let rhs: Vec<&Expr> = es.iter().filter_map(|e| match e { Expr::BinaryOp { rhs, .. } => Some(&**rhs), _ => None }).collect();
+                Expr::BinaryOp { op: *op, lhs: Box::new(self.generalize(&lhs)), rhs: Box::new(self.generalize(&rhs)) }
+            }
+            _ => Expr::Ident(self.var_for(texts)),
+        }
+    }
+
+    fn var_for(&mut self, texts: Vec<String>) -> String {
+        if let Some((_, v)) = self.vars.iter().find(|(t, _)| *t == texts) { return v.clone(); }
+        let mut k = 0usize;
+        let name = loop {
+            let letter = (b'a' + (k % 26) as u8) as char;
+            let candidate = if k < 26 { letter.to_string() } else { format!("{}{}", letter, k / 26) };
+            if !self.taken.contains(&candidate) { break candidate; }
+            k += 1;
+        };
+        self.taken.insert(name.clone());
+        self.vars.push((texts, name.clone()));
+        name
+    }
+}
+
+fn idents(e: &Expr, out: &mut HashSet<String>) {
+    match e {
+        Expr::Ident(v) => { out.insert(v.clone()); }
+        Expr::Call { args, .. } => for a in args { idents(a, out) },
+        Expr::BinaryOp { lhs, rhs, .. } => { idents(lhs, out); idents(rhs, out) }
+        _ => {}
+    }
+}
+
+/// Arguments that turn the helper body into `seq`, if it is an instance of it.
+fn match_helper(helper: &FunctionDef, seq: &[Stmt]) -> Option<Vec<Expr>> {
+    if helper.body.len() != seq.len() { return None; }
+    let mut bound: HashMap<String, Expr> = HashMap::new();
+    for (p, t) in helper.body.iter().zip(seq) {
+        match (p, t) {
+            (Stmt::Expr(pe), Stmt::Expr(te)) if match_param(pe, te, &helper.params, &mut bound) => {}
+            _ => return None,
+        }
+    }
+    helper.params.iter().map(|p| bound.get(p).cloned()).collect()
+}
+
+fn match_param(p: &Expr, t: &Expr, params: &[String], bound: &mut HashMap<String, Expr>) -> bool {
+    match (p, t) {
+        (Expr::Ident(v), _) if params.contains(v) => match bound.get(v) {
+            Some(b) => expr_to_string(b) == expr_to_string(t),
+            None => { bound.insert(v.clone(), t.clone()); true }
+        },
+        (Expr::Call { name: n1, args: a1 }, Expr::Call { name: n2, args: a2 }) => {
+            n1 == n2 && a1.len() == a2.len() && a1.iter().zip(a2).all(|(x, y)| match_param(x, y, params, bound))
+        }
+        (Expr::BinaryOp { op: o1, lhs: l1, rhs: r1 }, Expr::BinaryOp { op: o2, lhs: l2, rhs: r2 }) => {
+            o1 == o2 && match_param(l1, l2, params, bound) && match_param(r1, r2, params, bound)
+        }
+        _ => expr_to_string(p) == expr_to_string(t),
+    }
+}
+
+/// Rewrite `stmts` left to right, replacing sequences that match a helper with a call to it.
+fn extract_helpers(stmts: Vec<Stmt>, helpers: &[&FunctionDef], replacements: &mut usize, log: &mut Vec<Rewrite>) -> Vec<Stmt> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    'scan: while i < stmts.len() {
+        for h in helpers {
+            let end = i + h.body.len();
+            if end <= stmts.len() {
+                if let Some(args) = match_helper(h, &stmts[i..end]) {
+                    let call = Stmt::Expr(Expr::Call { name: h.name.clone(), args });
+                    log.push(Rewrite { pass: "extract_helpers", before: block_to_string(&stmts[i..end]), after: stmt_to_string(&call) });
+                    out.push(call);
+                    *replacements += 1;
+                    i = end;
+                    continue 'scan;
+                }
+            }
+        }
+        match &stmts[i] {
+            Stmt::If { cond, body } => out.push(Stmt::If { cond: cond.clone(), body: extract_helpers(body.clone(), helpers, replacements, log) }),
+            st => out.push(st.clone()),
+        }
+        i += 1;
+    }
+    out
+}
+
+fn block_to_string(stmts: &[Stmt]) -> String {
+    stmts.iter().map(stmt_to_string).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+pub(crate) fn function_to_string(name: &str, params: &[String], body: &[Stmt]) -> String {
+    let mut s = format!("function {}({}) {{ ", name, params.join(", "));
+    for st in body { s.push_str(&expr_stmt_block_to_string(st)); }
+    s.push_str(" }\n");
+    s
+}
+
+/// Known inputs for one handler, e.g. `other.tag = "Enemy"` or a constant component value.
+#[derive(Debug, Clone)]
+pub struct Specialization {
+    pub entity: String,
+    pub event: String,
+    pub known: KnownEnv,
+}
+
+/// Residual handler produced for a `Specialization`, with its estimated cost before and after.
+#[derive(Debug, Clone)]
+pub struct SpecializedHandler {
+    pub file: String,
+    pub entity: String,
+    pub event: String,
+    pub residual: Residual,
+    pub cost_before: usize,
+    pub cost_after: usize,
+}
+
+/// Drive the requested handlers of the corpus against their known inputs.
+pub fn specialize_corpus(in_dir: &str, specs: &[Specialization]) -> Vec<SpecializedHandler> {
+    let mut res = Vec::new();
+    for file in load_corpus(in_dir, &CorpusOptions::default()).files {
+        let functions: Vec<FunctionDef> = file.functions.iter().map(FunctionDef::from).collect();
+        for e in &file.entities {
+            for ev in &e.events {
+                for spec in specs.iter().filter(|sp| sp.entity == e.name && sp.event == ev.name) {
+                    let stmts = parse_statements(&ev.body);
+                    let residual = supercompile(&stmts, &spec.known, &functions);
+                    res.push(SpecializedHandler {
+                        file: file.relative.clone(),
+                        entity: e.name.clone(),
+                        event: ev.name.clone(),
+                        cost_before: static_cost(&stmts, &functions),
+                        cost_after: static_cost(&residual.body, &residual.functions),
+                        residual,
+                    });
+                }
+            }
+        }
+    }
+    res
 }
 
 fn expr_stmt_block_to_string(stmt: &crate::ast::Stmt) -> String {
@@ -149,4 +693,39 @@ fn expr_stmt_block_to_string(stmt: &crate::ast::Stmt) -> String {
 pub struct SimplifyReport {
     pub helper_count: usize,
     pub total_replacements: usize,
+    /// Estimated interpreted statements over all handlers, before and after simplification.
+    pub cost_before: usize,
+    pub cost_after: usize,
+    /// Every repeated sequence considered, most beneficial first, with the solver's verdict.
+    pub helpers: Vec<HelperDecision>,
+    /// Every rewrite applied, keyed by `file:Entity.Event`, in pass order.
+    pub rewrites: Vec<(String, Rewrite)>,
+    /// Equivalence of each handler of the rewritten files with its original (empty unless `verify`).
+    pub equivalence: Vec<HandlerCheck>,
+    pub incremental: IncrementalReport,
+    /// Corpus files that could not be read or parsed, and were left out.
+    pub errors: Vec<CorpusError>,
+}
+
+/// Which files a run actually processed, by file name.
+#[derive(Debug, Default)]
+pub struct IncrementalReport {
+    /// Driven from scratch (cache miss).
+    pub driven: Vec<String>,
+    /// Driving result loaded from the cache.
+    pub cached: Vec<String>,
+    pub written: Vec<String>,
+    /// Output identical to the previous run; not rewritten.
+    pub unchanged: Vec<String>,
+    /// Outputs of inputs that disappeared since the previous run.
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HelperDecision {
+    /// Helper name if the sequence was extracted.
+    pub name: Option<String>,
+    pub candidate: Candidate,
+    pub benefit: f64,
+    pub justification: String,
 }
@@ -27,7 +27,7 @@ impl EventSystem {
     }
 
     pub fn subscribe(&mut self, event_type: String, listener: EventListener) {
-        self.listeners.entry(event_type).or_insert_with(Vec::new).push(listener);
+        self.listeners.entry(event_type).or_default().push(listener);
     }
 
     pub fn emit(&self, event: Event) {
@@ -112,7 +112,7 @@ impl StateMachine {
     }
 
     pub fn add_transition(&mut self, from: EntityState, trigger: String, to: EntityState) {
-        self.transitions.entry(from).or_insert_with(HashMap::new).insert(trigger, to);
+        self.transitions.entry(from).or_default().insert(trigger, to);
     }
 
     pub fn on_event(&mut self, trigger: &str) -> bool {
@@ -8,3 +8,29 @@ fn health_take_heal() {
     h.heal(20.0);
     assert_eq!(h.current, 90.0);
 }
+
+#[test]
+fn vector_and_transform_math() {
+    use experiment::components::{Quaternion, Transform, Vector3};
+    use std::f32::consts::FRAC_PI_2;
+
+    let a = Vector3::new(1.0, 2.0, 3.0);
+    let b = Vector3::new(4.0, 5.0, 6.0);
+    assert_eq!(a + b, Vector3::new(5.0, 7.0, 9.0));
+    assert_eq!(b - a, Vector3::one() * 3.0);
+    assert_eq!(a.dot(b), 32.0);
+    assert_eq!(Vector3::new(1.0, 0.0, 0.0).cross(Vector3::new(0.0, 1.0, 0.0)), Vector3::new(0.0, 0.0, 1.0));
+    assert_eq!(Vector3::new(3.0, 4.0, 0.0).normalize().length(), 1.0);
+    assert_eq!(a.lerp(b, 0.5), Vector3::new(2.5, 3.5, 4.5));
+
+    let t = Transform {
+        position: Vector3::new(10.0, 0.0, 0.0),
+        rotation: Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), FRAC_PI_2),
+        scale: Vector3::one() * 2.0,
+    };
+    let p = t.transform_point(Vector3::new(1.0, 0.0, 0.0));
+    assert!(p.distance(Vector3::new(10.0, 2.0, 0.0)) < 1e-5);
+    let back = Transform::from_mat4(t.to_mat4());
+    assert!(back.position.distance(t.position) < 1e-5 && back.scale.distance(t.scale) < 1e-5);
+    assert!((back.rotation.to_euler().z - FRAC_PI_2).abs() < 1e-5);
+}
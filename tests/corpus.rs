@@ -0,0 +1,31 @@
+use experiment::corpus::{load_corpus, CorpusError, CorpusOptions};
+use experiment::supercompiler::simplify_corpus;
+
+#[test]
+fn nested_corpus_keeps_relative_paths_and_reports_bad_files() {
+    let dir = std::env::temp_dir().join(format!("experiment_corpus_in_{}", std::process::id()));
+    let out = std::env::temp_dir().join(format!("experiment_corpus_out_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_dir_all(&out);
+    std::fs::create_dir_all(dir.join("levels/boss")).unwrap();
+    std::fs::create_dir_all(dir.join("drafts")).unwrap();
+    std::fs::write(dir.join("player.meta"), "entity Player { on Update(dt) { move(velocity * dt); } }").unwrap();
+    std::fs::write(dir.join("levels/boss/guard.meta"), "entity Guard { on Hit() { takeDamage(5 + 5); } }").unwrap();
+    std::fs::write(dir.join("levels/broken.meta"), "entity Broken { on Hit() { collide(); }").unwrap();
+    std::fs::write(dir.join("drafts/wip.meta"), "entity Wip { }").unwrap();
+    std::fs::write(dir.join("levels/notes.txt"), "not a corpus file").unwrap();
+
+    let opts = CorpusOptions { exclude: vec!["drafts/**".to_string()], jobs: 2, ..CorpusOptions::default() };
+    let corpus = load_corpus(&dir.to_string_lossy(), &opts);
+    let names: Vec<&str> = corpus.files.iter().map(|f| f.relative.as_str()).collect();
+    assert_eq!(names, vec!["levels/boss/guard.meta", "player.meta"]);
+    assert!(matches!(&corpus.errors[..], [CorpusError::Parse { path, .. }] if path == "levels/broken.meta"), "{:?}", corpus.errors);
+
+    let (rep, _) = simplify_corpus(&dir.to_string_lossy(), &out.to_string_lossy());
+    assert_eq!(rep.errors.len(), 1);
+    let guard = std::fs::read_to_string(out.join("levels/boss/guard.meta")).unwrap();
+    assert!(guard.contains("takeDamage(10);"));
+    assert!(out.join("drafts/wip.meta").exists(), "default options include every .meta file");
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_dir_all(&out).unwrap();
+}
@@ -0,0 +1,15 @@
+use experiment::differential::{run_differential, DiffOptions};
+
+#[test]
+fn corpus_survives_supercompilation_and_aot() {
+    let tmp = std::env::temp_dir().join(format!("experiment_difftest_corpus_{}", std::process::id()));
+    let opts = DiffOptions {
+        simplified_dir: tmp.join("simplified").to_string_lossy().to_string(),
+        aot_dir: Some(tmp.join("aot").to_string_lossy().to_string()),
+        ..DiffOptions::default()
+    };
+    match run_differential("corpus", &opts) {
+        Ok(report) => assert_eq!(report.backends, vec!["interpreter", "supercompiled", "aot"]),
+        Err(e) => panic!("{}", e),
+    }
+}
@@ -0,0 +1,28 @@
+use experiment::components::{Transform, Velocity};
+use experiment::ecs::{NodeRef, Parent, World};
+use experiment::game_engine::GameEngine;
+use experiment::runtime::Value;
+
+#[test]
+fn node_tree_is_backed_by_ecs_entities() {
+    let mut engine = GameEngine::new();
+    let meta = r#"entity Ship { components: [Transform, Velocity, AI]; } entity Turret { components: [Transform, Health]; }"#;
+    engine.create_scene_from_meta("S", meta).unwrap();
+    let scene = engine.scenes.get_scene_mut("S").unwrap();
+    scene.root.children[0].components.get_mut("Velocity").unwrap().set_property("value".to_string(), Value::Float(2.0));
+
+    let mut world = World::new();
+    let root = world.attach_tree(&mut scene.root);
+    assert_eq!(world.len(), 3);
+    let ship = scene.root.find_by_id("ship_1").and_then(|n| n.ecs).unwrap();
+    assert_eq!(world.get::<Parent>(ship), Some(&Parent(root)));
+    assert_eq!(world.get::<NodeRef>(ship).map(|n| n.name.as_str()), Some("Ship"));
+
+    for (t, v) in world.query::<(&mut Transform, &Velocity)>() {
+        t.position.x += v.value.x;
+    }
+    world.sync_tree(&mut scene.root);
+    let ship = scene.root.find_by_id("ship_1").unwrap();
+    assert!(matches!(ship.get_component("Transform").unwrap().get_property("position"), Some(Value::Float(x)) if *x == 2.0));
+    assert!(ship.get_component("AI").is_some());
+}
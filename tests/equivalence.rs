@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use experiment::ast::{inline_helpers, parse_statements, resolve_wrappers, stmt_to_string};
+use experiment::cost_model::{Candidate, CostModel};
+use experiment::equivalence::{verify_corpus, Equivalence};
+use experiment::supercompiler::{simplify_corpus_with, SimplifyOptions};
+
+/// Extracts every repeated sequence, regardless of size.
+struct Eager;
+
+impl CostModel for Eager {
+    fn benefit(&self, c: &Candidate) -> f64 {
+        c.occurrences as f64
+    }
+}
+
+const FOLDS: &str = r#"
+entity Mine { on Collision(other) { if (other.tag == "Player") { takeDamage(20 + 5); collide(); } } }
+entity Spike { on Collision(other) { if (other.tag == "Player") { takeDamage(7); collide(); } } }
+function hurt(n) { takeDamage(n * 2); }
+entity Trap { on Trigger() { hurt(3); ; } }
+"#;
+
+#[test]
+fn every_simplified_handler_is_proved_equivalent() {
+    let dir = std::env::temp_dir().join(format!("experiment_equivalence_in_{}", std::process::id()));
+    let out = std::env::temp_dir().join(format!("experiment_equivalence_out_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&out);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("traps.meta"), FOLDS).unwrap();
+
+    let opts = SimplifyOptions { helper_budget: 10, cost_model: Box::new(Eager), ..Default::default() };
+    let (rep, _) = simplify_corpus_with(&dir.to_string_lossy(), &out.to_string_lossy(), &opts);
+    for check in &rep.equivalence {
+        assert!(matches!(check.result, Equivalence::Proved { .. }), "{}.{}: {:?}", check.entity, check.event, check.result);
+    }
+    let passes: Vec<&str> = rep.rewrites.iter().map(|(_, r)| r.pass).collect();
+    for pass in ["fold_constants", "drive", "extract_helpers"] {
+        assert!(passes.contains(&pass), "no {} rewrite in {:?}", pass, passes);
+    }
+
+    // the written corpus checks out too
+    let checks = verify_corpus(&dir.to_string_lossy(), &out.to_string_lossy()).unwrap();
+    assert_eq!(checks.len(), 3);
+    assert!(checks.iter().all(|c| matches!(c.result, Equivalence::Proved { .. })), "{:?}", checks);
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_dir_all(&out).unwrap();
+}
+
+#[test]
+fn warm_cache_reports_the_same_rewrites_and_checks() {
+    let dir = std::env::temp_dir().join(format!("experiment_equivalence_warm_in_{}", std::process::id()));
+    let out = std::env::temp_dir().join(format!("experiment_equivalence_warm_out_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&out);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("traps.meta"), FOLDS).unwrap();
+
+    let opts = SimplifyOptions { helper_budget: 10, cost_model: Box::new(Eager), ..Default::default() };
+    let (cold, _) = simplify_corpus_with(&dir.to_string_lossy(), &out.to_string_lossy(), &opts);
+    let (warm, _) = simplify_corpus_with(&dir.to_string_lossy(), &out.to_string_lossy(), &opts);
+    assert_eq!(warm.incremental.cached, vec!["traps.meta"]);
+    assert_eq!(warm.incremental.unchanged, vec!["traps.meta"]);
+    assert!(!warm.rewrites.is_empty());
+    assert_eq!(warm.rewrites, cold.rewrites);
+    assert_eq!(warm.equivalence.len(), 3);
+    assert!(warm.equivalence.iter().all(|c| matches!(c.result, Equivalence::Proved { .. })), "{:?}", warm.equivalence);
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_dir_all(&out).unwrap();
+}
+
+#[test]
+fn inlining_logs_every_expanded_call() {
+    let helpers = HashMap::from([("hurt".to_string(), parse_statements("takeDamage(2); collide();"))]);
+    let mut stmts = parse_statements(r#"hurt(); if (other.tag == "Player") { hurt(); } move(dt);"#);
+    let mut log = Vec::new();
+    inline_helpers(&mut stmts, &helpers, &mut log);
+    assert_eq!(log.len(), 2);
+    assert!(log.iter().all(|r| r.pass == "inline_helpers" && r.before.contains("hurt()") && r.after.contains("takeDamage(2)")));
+    assert_eq!(stmts.len(), 4);
+    assert!(stmt_to_string(&stmts[2]).contains("takeDamage(2)"));
+}
+
+#[test]
+fn wrapper_resolution_logs_rewrites_and_stops_on_cycles() {
+    let mut helpers = HashMap::from([
+        ("outer".to_string(), parse_statements("inner();")),
+        ("inner".to_string(), parse_statements("takeDamage(1); collide();")),
+        // a -> b -> a and a self-wrapper never settle; they must not loop forever
+        ("a".to_string(), parse_statements("b();")),
+        ("b".to_string(), parse_statements("a();")),
+        ("me".to_string(), parse_statements("me();")),
+    ]);
+    let mut log = Vec::new();
+    resolve_wrappers(&mut helpers, &mut log);
+    assert_eq!(helpers["outer"].len(), 2);
+    let resolved: Vec<&str> = log.iter().map(|r| r.before.split(':').next().unwrap()).collect();
+    assert_eq!(resolved, ["a", "outer"]);
+    assert!(log.iter().all(|r| r.pass == "resolve_wrappers"));
+    assert_eq!(stmt_to_string(&helpers["me"][0]), stmt_to_string(&parse_statements("me();")[0]));
+}
@@ -1,4 +1,7 @@
-use experiment::game_engine::GameEngine;
+use experiment::components::Vector3;
+use experiment::game_engine::{Component, FixedTimestep, GameEngine, Node, SignalTarget};
+use std::collections::HashMap;
+use experiment::runtime::{EntityInstance, Value};
 
 #[test]
 fn create_and_load_scene() {
@@ -6,7 +9,349 @@ fn create_and_load_scene() {
     let meta = r#"entity Test { on Update(dt) { move(velocity * dt); } }"#;
     assert!(engine.create_scene_from_meta("S1", meta).is_ok());
     assert!(engine.load_scene("S1").is_ok());
-    assert_eq!(engine.current_scene.as_deref(), Some("S1"));
+    assert_eq!(engine.scenes.active_scene.as_deref(), Some("S1"));
     engine.update(0.016);
     assert!(engine.time.frame_count > 0);
 }
+
+#[test]
+fn update_runs_frame_handlers_and_writes_components() {
+    let mut engine = GameEngine::new();
+    let meta = r#"
+entity Player { on Update(dt) { move(velocity * dt); } on Hit() { takeDamage(50); } }
+entity Turret { on Tick(dt) { takeDamage(1); } }
+"#;
+    engine.create_scene_from_meta("S", meta).unwrap();
+    engine.load_scene("S").unwrap();
+
+    let mut player = Node::new("p".to_string(), "Hero".to_string(), "Node".to_string()).with_entity("Player");
+    let mut physics = Component::new("Physics".to_string());
+    physics.set_property("velocity".to_string(), Value::Float(4.0));
+    player.add_component(physics);
+    player.add_component(Component::new("Transform".to_string()));
+    player.instance = Some(EntityInstance::new("Hero", "player"));
+
+    let mut turret = Node::new("t".to_string(), "Turret".to_string(), "Node".to_string());
+    let mut health = Component::new("Health".to_string());
+    health.set_property("hp".to_string(), Value::Int(10));
+    turret.add_component(health);
+    turret.instance = Some(EntityInstance::new("Turret", "enemy"));
+    player.add_child(turret);
+    engine.add_node("S", player).unwrap();
+
+    engine.update(0.5);
+    engine.update(0.5);
+    let hero = engine.scenes["S"].root.find_by_id("p").unwrap();
+    assert!(matches!(hero.get_component("Transform").unwrap().get_property("position"), Some(Value::Float(p)) if *p == 4.0));
+    assert_eq!(hero.instance.as_ref().unwrap().health, 100);
+    assert!(matches!(hero.children[0].get_component("Health").unwrap().get_property("hp"), Some(Value::Int(8))));
+}
+
+#[test]
+fn signals_reach_nested_nodes_and_groups() {
+    let mut engine = GameEngine::new();
+    let meta = r#"
+entity Guard { on Hit() { takeDamage(10); } }
+entity Crate { on Open() { collide(); } }
+"#;
+    engine.create_scene_from_meta("S", meta).unwrap();
+    let guard = |id: &str| {
+        let mut n = Node::new(id.to_string(), id.to_string(), "Node".to_string());
+        n.instance = Some(EntityInstance::new("Guard", "enemy"));
+        n
+    };
+    let mut level = Node::new("level".to_string(), "Level".to_string(), "Node".to_string());
+    let mut g1 = guard("g1");
+    g1.add_to_group("enemies");
+    g1.add_child(guard("g2"));
+    level.add_child(g1);
+    let mut crate_node = Node::new("c".to_string(), "Crate".to_string(), "Node".to_string());
+    crate_node.instance = Some(EntityInstance::new("Crate", "prop"));
+    level.add_child(crate_node);
+    engine.add_node("S", level).unwrap();
+
+    engine.emit_signal("S", "g2", "Hit", HashMap::new()).unwrap();
+    assert_eq!(engine.broadcast_signal("S", SignalTarget::Group("enemies"), "Hit", HashMap::new()), Ok(1));
+    // g1, g2 и guard_1, созданный из определения сущности
+    assert_eq!(engine.broadcast_signal("S", SignalTarget::All, "Hit", HashMap::new()), Ok(3));
+    let g1 = engine.scenes["S"].root.find_by_id("g1").unwrap();
+    assert_eq!(g1.instance.as_ref().unwrap().health, 80);
+    assert_eq!(g1.children[0].instance.as_ref().unwrap().health, 80);
+
+    assert_eq!(engine.emit_signal("Nope", "g1", "Hit", HashMap::new()), Err("Scene 'Nope' not found".to_string()));
+    assert_eq!(engine.emit_signal("S", "ghost", "Hit", HashMap::new()), Err("Node 'ghost' not found in scene 'S'".to_string()));
+    assert_eq!(engine.emit_signal("S", "c", "Hit", HashMap::new()), Err("Entity 'Crate' has no handler for signal 'Hit'".to_string()));
+}
+
+#[test]
+fn scene_nodes_are_spawned_from_entities() {
+    let mut engine = GameEngine::new();
+    let meta = r#"
+entity Player { components: [Transform, Physics]; on Update(dt) { move(velocity * dt); } }
+entity Enemy { components: [Transform, Health]; on Hit() { takeDamage(5); } }
+"#;
+    engine.create_scene_from_meta("S", meta).unwrap();
+    let ids: Vec<&str> = engine.scenes["S"].root.children.iter().map(|n| n.id.as_str()).collect();
+    assert_eq!(ids, ["player_1", "enemy_1"]);
+    assert!(engine.spawn("Enemy", Vector3::zero()).is_err());
+
+    engine.load_scene("S").unwrap();
+    assert_eq!(engine.spawn("Enemy", Vector3::new(10.0, 0.0, 0.0)), Ok("enemy_2".to_string()));
+    engine.emit_signal("S", "enemy_2", "Hit", HashMap::new()).unwrap();
+    let enemy = engine.scenes["S"].root.find_by_id("enemy_2").unwrap();
+    assert_eq!(enemy.node_type, "Node");
+    assert!(matches!(enemy.get_component("Health").unwrap().get_property("hp"), Some(Value::Int(95))));
+    assert!(matches!(enemy.get_component("Transform").unwrap().get_property("position"), Some(Value::Float(x)) if *x == 10.0));
+    assert_eq!(engine.spawn("Boss", Vector3::zero()), Err("Entity 'Boss' is not defined in scene 'S'".to_string()));
+}
+
+#[test]
+fn fixed_timestep_is_frame_rate_independent() {
+    let run = |frame_time: f64, frames: usize| {
+        let mut engine = GameEngine::new();
+        engine.timestep.step = 0.125;
+        engine.create_scene_from_meta("S", "entity Probe { on Tick(dt) { takeDamage(1); } }").unwrap();
+        engine.load_scene("S").unwrap();
+        for _ in 0..frames { engine.advance(frame_time); }
+        let health = engine.scenes["S"].root.find_by_id("probe_1").unwrap().instance.as_ref().unwrap().health;
+        (engine.time.step_count, health, engine.time.alpha)
+    };
+    assert_eq!(run(0.5, 4), (16, 84, 0.0));
+    assert_eq!(run(0.0625, 32), (16, 84, 0.0));
+    assert_eq!(run(0.0625, 31), (15, 85, 0.5));
+
+    let mut engine = GameEngine::new();
+    engine.timestep = FixedTimestep { step: 0.125, max_steps: 3 };
+    assert_eq!(engine.advance(1.0), 3);
+    assert_eq!(engine.time.alpha, 0.0);
+    engine.set_time_scale(0.5);
+    assert_eq!(engine.advance(0.25), 1);
+    engine.pause();
+    assert_eq!(engine.advance(1.0), 0);
+    assert_eq!(engine.time.total_time, 0.5);
+    assert_eq!(engine.time.unscaled_total_time, 2.25);
+    assert_eq!(engine.time.frame_count, 3);
+
+    engine.resume();
+    engine.set_time_scale(1.0);
+    for step in [0.0, -0.125, f64::NAN, f64::INFINITY] {
+        engine.timestep.step = step;
+        assert_eq!(engine.advance(0.25), 0);
+    }
+    engine.timestep.step = 0.125;
+    assert_eq!(engine.advance(0.25), 2);
+    assert_eq!(engine.time.alpha, 0.0);
+}
+
+#[test]
+fn pause_and_time_scale_apply_to_variable_and_fixed_steps() {
+    let probe = |engine: &GameEngine| {
+        engine.scenes["S"].root.find_by_id("probe_1").unwrap().instance.as_ref().unwrap().health
+    };
+    let mut engine = GameEngine::new();
+    engine.timestep.step = 0.125;
+    engine.create_scene_from_meta("S", "entity Probe { on Tick(dt) { takeDamage(1); } }").unwrap();
+    engine.load_scene("S").unwrap();
+
+    engine.set_time_scale(0.5);
+    engine.update(0.5);
+    assert_eq!(engine.time.delta_time, 0.25);
+    assert_eq!(engine.advance(0.5), 2);
+    assert_eq!(probe(&engine), 97);
+
+    engine.pause();
+    engine.update(0.5);
+    assert_eq!(engine.advance(0.5), 0);
+    assert_eq!(probe(&engine), 97);
+    assert_eq!(engine.time.total_time, 0.5);
+    assert_eq!(engine.time.unscaled_total_time, 2.0);
+    assert_eq!(engine.time.frame_count, 4);
+
+    engine.resume();
+    engine.update(0.5);
+    assert_eq!(probe(&engine), 96);
+    assert_eq!(engine.time.total_time, 0.75);
+}
+
+#[test]
+fn world_transforms_propagate_only_through_dirty_subtrees() {
+    use experiment::components::Transform;
+    let node = |id: &str, x: f32| {
+        let mut n = Node::new(id.to_string(), id.to_string(), "Node".to_string());
+        n.set_transform(Transform::from_position(Vector3::new(x, 0.0, 0.0)));
+        n
+    };
+    let mut root = node("root", 1.0);
+    let mut arm = node("arm", 2.0);
+    arm.add_child(node("hand", 3.0));
+    root.add_child(arm);
+    root.add_child(node("leg", 5.0));
+    assert_eq!(root.propagate_transforms(), 4);
+    assert_eq!(root.find_by_id("hand").unwrap().global_transform().position, Vector3::new(6.0, 0.0, 0.0));
+    assert_eq!(root.propagate_transforms(), 0);
+
+    root.find_by_id_mut("arm").unwrap().transform_mut().position.y = 1.0;
+    assert_eq!(root.propagate_transforms(), 2);
+    assert_eq!(root.find_by_id("hand").unwrap().global_transform().position, Vector3::new(6.0, 1.0, 0.0));
+    assert_eq!(root.find_by_id("leg").unwrap().global_transform().position, Vector3::new(6.0, 0.0, 0.0));
+}
+
+#[test]
+fn node_paths_wildcards_and_reparenting_keep_world_transform() {
+    use experiment::components::Transform;
+    let node = |id: &str, name: &str, ty: &str, x: f32| {
+        let mut n = Node::new(id.to_string(), name.to_string(), ty.to_string());
+        n.set_transform(Transform::from_position(Vector3::new(x, 0.0, 0.0)));
+        n
+    };
+    let mut enemies = node("enemies", "Enemies", "Node", 10.0);
+    for i in 1..=3 {
+        let mut guard = node(&format!("guard_{}", i), &format!("Guard{}", i), "CharacterBody2D", i as f32);
+        if i != 2 { guard.add_to_group("patrol"); }
+        enemies.add_child(guard);
+    }
+    let mut level = node("level", "Level", "Node", 1.0);
+    level.add_child(enemies);
+    level.add_child(node("items", "Items", "Node", -4.0));
+    let mut root = node("root", "Root", "Node", 0.0);
+    root.add_child(level);
+
+    assert_eq!(root.get_node("Level/Enemies/Guard2").unwrap().id, "guard_2");
+    assert_eq!(root.get_node_from("guard_2", "../../Items").unwrap().id, "items");
+    let ids = |nodes: Vec<&Node>| nodes.iter().map(|n| n.id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids(root.get_nodes("Level/*/Guard?")), ["guard_1", "guard_2", "guard_3"]);
+    assert_eq!(ids(root.get_nodes("**/Items")), ["items"]);
+    assert_eq!(ids(root.get_nodes("**/..")), ["root", "level", "enemies"]);
+    assert_eq!(ids(root.get_nodes("Level/**/**")), ["level", "enemies", "guard_1", "guard_2", "guard_3", "items"]);
+    assert_eq!(ids(root.find_all_by_type("CharacterBody2D")).len(), 3);
+    assert_eq!(ids(root.get_nodes_in_group("patrol")), ["guard_1", "guard_3"]);
+    assert_eq!(root.path_of("guard_3").as_deref(), Some("Level/Enemies/Guard3"));
+
+    let before = root.compute_global_transform("guard_3").unwrap().position;
+    assert_eq!(before, Vector3::new(14.0, 0.0, 0.0));
+    root.reparent("guard_3", "items").unwrap();
+    assert_eq!(root.path_of("guard_3").as_deref(), Some("Level/Items/Guard3"));
+    assert_eq!(root.compute_global_transform("guard_3").unwrap().position, before);
+    root.propagate_transforms();
+    assert_eq!(root.find_by_id("guard_3").unwrap().global_transform().position, before);
+    assert!(root.reparent("level", "guard_1").is_err());
+    assert!(root.reparent("root", "items").is_err());
+}
+
+#[test]
+fn lifecycle_handlers_run_at_tree_and_frame_boundaries() {
+    let meta = "entity Bullet { components: [Health]; \
+        on Ready() { takeDamage(1); } \
+        on PhysicsProcess(dt) { takeDamage(10); } \
+        on Process(dt) { takeDamage(100); } \
+        on ExitTree() { takeDamage(1000); } } \
+        entity Mine { components: [Health]; on PhysicsProcess(dt) { queueFree(); } }";
+    let mut engine = GameEngine::new();
+    engine.timestep = FixedTimestep { step: 0.125, max_steps: 5 };
+    engine.create_scene_from_meta("level", meta).unwrap();
+    engine.create_scene_from_meta("menu", "").unwrap();
+    let hp = |engine: &GameEngine, id: &str| {
+        engine.scenes["level"].root.find_by_id(id).map(|n| n.instance.as_ref().unwrap().health)
+    };
+    assert!(!engine.scenes["level"].root.find_by_id("bullet_1").unwrap().is_inside_tree());
+    assert_eq!(hp(&engine, "bullet_1"), Some(100));
+
+    engine.load_scene("level").unwrap();
+    let bullet = engine.scenes["level"].root.find_by_id("bullet_1").unwrap();
+    assert!(bullet.is_inside_tree() && bullet.is_ready());
+    assert_eq!(hp(&engine, "bullet_1"), Some(99));
+
+    // Two physics steps and one rendered frame; the mine frees itself after the first step
+    assert_eq!(engine.advance(0.25), 2);
+    assert_eq!(hp(&engine, "bullet_1"), Some(99 - 20 - 100));
+    assert_eq!(hp(&engine, "mine_1"), None);
+
+    // Nodes spawned into a loaded scene enter the tree right away; deferred children after the step
+    let id = engine.spawn("Bullet", Vector3::zero()).unwrap();
+    assert_eq!(hp(&engine, &id), Some(99));
+    let root = &mut engine.scenes.get_scene_mut("level").unwrap().root;
+    root.find_by_id_mut("bullet_1").unwrap().add_child_deferred(Node::new("fx".into(), "Fx".into(), "Node".into()));
+    assert!(root.find_by_id("fx").is_none());
+    engine.queue_free("level", &id).unwrap();
+    engine.update(0.1);
+    let root = &engine.scenes["level"].root;
+    assert!(root.find_by_id(&id).is_none());
+    assert!(root.find_by_id("fx").unwrap().is_ready());
+
+    engine.load_scene("menu").unwrap();
+    assert_eq!(hp(&engine, "bullet_1"), Some(-21 - 110 - 1000));
+    assert!(!engine.scenes["level"].root.is_inside_tree());
+}
+
+#[test]
+fn scene_flags_gate_physics_steps_and_rendering() {
+    let meta = "entity Drone { components: [Transform, Sprite, Health]; \
+        on PhysicsProcess(dt) { takeDamage(1); } on Process(dt) { takeDamage(10); } }";
+    let mut engine = GameEngine::new();
+    engine.timestep = FixedTimestep { step: 0.25, max_steps: 5 };
+    engine.create_scene_from_meta("S", meta).unwrap();
+    engine.load_scene("S").unwrap();
+    let health = |engine: &GameEngine| engine.scenes["S"].root.find_by_id("drone_1").unwrap().instance.as_ref().unwrap().health;
+
+    engine.advance(0.5);
+    assert_eq!(health(&engine), 100 - 2 - 10);
+    assert_eq!(engine.render_items().len(), 1);
+    assert_eq!(engine.render_items()[0].node_id, "drone_1");
+
+    let scene = engine.scenes.get_scene_mut("S").unwrap();
+    scene.physics_enabled = false;
+    scene.render_enabled = false;
+    assert_eq!(engine.advance(0.5), 2);
+    assert_eq!(health(&engine), 88 - 10);
+    assert!(engine.render_items().is_empty());
+}
+
+#[test]
+fn scene_stack_additive_scenes_and_autoload_survive_transitions() {
+    let mut engine = GameEngine::new();
+    engine.timestep = FixedTimestep { step: 0.25, max_steps: 5 };
+    let music = engine.autoload_from_meta("entity Music { components: [Health]; \
+        on SceneEnter(scene) { takeDamage(1); } on SceneExit(scene) { takeDamage(10); } }");
+    assert_eq!(music, ["music_1"]);
+    engine.create_scene_from_meta("level", "entity Hero { components: [Sprite, Health]; \
+        on PhysicsProcess(dt) { takeDamage(1); } on SceneExit(scene) { takeDamage(50); } }").unwrap();
+    engine.create_scene_from_meta("pause", "entity Menu { components: [Sprite]; }").unwrap();
+    engine.create_scene_from_meta("hud", "entity Hud { components: [Sprite]; }").unwrap();
+    let music_hp = |e: &GameEngine| e.scenes.autoload.root.find_by_id("music_1").unwrap().instance.as_ref().unwrap().health;
+    let hero_hp = |e: &GameEngine| e.scenes["level"].root.find_by_id("hero_1").unwrap().instance.as_ref().unwrap().health;
+
+    engine.load_scene("level").unwrap();
+    engine.load_additive("hud").unwrap();
+    assert_eq!(music_hp(&engine), 98);
+    engine.advance(0.25);
+    assert_eq!(hero_hp(&engine), 99);
+
+    // Pause menu over the game: the game is drawn underneath but does not update
+    engine.push_scene("pause").unwrap();
+    assert_eq!(engine.scenes.loaded_scenes(), ["level", "pause", "hud"]);
+    engine.advance(0.25);
+    assert_eq!(hero_hp(&engine), 99);
+    let drawn: Vec<String> = engine.render_items().into_iter().map(|i| i.node_id).collect();
+    assert_eq!(drawn, ["hero_1", "menu_1", "hud_1"]);
+    assert!(engine.push_scene("hud").unwrap_err().contains("already loaded"));
+
+    assert_eq!(engine.pop_scene().unwrap(), "pause");
+    assert_eq!(music_hp(&engine), 97 - 10);
+    engine.advance(0.25);
+    assert_eq!(hero_hp(&engine), 98);
+    assert!(engine.unload_scene("level").unwrap_err().contains("not additive"));
+    assert!(engine.pop_scene().is_err());
+
+    // Changing scenes unloads the game and the HUD, the autoload node stays
+    engine.load_scene("pause").unwrap();
+    assert_eq!(engine.scenes.loaded_scenes(), ["pause"]);
+    assert_eq!(hero_hp(&engine), 48);
+    assert_eq!(music_hp(&engine), 87 - 20 - 1);
+    assert!(engine.scenes.autoload.root.find_by_id("music_1").unwrap().is_inside_tree());
+
+    // A stack left without an active scene (the fields are public) is an error, not a panic
+    engine.scenes.stack.push("level".to_string());
+    engine.scenes.active_scene = None;
+    assert!(engine.pop_scene().unwrap_err().contains("No active scene"));
+    assert_eq!(engine.scenes.stack, ["level"]);
+}
@@ -0,0 +1,25 @@
+use experiment::lint::{fix_corpus, lint_corpus, LintConfig};
+
+#[test]
+fn shipped_corpus_has_no_lint_errors() {
+    let report = lint_corpus("corpus", &LintConfig::default());
+    assert!(report.errors.is_empty());
+    assert!(!report.has_errors(), "{:?}", report.diagnostics);
+}
+
+#[test]
+fn fix_corpus_rewrites_only_fixable_files() {
+    let dir = std::env::temp_dir().join(format!("experiment_lint_fix_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("dead.meta"), "entity Mine {\n    on Tick() { if (0) { takeDamage(5); } }\n    on Hit() { }\n}\n").unwrap();
+    std::fs::write(dir.join("clean.meta"), "entity Rock { on Hit() { takeDamage(1); } }\n").unwrap();
+
+    let changed = fix_corpus(&dir.to_string_lossy(), &LintConfig::default()).unwrap();
+    assert_eq!(changed, vec!["dead.meta"]);
+    assert_eq!(std::fs::read_to_string(dir.join("dead.meta")).unwrap(), "entity Mine {\n    on Tick() {  }\n}\n");
+    // the emptied Tick is found on the next run
+    let report = lint_corpus(&dir.to_string_lossy(), &LintConfig::default());
+    assert!(report.diagnostics.iter().any(|d| d.rule == "empty-handler" && d.location == "Mine.Tick"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,174 @@
+use experiment::components::Vector3;
+use experiment::game_engine::{Component, GameEngine, Node};
+use experiment::meta_lang::parse_entities;
+use experiment::prefab::{Prefab, PrefabInstance, PropagateReport};
+use experiment::runtime::Value;
+use experiment::scene::Scene;
+use experiment::scene_file::SceneFile;
+
+fn texture(node: &Node) -> String {
+    match node.get_component("Sprite").unwrap().get_property("texture") {
+        Some(Value::Str(s)) => s.clone(),
+        other => panic!("no texture: {:?}", other),
+    }
+}
+
+fn hp(node: &Node) -> i64 {
+    match node.get_component("Health").unwrap().get_property("hp") {
+        Some(Value::Int(hp)) => *hp,
+        other => panic!("no hp: {:?}", other),
+    }
+}
+
+#[test]
+fn prefab_instances_keep_overrides_when_templates_change() {
+    let mut engine = GameEngine::new();
+    let mut gun = Node::new("gun".into(), "Gun".into(), "Sprite2D".into());
+    let mut sprite = Component::new("Sprite".to_string());
+    sprite.set_property("texture".to_string(), Value::Str("pistol.png".into()));
+    gun.add_component(sprite);
+    engine.scenes.prefabs.add(Prefab::new("Gun", gun));
+
+    let entity = &parse_entities("entity Enemy { components: [Transform, Health]; on Update(dt) { patrol(); } }")[0];
+    let mut enemy = Prefab::from_entity(entity);
+    let mut body = Node::new("body".into(), "Body".into(), "Node".into());
+    let mut gun_link = Node::new("gun".into(), "Gun".into(), "Node".into());
+    gun_link.prefab = Some(PrefabInstance { prefab: "Gun".to_string(), overrides: Vec::new() });
+    body.add_child(gun_link);
+    enemy.root.add_child(body);
+    engine.scenes.prefabs.add(enemy);
+
+    engine.scenes.add_scene(Scene::new("level".to_string()));
+    let first = engine.instance_prefab("level", "root_level", "Enemy").unwrap();
+    let second = engine.instance_prefab("level", "root_level", "Enemy").unwrap();
+    let manager = &mut engine.scenes;
+    assert_eq!((first.as_str(), second.as_str()), ("enemy_1", "enemy_2"));
+    manager.set_prefab_override("level", "enemy_2", "", "Health", "hp", Value::Int(50)).unwrap();
+    manager.set_prefab_override("level", "enemy_2", "", "Transform", "position", Value::Float(7.0)).unwrap();
+    manager.set_prefab_override("level", "enemy_2", "Body/Gun", "Sprite", "texture", Value::Str("rifle.png".into())).unwrap();
+    let root = &mut manager.get_scene_mut("level").unwrap().root;
+    root.find_by_id_mut("enemy_1").unwrap().add_child(Node::new("hat".into(), "Hat".into(), "Node".into()));
+    let gun_1 = root.find_by_id("enemy_1.body.gun").unwrap();
+    assert_eq!((texture(gun_1), gun_1.owner.as_deref()), ("pistol.png".to_string(), Some("enemy_1")));
+    assert_eq!(root.find_by_id("enemy_2").unwrap().transform().position, Vector3::new(7.0, 0.0, 0.0));
+
+    // Save and reload, then change both templates: every instance follows, overrides stay
+    let scene = manager.scenes.remove("level").unwrap();
+    manager.add_scene(Scene::from_text(&scene.to_text()).unwrap());
+    let set = |node: &mut Node, component: &str, key: &str, value: Value| {
+        node.components.get_mut(component).unwrap().set_property(key.to_string(), value);
+    };
+    set(&mut manager.prefabs.get_mut("Gun").unwrap().root, "Sprite", "texture", Value::Str("laser.png".into()));
+    set(&mut manager.prefabs.get_mut("Enemy").unwrap().root, "Health", "hp", Value::Int(200));
+    assert_eq!(manager.propagate_prefab("Gun").unwrap(), PropagateReport { updated: 2, skipped_live: Vec::new() });
+
+    let root = &manager.get_scene("level").unwrap().root;
+    assert_eq!(texture(root.find_by_id("enemy_1.body.gun").unwrap()), "laser.png");
+    assert_eq!(texture(root.find_by_id("enemy_2.body.gun").unwrap()), "rifle.png");
+    assert_eq!((hp(root.find_by_id("enemy_1").unwrap()), hp(root.find_by_id("enemy_2").unwrap())), (200, 50));
+    assert_eq!(root.find_by_id("enemy_2").unwrap().transform().position, Vector3::new(7.0, 0.0, 0.0));
+    assert!(root.find_by_id("hat").is_some());
+}
+
+#[test]
+fn prefab_instances_and_spawned_entities_share_id_numbering() {
+    let source = "entity Enemy { components: [Transform, Health]; on Hit() { takeDamage(5); } }";
+    let mut engine = GameEngine::new();
+    engine.create_scene_from_meta("level", source).unwrap();
+    engine.scenes.prefabs.add(Prefab::from_entity(&parse_entities(source)[0]));
+
+    let instanced = engine.instance_prefab("level", "root_level", "Enemy").unwrap();
+    let spawned = engine.spawn_in("level", "Enemy", Vector3::zero()).unwrap();
+    let again = engine.instance_prefab("level", "root_level", "Enemy").unwrap();
+    assert_eq!([instanced.as_str(), spawned.as_str(), again.as_str()], ["enemy_2", "enemy_3", "enemy_4"]);
+    let root = &engine.scenes["level"].root;
+    assert!(root.find_by_id("enemy_2").unwrap().prefab.is_some());
+    assert!(root.find_by_id("enemy_3").unwrap().prefab.is_none());
+    assert_eq!(root.find_all(|n| n.id == "enemy_2").len(), 1);
+}
+
+#[test]
+fn prefabs_instanced_into_a_loaded_scene_enter_the_tree() {
+    let source = r#"
+entity Enemy {
+    components: [Transform, Health];
+    on EnterTree() { takeDamage(1); }
+    on Ready() { takeDamage(10); }
+}
+"#;
+    let entity = &parse_entities(source)[0];
+    let mut enemy = Prefab::from_entity(entity);
+    let mut minion = Prefab::from_entity(entity).root;
+    minion.id = "minion".to_string();
+    enemy.root.add_child(minion);
+    let mut engine = GameEngine::new();
+    engine.scenes.add_scene(Scene::new("level".to_string()));
+    engine.scenes.get_scene_mut("level").unwrap().entities_meta = parse_entities(source);
+    engine.scenes.prefabs.add(enemy);
+    engine.load_scene("level").unwrap();
+
+    let id = engine.instance_prefab("level", "root_level", "Enemy").unwrap();
+    for node in [id.as_str(), "enemy_1.minion"] {
+        let n = engine.scenes["level"].root.find_by_id(node).unwrap();
+        assert!(n.is_inside_tree() && n.is_ready(), "{}", node);
+        assert_eq!(n.instance.as_ref().unwrap().health, 89, "{}", node);
+    }
+    assert!(engine.instance_prefab("level", "missing", "Enemy").is_err());
+    assert_eq!(engine.instance_prefab("level", "root_level", "Enemy").unwrap(), "enemy_2");
+}
+
+#[test]
+fn propagation_leaves_live_instances_alone() {
+    let source = "entity Enemy { components: [Health]; on Ready() { takeDamage(10); } }";
+    let mut engine = GameEngine::new();
+    engine.create_scene_from_meta("level", source).unwrap();
+    engine.create_scene_from_meta("menu", "entity Menu { }").unwrap();
+    engine.scenes.prefabs.add(Prefab::from_entity(&parse_entities(source)[0]));
+    engine.load_scene("level").unwrap();
+    let id = engine.instance_prefab("level", "root_level", "Enemy").unwrap();
+
+    let template = &mut engine.scenes.prefabs.get_mut("Enemy").unwrap().root;
+    template.components.get_mut("Health").unwrap().set_property("hp".to_string(), Value::Int(300));
+    assert_eq!(engine.scenes.propagate_prefab("Enemy").unwrap(), PropagateReport { updated: 0, skipped_live: vec![id.clone()] });
+    let live = engine.scenes["level"].root.find_by_id(&id).unwrap();
+    assert!(live.is_ready());
+    assert_eq!((hp(live), live.instance.as_ref().unwrap().health), (90, 90));
+    let library = engine.scenes.prefabs.clone();
+    let err = library.propagate(&mut engine.scenes.get_scene_mut("level").unwrap().root, "Enemy").unwrap_err();
+    assert!(err.contains("is live"), "{}", err);
+
+    // after unloading the scene, its instances follow the template
+    engine.load_scene("menu").unwrap();
+    assert_eq!(engine.scenes.propagate_prefab("Enemy").unwrap().updated, 1);
+    assert_eq!(hp(engine.scenes["level"].root.find_by_id(&id).unwrap()), 300);
+}
+
+#[test]
+fn failed_propagation_leaves_every_scene_unchanged() {
+    let mut crate_root = Node::new("crate".into(), "Crate".into(), "Node".into());
+    let mut lid = Node::new("lid".into(), "Lid".into(), "Node".into());
+    let mut sprite = Component::new("Sprite".to_string());
+    sprite.set_property("texture".to_string(), Value::Str("wood.png".into()));
+    lid.add_component(sprite.clone());
+    crate_root.add_component(sprite);
+    crate_root.add_child(lid);
+    let mut engine = GameEngine::new();
+    engine.scenes.prefabs.add(Prefab::new("Crate", crate_root));
+    for scene in ["a", "b"] {
+        engine.scenes.add_scene(Scene::new(scene.to_string()));
+        engine.instance_prefab(scene, &format!("root_{}", scene), "Crate").unwrap();
+    }
+    engine.scenes.set_prefab_override("b", "crate_1", "Lid", "Sprite", "texture", Value::Str("iron.png".into())).unwrap();
+
+    // the new template has no Lid, so scene b's override cannot be applied
+    let template = &mut engine.scenes.prefabs.get_mut("Crate").unwrap().root;
+    template.children.clear();
+    template.components.get_mut("Sprite").unwrap().set_property("texture".to_string(), Value::Str("steel.png".into()));
+    let err = engine.scenes.propagate_prefab("Crate").unwrap_err();
+    assert!(err.contains("Scene 'b'") && err.contains("no node 'Lid'"), "{}", err);
+    for scene in ["a", "b"] {
+        let root = &engine.scenes[scene].root;
+        assert_eq!(texture(root.find_by_id("crate_1").unwrap()), "wood.png");
+        assert!(root.find_by_id("crate_1.lid").is_some());
+    }
+}
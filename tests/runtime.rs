@@ -1,4 +1,4 @@
-use experiment::runtime::{EntityInstance, execute_event, Value};
+use experiment::runtime::{EntityInstance, execute_event};
 use experiment::meta_lang::Event;
 
 #[test]
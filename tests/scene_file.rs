@@ -0,0 +1,74 @@
+use experiment::components::{Quaternion, Vector3};
+use experiment::game_engine::{Component, GameEngine};
+use experiment::runtime::{SimpleEntity, Value};
+use experiment::scene::Scene;
+use experiment::scene_file::SceneFile;
+
+#[test]
+fn scenes_round_trip_through_text_binary_and_files() {
+    let meta = "entity Enemy { components: [Transform, Health, AI]; on Update(dt) { patrol(); } on Hit(other) {\n    if (other.tag == \"player\") { takeDamage(5); }\n} }";
+    let mut engine = GameEngine::new();
+    engine.create_scene_from_meta("level", meta).unwrap();
+    let guard = engine.spawn_in("level", "Enemy", Vector3::new(3.0, -1.5, 0.25)).unwrap();
+    let scene = engine.scenes.get_scene_mut("level").unwrap();
+    let node = scene.root.find_by_id_mut(&guard).unwrap();
+    node.add_to_group("patrol");
+    node.active = false;
+    node.transform_mut().rotation = Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), 0.7);
+    let mut memory = Component::new("Memory".to_string());
+    memory.set_property("last seen".to_string(), Value::EntitySnapshot(SimpleEntity { name: "Player".into(), tag: "player".into(), health: 80 }));
+    memory.set_property("note".to_string(), Value::Str("line\n\"quoted\"".into()));
+    node.add_component(memory);
+
+    let text = scene.to_text();
+    assert!(text.starts_with("version 3\n"));
+    let mut from_text = Scene::from_text(&text).unwrap();
+    assert_eq!(from_text.to_text(), text);
+    let from_binary = Scene::from_binary(&scene.to_binary()).unwrap();
+    assert_eq!(from_binary.to_text(), text);
+    assert!(scene.to_binary().len() < text.len());
+
+    let loaded = from_binary.root.find_by_id(&guard).unwrap();
+    assert!(!loaded.active && loaded.is_in_group("patrol"));
+    assert_eq!(loaded.transform(), scene.root.find_by_id(&guard).unwrap().transform());
+    assert!(matches!(loaded.get_component("Memory").unwrap().get_property("last seen"), Some(Value::EntitySnapshot(e)) if e.health == 80));
+    assert_eq!(from_binary.entities_meta[0].events[1].body, scene.entities_meta[0].events[1].body);
+
+    // Spawner numbering continues after loading
+    from_text.name = "copy".to_string();
+    engine.scenes.add_scene(from_text);
+    assert_eq!(engine.spawn_in("copy", "Enemy", Vector3::zero()).unwrap(), "enemy_3");
+
+    let dir = std::env::temp_dir().join(format!("scene_file_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut game_scene = Scene::new("menu".to_string()).with_physics(false);
+    game_scene.root.add_to_group("ui");
+    for name in ["menu.scene", "menu.sceneb"] {
+        let path = dir.join(name).to_string_lossy().into_owned();
+        game_scene.save(&path).unwrap();
+        let back = Scene::load(&path).unwrap();
+        assert!(!back.physics_enabled && back.render_enabled && back.root.is_in_group("ui"));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn version_1_scenes_are_migrated() {
+    // v1 kept positions only in the Transform component properties
+    let v1 = "version 1\nname \"old\"\nnode \"root\" \"Root\" \"Node\" {\n    node \"crate\" \"Crate\" \"Node\" {\n        component \"Transform\" {\n            position 4.0\n            y 2\n        }\n    }\n}\n";
+    let scene = Scene::from_text(v1).unwrap();
+    let node = scene.root.find_by_id("crate").unwrap();
+    assert_eq!(node.transform().position, Vector3::new(4.0, 2.0, 0.0));
+    assert!(scene.to_text().starts_with("version 3\n"));
+    assert!(Scene::from_text("version 4\nname \"new\"\n").unwrap_err().contains("not supported"));
+}
+
+#[test]
+fn out_of_range_versions_and_counters_are_rejected() {
+    for version in ["-1", "4294967299"] {
+        let text = format!("version {}\nname \"bad\"\n", version);
+        assert!(Scene::from_text(&text).unwrap_err().contains("`version`"), "{}", version);
+    }
+    let text = "version 3\nname \"bad\"\ncounter \"Enemy\" -2\nnode \"root\" \"Root\" \"Node\" {\n}\n";
+    assert_eq!(Scene::from_text(text).unwrap_err(), "counter 'Enemy' must be a non-negative integer");
+}
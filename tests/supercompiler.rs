@@ -0,0 +1,123 @@
+use experiment::ast::{stmt_to_string, Expr};
+use experiment::driving::KnownEnv;
+use experiment::cost_model::{Candidate, CostModel};
+use experiment::supercompiler::{simplify_corpus, simplify_corpus_with, specialize_corpus, SimplifyOptions, Specialization};
+use experiment::differential::{run_differential, DiffOptions};
+use experiment::incremental::Manifest;
+
+#[test]
+fn specializing_on_known_tags_removes_guards() {
+    let mut known = KnownEnv::new();
+    known.insert("other.tag".to_string(), Expr::Str("Enemy".to_string()));
+    let specs = vec![Specialization { entity: "Player".to_string(), event: "Collision".to_string(), known }];
+    let res = specialize_corpus("corpus", &specs);
+    assert_eq!(res.len(), 1);
+    let body: Vec<String> = res[0].residual.body.iter().map(stmt_to_string).collect();
+    assert_eq!(body, vec!["takeDamage(10);"]);
+    assert!(res[0].cost_after < res[0].cost_before);
+}
+
+#[test]
+fn simplified_corpus_is_not_more_expensive() {
+    let out = std::env::temp_dir().join(format!("experiment_supercompiler_out_{}", std::process::id()));
+    let (rep, _) = simplify_corpus("corpus", &out.to_string_lossy());
+    assert!(rep.cost_before > 0);
+    assert!(rep.cost_after <= rep.cost_before);
+    std::fs::remove_dir_all(&out).unwrap();
+}
+
+/// Extracts every repeated sequence, regardless of size.
+struct Eager;
+
+impl CostModel for Eager {
+    fn benefit(&self, c: &Candidate) -> f64 {
+        c.occurrences as f64
+    }
+}
+
+const HITS: &str = r#"
+entity A { on Hit() { takeDamage(10); collide(); } }
+entity B { on Hit() { takeDamage(25); collide(); } }
+entity C { on Update(dt) { move(velocity * dt); takeDamage(5); collide(); } }
+"#;
+
+#[test]
+fn repeated_sequences_become_parameterized_helpers() {
+    let dir = std::env::temp_dir().join(format!("experiment_helpers_in_{}", std::process::id()));
+    let out = std::env::temp_dir().join(format!("experiment_helpers_out_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("hits.meta"), HITS).unwrap();
+    let opts = SimplifyOptions { cost_model: Box::new(Eager), ..SimplifyOptions::default() };
+    let (rep, chosen) = simplify_corpus_with(&dir.to_string_lossy(), &out.to_string_lossy(), &opts);
+    assert_eq!(rep.helper_count, 1);
+    assert_eq!(rep.total_replacements, 3);
+    assert_eq!(chosen, vec![("takeDamage;collide".to_string(), "_helper_1".to_string())]);
+
+    let simplified = std::fs::read_to_string(out.join("hits.meta")).unwrap();
+    assert!(simplified.contains("_helper_1(10);"));
+    assert!(simplified.contains("_helper_1(25);"));
+    assert!(simplified.contains("move(velocity * dt);\n        _helper_1(5);"));
+    assert!(simplified.contains("function _helper_1(a) { takeDamage(a); collide();  }"));
+
+    let opts = DiffOptions { simplified_dir: out.to_string_lossy().to_string(), ..DiffOptions::default() };
+    if let Err(e) = run_differential(&dir.to_string_lossy(), &opts) { panic!("{}", e); }
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_dir_all(&out).unwrap();
+}
+
+#[test]
+fn default_cost_model_explains_its_choices() {
+    let dir = std::env::temp_dir().join(format!("experiment_costs_in_{}", std::process::id()));
+    let out = std::env::temp_dir().join(format!("experiment_costs_out_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("hits.meta"), HITS).unwrap();
+    let (rep, _) = simplify_corpus(&dir.to_string_lossy(), &out.to_string_lossy());
+    // two short calls with an argument are cheaper inline than behind a helper
+    assert_eq!(rep.helper_count, 0);
+    let d = rep.helpers.iter().find(|d| d.candidate.shape == "takeDamage/1;collide/0").unwrap();
+    assert_eq!(d.candidate.occurrences, 3);
+    assert_eq!(d.candidate.params, 1);
+    assert!(d.name.is_none());
+    assert!(d.justification.starts_with("kept inline"), "{}", d.justification);
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_dir_all(&out).unwrap();
+}
+
+#[test]
+fn only_changed_files_and_helper_dependents_are_reprocessed() {
+    let dir = std::env::temp_dir().join(format!("experiment_incremental_in_{}", std::process::id()));
+    let out = std::env::temp_dir().join(format!("experiment_incremental_out_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let _ = std::fs::remove_dir_all(&out);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.meta"), "entity A { on Hit() { takeDamage(10); collide(); } }").unwrap();
+    std::fs::write(dir.join("b.meta"), "entity B { on Hit() { takeDamage(25); collide(); } }").unwrap();
+    std::fs::write(dir.join("c.meta"), "entity C { on Update(dt) { move(velocity * dt); } }").unwrap();
+    let opts = SimplifyOptions { cost_model: Box::new(Eager), ..SimplifyOptions::default() };
+    let run = || simplify_corpus_with(&dir.to_string_lossy(), &out.to_string_lossy(), &opts).0.incremental;
+
+    let first = run();
+    assert_eq!(first.driven, vec!["a.meta", "b.meta", "c.meta"]);
+    assert_eq!(first.written, first.driven);
+    let manifest = Manifest::load(&out.to_string_lossy()).unwrap();
+    assert_eq!(manifest.helpers[0].sources, vec!["a.meta:A.Hit", "b.meta:B.Hit"]);
+    assert_eq!(manifest.output("c.meta").unwrap().helpers, Vec::<String>::new());
+
+    let second = run();
+    assert!(second.driven.is_empty() && second.written.is_empty());
+    assert_eq!(second.unchanged, vec!["a.meta", "b.meta", "c.meta"]);
+
+    // reformatting is not a change
+    std::fs::write(dir.join("c.meta"), "entity C {\n    on Update(dt) {\n        move(velocity * dt);\n    }\n}\n").unwrap();
+    assert_eq!(run().cached, vec!["a.meta", "b.meta", "c.meta"]);
+
+    // editing A removes the shared helper, so B (unchanged itself) is rewritten too
+    std::fs::write(dir.join("a.meta"), "entity A { on Hit() { takeDamage(10); explode(); } }").unwrap();
+    let third = run();
+    assert_eq!(third.driven, vec!["a.meta"]);
+    assert_eq!(third.written, vec!["a.meta", "b.meta"]);
+    assert_eq!(third.unchanged, vec!["c.meta"]);
+    assert!(!std::fs::read_to_string(out.join("b.meta")).unwrap().contains("_helper_1"));
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_dir_all(&out).unwrap();
+}
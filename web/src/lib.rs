@@ -1,17 +1,15 @@
 use wasm_bindgen::prelude::*;
-use web_sys::{window, HtmlCanvasElement, CanvasRenderingContext2d, Window};
+use web_sys::{window, HtmlCanvasElement, CanvasRenderingContext2d};
 use std::collections::HashMap;
-use wasm_bindgen::JsValue;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::JsCast;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 struct Event { name: String, params: Option<String>, body: String }
 
-fn trim_semicolon(s: &str) -> &str { let s = s.trim(); if s.ends_with(';') { &s[..s.len()-1] } else { s } }
-
 fn parse_entities(input: &str) -> Vec<(String, Vec<Event>)> {
     let mut res = Vec::new();
     let mut i = 0usize;
@@ -52,6 +50,7 @@ fn parse_entities(input: &str) -> Vec<(String, Vec<Event>)> {
     res
 }
 
+#[allow(dead_code)]
 #[derive(Clone)]
 struct EntityInstance {
     name: String,
@@ -79,6 +78,7 @@ impl EntityInstance {
     }
 }
 
+#[allow(dead_code)]
 enum Value { Float(f64), Int(i64), Str(String), EntitySnapshot(String, String, i32) }
 
 fn eval_condition(cond: &str, params: &HashMap<String, Value>) -> bool {
@@ -89,9 +89,7 @@ fn eval_condition(cond: &str, params: &HashMap<String, Value>) -> bool {
             let right = parts[1].trim_matches('"');
             if left.ends_with(".tag") {
                 let var = left.trim_end_matches(".tag");
-                if let Some(v) = params.get(var) {
-                    if let Value::EntitySnapshot(_, tag, _) = v { return tag == right; }
-                }
+                if let Some(Value::EntitySnapshot(_, tag, _)) = params.get(var) { return tag == right; }
             }
         }
     }
@@ -181,7 +179,7 @@ pub fn start_app(canvas_id: &str, script: &str) {
         }
     }
 
-    let mut last = js_sys::Date::now();
+    let last = js_sys::Date::now();
 
     // animation loop using recursion through request_animation_frame
     let ctx = Rc::new(ctx);
@@ -224,7 +222,7 @@ pub fn start_app(canvas_id: &str, script: &str) {
                 let p = plane_clone.borrow();
                 let w = canvas_clone.width() as f64;
                 let h = canvas_clone.height() as f64;
-                ctx_clone.set_fill_style(&JsValue::from_str("#0b1220"));
+                ctx_clone.set_fill_style_str("#0b1220");
                 ctx_clone.fill_rect(0.0, 0.0, w, h);
                 if p.name == "Cube" {
                     // rotate and draw centered square with simple 3-axis effect
@@ -237,23 +235,23 @@ pub fn start_app(canvas_id: &str, script: &str) {
                     let sy = p.rotation_x.cos();
                     ctx_clone.scale(sx, sy).ok();
                     let size = 50.0;
-                    ctx_clone.set_fill_style(&JsValue::from_str("#4ade80"));
+                    ctx_clone.set_fill_style_str("#4ade80");
                     ctx_clone.fill_rect(-size/2.0, -size/2.0, size, size);
                     ctx_clone.restore();
                 } else {
-                    ctx_clone.set_fill_style(&JsValue::from_str("#f97316"));
+                    ctx_clone.set_fill_style_str("#f97316");
                     ctx_clone.begin_path();
                     let x = p.position;
                     let y = h / 2.0;
-                    ctx_clone.move_to(x as f64, y as f64 - 10.0);
-                    ctx_clone.line_to(x as f64 + 30.0, y as f64);
-                    ctx_clone.line_to(x as f64, y as f64 + 10.0);
+                    ctx_clone.move_to(x, y - 10.0);
+                    ctx_clone.line_to(x + 30.0, y);
+                    ctx_clone.line_to(x, y + 10.0);
                     ctx_clone.close_path();
                     ctx_clone.fill();
                 }
 
                 // draw HUD
-                ctx_clone.set_fill_style(&JsValue::from_str("white"));
+                ctx_clone.set_fill_style_str("white");
                 ctx_clone.fill_text(
                     &format!("pos: {:.2} vel: {:.2} hp: {} rotX:{:.2} rotY:{:.2} rotZ:{:.2}",
                              p.position, p.velocity, p.health, p.rotation_x, p.rotation_y, p.rotation_z),